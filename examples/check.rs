@@ -18,7 +18,7 @@ fn main() {
                 session.send_line("n").unwrap();
             },
         ) {
-            Err(Error::Eof) => break,
+            Err(Error::Eof(_)) => break,
             result => result.unwrap(),
         };
     }
@@ -44,7 +44,7 @@ fn main() {
             )
             .await
             {
-                Err(Error::Eof) => break,
+                Err(Error::Eof(_)) => break,
                 result => result.unwrap(),
             };
         }
@@ -0,0 +1,230 @@
+//! Module contains functions to remove ANSI escape sequences from an output.
+//!
+//! Colored output puts sequences in the middle of words,
+//! which gets in the way of matching.
+//!
+//! ```
+//! use expectrl::ansi::strip;
+//!
+//! assert_eq!(strip(b"h\x1b[1mello\x1b[0m"), b"hello");
+//! ```
+//!
+//! See [crate::Plain] and [crate::Session::set_strip_ansi] to match a text without sequences.
+
+use crate::{needle::Match, Error};
+use std::borrow::Cow;
+
+/// Removes ANSI escape sequences from bytes.
+///
+/// Control characters like `\r` and `\n` are kept.
+/// An incomplete sequence at the end is removed as well.
+pub fn strip(bytes: &[u8]) -> Vec<u8> {
+    Stripper::new().strip(bytes)
+}
+
+/// Returns a filter for [crate::interact::InteractOptions::output_filter]
+/// which removes ANSI escape sequences.
+///
+/// A sequence may be split across chunks of an output.
+///
+/// ```no_run
+/// use expectrl::{ansi, interact::InteractOptions};
+///
+/// let opts = InteractOptions::terminal()
+///     .unwrap()
+///     .output_filter(ansi::strip_filter());
+/// ```
+pub fn strip_filter() -> impl FnMut(&[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    let mut stripper = Stripper::new();
+    move |bytes| Ok(Cow::Owned(stripper.strip(bytes)))
+}
+
+/// Stripper removes ANSI escape sequences from a stream of bytes.
+///
+/// Unlike [strip] it keeps a state between calls,
+/// so a sequence may be split across chunks.
+#[derive(Debug, Default, Clone)]
+pub struct Stripper {
+    state: State,
+}
+
+impl Stripper {
+    /// Creates a new stripper.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes ANSI escape sequences from a chunk of bytes.
+    pub fn strip(&mut self, bytes: &[u8]) -> Vec<u8> {
+        bytes.iter().copied().filter(|&b| self.advance(b)).collect()
+    }
+
+    /// Processes a byte and returns true if it's a part of a text.
+    fn advance(&mut self, b: u8) -> bool {
+        self.state = match (self.state, b) {
+            (State::Ground, 0x1b) => State::Escape,
+            (State::Ground, _) => return true,
+            (State::Str, 0x07) => State::Ground,
+            (State::Str, 0x1b) => State::StrEscape,
+            (State::Str, _) => State::Str,
+            (State::StrEscape, b'\\') => State::Ground,
+            // It's not a string terminator but a start of a new sequence.
+            (State::StrEscape, _) => {
+                self.state = State::Escape;
+                return self.advance(b);
+            }
+            (_, 0x1b) => State::Escape,
+            (State::Escape, b'[') => State::Csi,
+            (State::Escape, b']' | b'P' | b'X' | b'^' | b'_') => State::Str,
+            (State::Escape | State::EscapeIntermediate, 0x20..=0x2f) => State::EscapeIntermediate,
+            (State::Csi, 0x20..=0x3f) => State::Csi,
+            _ => State::Ground,
+        };
+
+        false
+    }
+
+    fn is_in_sequence(&self) -> bool {
+        self.state != State::Ground
+    }
+}
+
+/// A state of parsing of escape sequences.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    Csi,
+    /// OSC, DCS, SOS, PM and APC strings.
+    Str,
+    StrEscape,
+}
+
+/// StrippedText is a text without escape sequences
+/// which remembers where its bytes are in an original buffer.
+#[derive(Debug)]
+pub(crate) struct StrippedText {
+    text: Vec<u8>,
+    /// An index of each text byte in an original buffer.
+    positions: Vec<usize>,
+    /// A length of an original buffer without an incomplete sequence at the end.
+    complete_len: usize,
+}
+
+impl StrippedText {
+    pub(crate) fn new(bytes: &[u8]) -> Self {
+        let mut stripper = Stripper::new();
+        let mut text = Vec::with_capacity(bytes.len());
+        let mut positions = Vec::with_capacity(bytes.len());
+        let mut complete_len = 0;
+
+        for (i, &b) in bytes.iter().enumerate() {
+            if stripper.advance(b) {
+                text.push(b);
+                positions.push(i);
+            }
+
+            if !stripper.is_in_sequence() {
+                complete_len = i + 1;
+            }
+        }
+
+        Self {
+            text,
+            positions,
+            complete_len,
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// Converts a match in a text into a match in an original buffer.
+    ///
+    /// A match which starts at the beginning of a text covers the sequences before it,
+    /// and a match which reaches the end of a text covers the sequences after it.
+    /// Otherwise a match is bounded by its first and last bytes.
+    pub(crate) fn to_original(&self, m: Match) -> Match {
+        let start = match self.positions.get(m.start()) {
+            _ if m.start() == 0 => 0,
+            Some(&pos) => pos,
+            None => self.complete_len,
+        };
+
+        let end = match m.end() {
+            end if end == self.text.len() => self.complete_len,
+            0 => 0,
+            end => self.positions[end - 1] + 1,
+        };
+
+        Match::new(start, end.max(start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip() {
+        assert_eq!(strip(b"Hello World"), b"Hello World");
+        assert_eq!(strip(b"h\x1b[1mello\x1b[0m\r\n"), b"hello\r\n");
+        assert_eq!(strip(b"\x1b[38;5;208mx\x1b[?25l\x1b[2J\x1b[1;1H"), b"x");
+        assert_eq!(
+            strip(b"\x1b]0;title\x07a\x1b]8;;url\x1b\\b\x1b]8;;\x1b\\"),
+            b"ab"
+        );
+        assert_eq!(strip(b"\x1b(Ba\x1b7b\x1b8c\x1bM"), b"abc");
+        assert_eq!(strip(b"abc\x1b[1"), b"abc");
+
+        // A string interrupted by a new sequence.
+        assert_eq!(strip(b"\x1b]0;title\x1b[31mred"), b"red");
+    }
+
+    #[test]
+    fn test_stripper_keeps_state() {
+        let mut stripper = Stripper::new();
+        assert_eq!(stripper.strip(b"a\x1b"), b"a");
+        assert_eq!(stripper.strip(b"[3"), b"");
+        assert_eq!(stripper.strip(b"1mb\x1b]0;"), b"b");
+        assert_eq!(stripper.strip(b"title\x1b"), b"");
+        assert_eq!(stripper.strip(b"\\c"), b"c");
+    }
+
+    #[test]
+    fn test_strip_filter() {
+        let mut filter = strip_filter();
+        assert_eq!(filter(b"h\x1b[").unwrap().as_ref(), b"h");
+        assert_eq!(filter(b"1mi").unwrap().as_ref(), b"i");
+    }
+
+    #[test]
+    fn test_stripped_text_to_original() {
+        let buf = b"x \x1b[31mhello\x1b[0m world\x1b[0m\x1b[";
+        let text = StrippedText::new(buf);
+        assert_eq!(text.as_bytes(), b"x hello world");
+
+        // "hello"
+        let m = text.to_original(Match::new(2, 7));
+        assert_eq!(&buf[m.start()..m.end()], b"hello");
+
+        // "world" reaches the end of a text.
+        let m = text.to_original(Match::new(8, 13));
+        assert_eq!(&buf[m.start()..m.end()], b"world\x1b[0m");
+
+        // An empty match at the end.
+        assert_eq!(text.to_original(Match::new(13, 13)), Match::new(26, 26));
+        assert_eq!(text.to_original(Match::new(0, 0)), Match::new(0, 0));
+
+        // "x" starts at the beginning of a text.
+        let text = StrippedText::new(b"\x1b[1mx\x1b[0my");
+        assert_eq!(text.to_original(Match::new(0, 1)), Match::new(0, 5));
+
+        let text = StrippedText::new(b"\x1b[1m");
+        assert_eq!(text.as_bytes(), b"");
+        assert_eq!(text.to_original(Match::new(0, 0)), Match::new(0, 4));
+    }
+}
@@ -0,0 +1,286 @@
+//! Module contains a [SessionBuilder] which configures a process before it's spawned.
+
+use crate::{
+    error::Error, repl::ReplSession, session::command_key_map, stderr::StderrFifo,
+    stream::nix_error_to_io, Session,
+};
+use nix::{
+    libc::STDIN_FILENO,
+    sys::{
+        ptrace,
+        signal::Signal,
+        termios::{self, LocalFlags, OutputFlags, SetArg},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
+use std::{ffi::OsStr, os::unix::process::CommandExt, path::Path, process::Command};
+
+/// SessionBuilder configures a command and a pseudo terminal before a process is spawned.
+///
+/// A lot of programs read a window size and terminal modes only once at startup,
+/// so setting them after [Session::spawn] (e.g. via `set_echo`) is often too late.
+/// The builder applies them in a child process right before `exec`.
+///
+/// ```no_run
+/// use expectrl::SessionBuilder;
+///
+/// let mut session = SessionBuilder::new("vim")
+///     .arg("file.txt")
+///     .env_clear()
+///     .term("xterm-256color")
+///     .window_size(120, 40)
+///     .echo(true)
+///     .spawn()
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SessionBuilder {
+    command: Command,
+    window_size: Option<(u16, u16)>,
+    echo: Option<bool>,
+    canonical: Option<bool>,
+    onlcr: Option<bool>,
+    separate_stderr: bool,
+    env_clear: bool,
+}
+
+impl SessionBuilder {
+    /// Creates a builder for a program.
+    ///
+    /// The program is searched in `PATH` as [std::process::Command] does.
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
+        Self::from(Command::new(program))
+    }
+
+    /// Adds an argument to a program.
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.command.arg(arg);
+        self
+    }
+
+    /// Adds a list of arguments to a program.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.command.args(args);
+        self
+    }
+
+    /// Sets an environment variable.
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(mut self, key: K, value: V) -> Self {
+        self.command.env(key, value);
+        self
+    }
+
+    /// Removes an environment variable.
+    pub fn env_remove<K: AsRef<OsStr>>(mut self, key: K) -> Self {
+        self.command.env_remove(key);
+        self
+    }
+
+    /// Clears an environment inherited from the current process.
+    ///
+    /// Only variables set by [SessionBuilder::env] and [SessionBuilder::term] will be passed to a process.
+    pub fn env_clear(mut self) -> Self {
+        self.command.env_clear();
+        self.env_clear = true;
+        self
+    }
+
+    /// Sets a working directory of a process.
+    pub fn current_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.command.current_dir(dir);
+        self
+    }
+
+    /// Sets a `TERM` environment variable.
+    pub fn term<S: AsRef<OsStr>>(self, term: S) -> Self {
+        self.env("TERM", term)
+    }
+
+    /// Sets a window size of a pseudo terminal.
+    ///
+    /// By default it's 80 columns and 24 rows.
+    ///
+    /// A process is held right after `exec` until the size is set, so a program sees it from the start.
+    /// It's done by tracing a process until then,
+    /// so a set-user-ID program (e.g. `sudo`) doesn't get its privileges.
+    pub fn window_size(mut self, cols: u16, rows: u16) -> Self {
+        self.window_size = Some((cols, rows));
+        self
+    }
+
+    /// Turns on or off an `ECHO` mode of a terminal.
+    ///
+    /// By default echo is turned off.
+    pub fn echo(mut self, on: bool) -> Self {
+        self.echo = Some(on);
+        self
+    }
+
+    /// Turns on or off a canonical (`ICANON`) mode of a terminal,
+    /// in which input is delivered to a process line by line.
+    pub fn canonical(mut self, on: bool) -> Self {
+        self.canonical = Some(on);
+        self
+    }
+
+    /// Turns on or off an `ONLCR` mode of a terminal,
+    /// in which `\n` in an output of a process is translated to `\r\n`.
+    pub fn onlcr(mut self, on: bool) -> Self {
+        self.onlcr = Some(on);
+        self
+    }
+
+    /// Redirects a stderr of a process to a separate pipe.
+    ///
+    /// See [Session::spawn_with_stderr].
+    pub fn separate_stderr(mut self, on: bool) -> Self {
+        self.separate_stderr = on;
+        self
+    }
+
+    /// Spawns a process with a given configuration.
+    pub fn spawn(self) -> Result<Session, Error> {
+        let window_size = self.window_size;
+        let separate_stderr = self.separate_stderr;
+        let env_clear = self.env_clear;
+        let mut command = self.into_command();
+
+        // Session::spawn can't tell that an environment was cleared,
+        // so it would pick up an inherited TERM.
+        let key_map = if env_clear {
+            Some(command_key_map(&command, false))
+        } else {
+            None
+        };
+
+        let stderr = if separate_stderr {
+            let fifo = StderrFifo::new()?;
+            fifo.redirect(&mut command);
+            Some(fifo)
+        } else {
+            None
+        };
+
+        if window_size.is_some() {
+            hold_after_exec(&mut command);
+        }
+
+        let mut session = Session::spawn(command)?;
+
+        if let Some(fifo) = stderr {
+            session.set_stderr(fifo)?;
+        }
+
+        if let Some(key_map) = key_map {
+            session.set_key_map(key_map);
+        }
+
+        if let Some((cols, rows)) = window_size {
+            let status = waitpid(session.pid(), Some(WaitPidFlag::WUNTRACED))?;
+            let result = session.set_window_size(cols, rows);
+            release(session.pid(), status)?;
+            result?;
+        }
+
+        Ok(session)
+    }
+
+    /// Spawns a process as a [ReplSession].
+    ///
+    /// It doesn't wait for a first prompt.
+    pub fn spawn_repl<P: AsRef<str>, Q: AsRef<str>>(
+        self,
+        prompt: P,
+        quit_command: Option<Q>,
+    ) -> Result<ReplSession, Error> {
+        let session = self.spawn()?;
+        ReplSession::new(session, prompt, quit_command)
+    }
+
+    fn into_command(self) -> Command {
+        let Self {
+            mut command,
+            echo,
+            canonical,
+            onlcr,
+            ..
+        } = self;
+
+        if echo.is_some() || canonical.is_some() || onlcr.is_some() {
+            // SAFETY: the closure is called after fork,
+            // it only calls tcgetattr/tcsetattr which are async-signal-safe.
+            unsafe {
+                command.pre_exec(move || {
+                    set_terminal(echo, canonical, onlcr).map_err(nix_error_to_io)
+                });
+            }
+        }
+
+        command
+    }
+}
+
+impl From<Command> for SessionBuilder {
+    fn from(command: Command) -> Self {
+        Self {
+            command,
+            window_size: None,
+            echo: None,
+            canonical: None,
+            onlcr: None,
+            separate_stderr: false,
+            env_clear: false,
+        }
+    }
+}
+
+/// Makes a child stop right after `exec`, before a program is run.
+///
+/// [ptyprocess::PtyProcess::spawn] resets a window size to a default one once a child is executed,
+/// so a size set before `exec` could be overridden before a program reads it.
+/// A traced process is stopped by `SIGTRAP` on `exec`,
+/// which gives a parent a chance to set a size, see [release].
+fn hold_after_exec(command: &mut Command) {
+    // SAFETY: the closure is called after fork,
+    // it only calls ptrace which is async-signal-safe.
+    unsafe {
+        command.pre_exec(|| ptrace::traceme().map_err(nix_error_to_io));
+    }
+}
+
+/// Lets a process which was stopped on `exec` run.
+fn release(pid: Pid, status: WaitStatus) -> Result<(), Error> {
+    match status {
+        // Any other signal is handed over to a process.
+        WaitStatus::Stopped(_, Signal::SIGTRAP) => ptrace::detach(pid, None)?,
+        WaitStatus::Stopped(_, signal) => ptrace::detach(pid, signal)?,
+        // A process was killed before it was started.
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn set_terminal(
+    echo: Option<bool>,
+    canonical: Option<bool>,
+    onlcr: Option<bool>,
+) -> nix::Result<()> {
+    let mut flags = termios::tcgetattr(STDIN_FILENO)?;
+    if let Some(on) = echo {
+        flags.local_flags.set(LocalFlags::ECHO, on);
+    }
+    if let Some(on) = canonical {
+        flags.local_flags.set(LocalFlags::ICANON, on);
+    }
+    if let Some(on) = onlcr {
+        flags.output_flags.set(OutputFlags::ONLCR, on);
+    }
+
+    termios::tcsetattr(STDIN_FILENO, SetArg::TCSANOW, &flags)
+}
@@ -83,7 +83,7 @@ macro_rules! check {
     (@case $session:expr, (), ($($tail:tt)*), ($($default:tt)*)) => {
         // last point of @case
         // call code generation via @branch
-        $crate::check!(@branch $session, ($($tail)*), ($($default)*))
+        $crate::check!(@branch $session, 0usize, ($($tail)*), ($($default)*))
     };
     // We need to use a variable for pattern mathing,
     // user may chose to drop var name using a placeholder '_',
@@ -100,25 +100,27 @@ macro_rules! check {
     //
     // The question is which solution is more effichient.
     // I took the following approach because there's no chance we influence user's land via the variable name we pick.
-    (@branch $session:expr, ($var:tt = $exp:expr => $body:tt, $($tail:tt)*), ($($default:tt)*)) => {
+    //
+    // An index of a branch is counted in the recursion and it's set as a needle index of a Found.
+    (@branch $session:expr, $index:expr, ($var:tt = $exp:expr => $body:tt, $($tail:tt)*), ($($default:tt)*)) => {
         match $session.check($exp) {
             result if result.as_ref().map(|found| !found.is_empty()).unwrap_or(false) => {
-                let $var = result.unwrap();
+                let $var = result.unwrap().with_needle_index($index);
                 $body;
                 #[allow(unreachable_code)]
                 Ok(())
             }
             Ok(_) => {
-                $crate::check!(@branch $session, ($($tail)*), ($($default)*))
+                $crate::check!(@branch $session, $index + 1, ($($tail)*), ($($default)*))
             }
             Err(err) => Err(err),
         }
     };
-    (@branch $session:expr, (), ($default:tt)) => {
+    (@branch $session:expr, $index:expr, (), ($default:tt)) => {
         // A standart default branch
         $default
     };
-    (@branch $session:expr, ($($tail:tt)*), ($($default:tt)*)) => {
+    (@branch $session:expr, $index:expr, ($($tail:tt)*), ($($default:tt)*)) => {
         compile_error!(
             concat!(
                 "No supported syntax tail=(",
@@ -196,7 +198,7 @@ macro_rules! check {
     (@case $session:expr, (), ($($tail:tt)*), ($($default:tt)*)) => {
         // last point of @case
         // call code generation via @branch
-        $crate::check!(@branch $session, ($($tail)*), ($($default)*))
+        $crate::check!(@branch $session, 0usize, ($($tail)*), ($($default)*))
     };
     // We need to use a variable for pattern mathing,
     // user may chose to drop var name using a placeholder '_',
@@ -213,25 +215,27 @@ macro_rules! check {
     //
     // The question is which solution is more effichient.
     // I took the following approach because there's no chance we influence user's land via the variable name we pick.
-    (@branch $session:expr, ($var:tt = $exp:expr => $body:tt, $($tail:tt)*), ($($default:tt)*)) => {
+    //
+    // An index of a branch is counted in the recursion and it's set as a needle index of a Found.
+    (@branch $session:expr, $index:expr, ($var:tt = $exp:expr => $body:tt, $($tail:tt)*), ($($default:tt)*)) => {
         match $session.check($exp).await {
             result if result.as_ref().map(|found| !found.is_empty()).unwrap_or(false) => {
-                let $var = result.unwrap();
+                let $var = result.unwrap().with_needle_index($index);
                 $body;
                 #[allow(unreachable_code)]
                 Ok(())
             }
             Ok(_) => {
-                $crate::check!(@branch $session, ($($tail)*), ($($default)*))
+                $crate::check!(@branch $session, $index + 1, ($($tail)*), ($($default)*))
             }
             Err(err) => Err(err),
         }
     };
-    (@branch $session:expr, (), ($default:tt)) => {
+    (@branch $session:expr, $index:expr, (), ($default:tt)) => {
         // A standart default branch
         $default
     };
-    (@branch $session:expr, ($($tail:tt)*), ($($default:tt)*)) => {
+    (@branch $session:expr, $index:expr, ($($tail:tt)*), ($($default:tt)*)) => {
         compile_error!(
             concat!(
                 "No supported syntax tail=(",
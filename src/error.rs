@@ -1,7 +1,12 @@
+use crate::Found;
 use std::error;
 use std::fmt;
 use std::fmt::Display;
 use std::io;
+use std::time::Duration;
+
+#[cfg(unix)]
+use ptyprocess::WaitStatus;
 
 /// An main error type used in [crate].
 #[derive(Debug)]
@@ -13,8 +18,27 @@ pub enum Error {
     Win(conpty::Error),
     CommandParsing,
     RegexParsing,
-    ExpectTimeout,
-    Eof,
+    /// A key notation can't be parsed, it holds the part which is wrong.
+    ///
+    /// See [crate::Key::parse_sequence].
+    KeyParsing(String),
+    /// A timeout was reached before a needle was matched.
+    ExpectTimeout(Box<ExpectDetails>),
+    /// An EOF was reached before a needle was matched.
+    Eof(Box<ExpectDetails>),
+    /// A buffer exceeded its maximum size before a needle was matched.
+    ///
+    /// See [crate::session::DiscardPolicy].
+    BufferOverflow(Box<ExpectDetails>),
+    /// A guard needle was matched while expecting another one.
+    ///
+    /// See [crate::session::Session::expect_guarded].
+    GuardMatched(Box<Found>),
+    /// A stderr of a process is not captured.
+    ///
+    /// See [crate::Session::spawn_with_stderr].
+    #[cfg(unix)]
+    StderrNotCaptured,
     Other(String),
 }
 
@@ -28,15 +52,141 @@ impl Display for Error {
             Error::Win(err) => write!(f, "Win error {}", err),
             Error::CommandParsing => write!(f, "Can't parse a command string, please check it out"),
             Error::RegexParsing => write!(f, "Can't parse a regex expression"),
-            Error::ExpectTimeout => write!(f, "Reached a timeout for expect type of command"),
+            Error::KeyParsing(notation) => write!(f, "Can't parse a key notation {:?}", notation),
+            Error::ExpectTimeout(details) => {
+                write!(
+                    f,
+                    "Reached a timeout for expect type of command; {}",
+                    details
+                )
+            }
+            #[cfg(unix)]
+            Error::StderrNotCaptured => write!(
+                f,
+                "A stderr is not captured, use Session::spawn_with_stderr"
+            ),
             Error::Other(message) => write!(f, "Error {}", message),
-            Error::Eof => write!(f, "EOF was reached; the read may successed later"),
+            Error::BufferOverflow(details) => {
+                write!(f, "A buffer reached its maximum size; {}", details)
+            }
+            Error::GuardMatched(found) => write!(
+                f,
+                "A guard was matched while expecting; matched {:?}",
+                String::from_utf8_lossy(found.first())
+            ),
+            Error::Eof(details) => write!(
+                f,
+                "EOF was reached; the read may successed later; {}",
+                details
+            ),
         }
     }
 }
 
 impl error::Error for Error {}
 
+/// ExpectDetails describes a state of a failed expectation.
+///
+/// It's carried by [Error::ExpectTimeout], [Error::Eof] and [Error::BufferOverflow].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectDetails {
+    needle: String,
+    buffer: Vec<u8>,
+    buffer_len: usize,
+    elapsed: Duration,
+    #[cfg(unix)]
+    status: Option<WaitStatus>,
+}
+
+impl ExpectDetails {
+    /// A maximum number of bytes of a pending buffer which are kept.
+    pub const BUFFER_LIMIT: usize = 1024;
+
+    /// Creates a description of a failure.
+    ///
+    /// Only the last [ExpectDetails::BUFFER_LIMIT] bytes of a buffer are kept.
+    pub(crate) fn new(needle: String, buffer: &[u8], elapsed: Duration) -> Self {
+        let start = buffer.len().saturating_sub(Self::BUFFER_LIMIT);
+        Self {
+            needle,
+            buffer: buffer[start..].to_vec(),
+            buffer_len: buffer.len(),
+            elapsed,
+            #[cfg(unix)]
+            status: None,
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn set_status(&mut self, status: Option<WaitStatus>) {
+        self.status = status;
+    }
+
+    /// Returns a description of a needle which was expected, see [crate::Needle::description].
+    pub fn needle(&self) -> &str {
+        &self.needle
+    }
+
+    /// Returns a pending buffer which wasn't matched.
+    ///
+    /// It might be truncated, in such case the beginning of the buffer is dropped.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Returns a length of a pending buffer before it was truncated.
+    pub fn buffer_len(&self) -> usize {
+        self.buffer_len
+    }
+
+    /// Verifies if a buffer was truncated.
+    pub fn is_truncated(&self) -> bool {
+        self.buffer.len() < self.buffer_len
+    }
+
+    /// Returns a time spent on a expectation.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns a status of a process at the moment of a failure.
+    ///
+    /// The status is not collected, so [crate::Session::wait] can still be called.
+    /// It's `None` if a session doesn't know its process or it couldn't be retrieved.
+    #[cfg(unix)]
+    pub fn status(&self) -> Option<WaitStatus> {
+        self.status
+    }
+}
+
+impl Display for ExpectDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {} for {:?}", self.needle, self.elapsed)?;
+
+        #[cfg(unix)]
+        if let Some(status) = self.status {
+            write!(f, ", process status {:?}", status)?;
+        }
+
+        if self.is_truncated() {
+            write!(
+                f,
+                ", pending buffer ({} bytes, last {} shown) {:?}",
+                self.buffer_len,
+                self.buffer.len(),
+                String::from_utf8_lossy(&self.buffer)
+            )
+        } else {
+            write!(
+                f,
+                ", pending buffer ({} bytes) {:?}",
+                self.buffer_len,
+                String::from_utf8_lossy(&self.buffer)
+            )
+        }
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
         Self::IO(err)
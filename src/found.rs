@@ -1,4 +1,4 @@
-use crate::needle::Match;
+use crate::needle::{Match, MatchGroups};
 
 /// Found is a represention of a matched pattern.
 ///
@@ -7,12 +7,58 @@ use crate::needle::Match;
 pub struct Found {
     buf: Vec<u8>,
     matches: Vec<Match>,
+    groups: Vec<MatchGroups>,
+    needle_index: Option<usize>,
 }
 
 impl Found {
     /// New returns an instance of Found.
+    ///
+    /// Each match is considered as a match without capture groups.
     pub(crate) fn new(buf: Vec<u8>, matches: Vec<Match>) -> Self {
-        Self { buf, matches }
+        let groups = matches.iter().cloned().map(MatchGroups::from).collect();
+        Self {
+            buf,
+            matches,
+            groups,
+            needle_index: None,
+        }
+    }
+
+    /// Sets capture groups of the matches.
+    pub(crate) fn with_groups(mut self, groups: Vec<MatchGroups>) -> Self {
+        self.needle_index = groups.first().and_then(|groups| groups.needle_index());
+        self.groups = groups;
+        self
+    }
+
+    /// Sets an index of a needle which was matched.
+    ///
+    /// It's used by [crate::check!] macros to set an index of a matched branch.
+    #[doc(hidden)]
+    pub fn with_needle_index(mut self, index: usize) -> Self {
+        self.needle_index = Some(index);
+        self
+    }
+
+    /// Returns an index of a needle which was matched.
+    ///
+    /// It's set by [crate::Any] to an index of the matched alternative,
+    /// and by [crate::check!] macros to an index of the matched branch.
+    /// Otherwise it's `None`.
+    ///
+    /// ```
+    /// # #[cfg(not(feature = "async"))]
+    /// # {
+    /// use expectrl::Any;
+    ///
+    /// let mut p = expectrl::spawn("echo 'Permission denied'").unwrap();
+    /// let m = p.expect(Any(["password:", "denied", "$ "])).unwrap();
+    /// assert_eq!(m.needle_index(), Some(1));
+    /// # }
+    /// ```
+    pub fn needle_index(&self) -> Option<usize> {
+        self.needle_index
     }
 
     /// is_empty verifies if any matches were actually found.
@@ -34,6 +80,44 @@ impl Found {
             .collect()
     }
 
+    /// Get returns a capture group by index of the first match.
+    ///
+    /// The group with index 0 is the whole match.
+    ///
+    /// ```
+    /// # #[cfg(not(feature = "async"))]
+    /// # {
+    /// let mut p = expectrl::spawn("echo 'port: 8080'").unwrap();
+    /// let m = p.expect(expectrl::Regex(r"port: (\d+)\r")).unwrap();
+    /// assert_eq!(m.get(1), Some(&b"8080"[..]));
+    /// # }
+    /// ```
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        self.captures().next()?.get(index)
+    }
+
+    /// Name returns a named capture group of the first match.
+    ///
+    /// ```
+    /// # #[cfg(not(feature = "async"))]
+    /// # {
+    /// let mut p = expectrl::spawn("echo 'user=root'").unwrap();
+    /// let m = p.expect(expectrl::Regex(r"user=(?P<user>\w+)\r")).unwrap();
+    /// assert_eq!(m.name("user"), Some(&b"root"[..]));
+    /// # }
+    /// ```
+    pub fn name(&self, name: &str) -> Option<&[u8]> {
+        self.captures().next()?.name(name)
+    }
+
+    /// Captures returns capture groups of each match separately.
+    pub fn captures(&self) -> impl Iterator<Item = Captures<'_>> {
+        self.groups.iter().map(move |groups| Captures {
+            buf: &self.buf,
+            groups,
+        })
+    }
+
     /// before returns a bytes before match.
     pub fn before(&self) -> &[u8] {
         &self.buf[..self.left_most_index()]
@@ -62,6 +146,40 @@ impl Found {
     }
 }
 
+/// Captures is a set of capture groups of a single match.
+#[derive(Debug, Clone, Copy)]
+pub struct Captures<'a> {
+    buf: &'a [u8],
+    groups: &'a MatchGroups,
+}
+
+impl<'a> Captures<'a> {
+    /// Get returns a capture group by index.
+    ///
+    /// The group with index 0 is the whole match.
+    /// It returns `None` if there's no such group or it didn't participate in the match.
+    pub fn get(&self, index: usize) -> Option<&'a [u8]> {
+        let m = self.groups.get(index)?;
+        self.buf.get(m.start()..m.end())
+    }
+
+    /// Name returns a capture group by its name.
+    pub fn name(&self, name: &str) -> Option<&'a [u8]> {
+        let m = self.groups.name(name)?;
+        self.buf.get(m.start()..m.end())
+    }
+
+    /// Len returns a number of capture groups including the ones which didn't participate in the match.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// is_empty verifies if there's no capture groups.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
 impl IntoIterator for Found {
     type Item = Vec<u8>;
     type IntoIter = std::vec::IntoIter<Vec<u8>>;
@@ -88,6 +206,53 @@ impl<'a> IntoIterator for &'a Found {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_captures() {
+        let groups = vec![
+            MatchGroups::new(
+                vec![Some(Match::new(0, 7)), Some(Match::new(0, 3)), None],
+                vec![None, Some("key".to_string()), Some("value".to_string())],
+            ),
+            MatchGroups::new(
+                vec![
+                    Some(Match::new(8, 13)),
+                    Some(Match::new(8, 10)),
+                    Some(Match::new(11, 13)),
+                ],
+                vec![None, Some("key".to_string()), Some("value".to_string())],
+            ),
+        ];
+        let found = Found::new(
+            b"asd=123 qw=12".to_vec(),
+            groups.iter().flat_map(|g| g.matches().cloned()).collect(),
+        )
+        .with_groups(groups);
+
+        assert_eq!(found.get(0), Some(&b"asd=123"[..]));
+        assert_eq!(found.get(1), Some(&b"asd"[..]));
+        assert_eq!(found.get(2), None);
+        assert_eq!(found.get(3), None);
+        assert_eq!(found.name("key"), Some(&b"asd"[..]));
+        assert_eq!(found.name("value"), None);
+        assert_eq!(found.name("missing"), None);
+
+        let captures = found.captures().collect::<Vec<_>>();
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures[1].len(), 3);
+        assert_eq!(captures[1].get(0), Some(&b"qw=12"[..]));
+        assert_eq!(captures[1].name("key"), Some(&b"qw"[..]));
+        assert_eq!(captures[1].name("value"), Some(&b"12"[..]));
+    }
+
+    #[test]
+    fn test_captures_without_groups() {
+        let found = Found::new(b"Hello World".to_vec(), vec![Match::new(6, 11)]);
+        assert_eq!(found.get(0), Some(&b"World"[..]));
+        assert_eq!(found.get(1), None);
+        assert_eq!(found.name("any"), None);
+        assert_eq!(found.captures().count(), 1);
+    }
+
     #[test]
     fn test_iterator_on_found() {
         assert_eq!(
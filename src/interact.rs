@@ -1,25 +1,37 @@
 //! This module contains a [InteractOptions] which allows a castomization of
 //! [crate::Session::interact] flow.
 
-use crate::{session::Session, ControlCode, Error};
+use crate::{
+    needle::{flatten_groups, NeedleContext},
+    session::Session,
+    ControlCode, Error,
+};
 use std::{
     borrow::Cow,
+    cell::Cell,
     collections::HashMap,
     io::{self, Write},
+    time::Instant,
 };
 
 #[cfg(unix)]
-use crate::{stream::Stream, WaitStatus};
+use crate::{session::ProcessStream, stream::Stream, WaitStatus};
 #[cfg(unix)]
 use nix::{
     libc::STDIN_FILENO,
-    sys::termios,
+    sys::{
+        signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
+        termios,
+    },
     unistd::{dup, isatty},
 };
 #[cfg(unix)]
 use ptyprocess::set_raw;
 #[cfg(unix)]
-use std::os::unix::prelude::FromRawFd;
+use std::{
+    os::unix::prelude::FromRawFd,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 #[cfg(not(feature = "async"))]
 use std::io::Read;
@@ -172,6 +184,8 @@ impl<R, W, C> InteractOptions<R, W, C> {
     /// The output_filter will be passed all the output from the child process.
     ///
     /// The filter isn't applied to user's `read` calls through the [`Context`] in callbacks.
+    ///
+    /// [crate::ansi::strip_filter] is a ready-made filter which removes ANSI escape sequences.
     pub fn output_filter<F>(mut self, f: F) -> Self
     where
         F: FnMut(&[u8]) -> Result<Cow<[u8]>, Error> + 'static,
@@ -259,34 +273,57 @@ impl<R, W, C> InteractOptions<R, W, C> {
         Ok(Match::No)
     }
 
+    /// Runs output handlers.
+    ///
+    /// It returns a time when the output must be checked again even if nothing new comes,
+    /// if any of the needles asked for it.
     fn check_output(
         &mut self,
         session: &mut Session,
         buf: &mut Vec<u8>,
         eof: bool,
-    ) -> Result<(), Error> {
-        'checks: loop {
-            for (search, callback) in self.output_handlers.iter_mut() {
-                let found = search.check(buf, eof)?;
+        last_output: Instant,
+    ) -> Result<Option<Instant>, Error> {
+        let idle = last_output.elapsed();
+        loop {
+            let recheck = Cell::new(None);
+            let ctx = NeedleContext::new(eof, idle, &recheck).with_screen(session.screen());
+
+            let mut matched = None;
+            for (i, (search, _)) in self.output_handlers.iter().enumerate() {
+                let groups = search.check_groups_in_context(buf, &ctx)?;
+                let found = flatten_groups(&groups);
                 if !found.is_empty() {
-                    let end_index = crate::Found::right_most_index(&found);
-                    let involved_bytes = buf[..end_index].to_vec();
-                    let found = crate::Found::new(involved_bytes, found);
-                    buf.drain(..end_index);
-
-                    let context = Context {
-                        input: &mut self.input,
-                        output: &mut self.output,
-                        state: &mut self.state,
-                        session,
-                    };
-                    (callback)(context, found)?;
-
-                    continue 'checks;
+                    matched = Some((i, found, groups));
+                    break;
                 }
             }
 
-            return Ok(());
+            let recheck_at = recheck.get().map(|timeout| Instant::now() + timeout);
+            let (i, found, groups) = match matched {
+                Some(matched) => matched,
+                None => return Ok(recheck_at),
+            };
+
+            let end_index = crate::Found::right_most_index(&found);
+            let involved_bytes = buf[..end_index].to_vec();
+            let found = crate::Found::new(involved_bytes, found).with_groups(groups);
+            buf.drain(..end_index);
+
+            let context = Context {
+                input: &mut self.input,
+                output: &mut self.output,
+                state: &mut self.state,
+                session,
+            };
+            (self.output_handlers[i].1)(context, found)?;
+
+            // Running the checks again makes sense only if the match consumed something,
+            // otherwise the same handler would be matched over and over again.
+            // The needles checked before the matched one may still wait for a recheck.
+            if end_index == 0 || (buf.is_empty() && !eof) {
+                return Ok(recheck_at);
+            }
         }
     }
 
@@ -379,10 +416,20 @@ where
         set_raw(STDIN_FILENO)?;
     }
 
+    // Pass a size of the terminal and its further changes to the process.
+    let resize_handler = if isatty_terminal {
+        copy_terminal_size(session)?;
+        Some(ResizeHandler::install()?)
+    } else {
+        None
+    };
+
     session.set_echo(true, None)?;
 
     let result = interact(session, options);
 
+    drop(resize_handler);
+
     if isatty_terminal {
         // it's suppose to be always OK.
         // but we don't use unwrap just in case.
@@ -419,6 +466,11 @@ where
     };
     let mut exited = false;
 
+    // The output is checked again without new data when a needle asks for it,
+    // e.g. [crate::Idle] waits for a quiet period.
+    let mut last_output = Instant::now();
+    let mut recheck_at = None;
+
     let mut buf = [0; 512];
     loop {
         // In case where proceses exits we are trying to
@@ -430,15 +482,21 @@ where
             exited = true;
         }
 
+        if matches!(options.input_from, InputFrom::Terminal) {
+            pass_terminal_resize(session)?;
+        }
+
         match session.try_read(&mut buf) {
             Ok(n) => {
                 let eof = n == 0;
                 if eof {
                     exited = true;
+                } else {
+                    last_output = Instant::now();
                 }
 
                 output_buffer.extend_from_slice(&buf[..n]);
-                options.check_output(session, &mut output_buffer, eof)?;
+                recheck_at = options.check_output(session, &mut output_buffer, eof, last_output)?;
 
                 let bytes = if let Some(filter) = options.output_filter.as_mut() {
                     (filter)(&buf[..n])?
@@ -449,7 +507,12 @@ where
                 options.output.write_all(&bytes)?;
                 options.output.flush()?;
             }
-            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                if matches!(recheck_at, Some(at) if at <= Instant::now()) {
+                    recheck_at =
+                        options.check_output(session, &mut output_buffer, false, last_output)?;
+                }
+            }
             Err(err) => return Err(err.into()),
         }
 
@@ -543,10 +606,20 @@ where
         set_raw(STDIN_FILENO)?;
     }
 
+    // Pass a size of the terminal and its further changes to the process.
+    let resize_handler = if isatty_terminal {
+        copy_terminal_size(session)?;
+        Some(ResizeHandler::install()?)
+    } else {
+        None
+    };
+
     session.set_echo(true, None)?;
 
     let result = interact(session, options).await;
 
+    drop(resize_handler);
+
     if isatty_terminal {
         // it's suppose to be always OK.
         // but we don't use unwrap just in case.
@@ -585,6 +658,11 @@ where
 
     let mut output_buffer = Vec::new();
 
+    // The output is checked again without new data when a needle asks for it,
+    // e.g. [crate::Idle] waits for a quiet period.
+    let mut last_output = Instant::now();
+    let mut recheck_at = None;
+
     let mut buf = [0; 512];
     loop {
         let status = session.status()?;
@@ -592,12 +670,19 @@ where
             return Ok(status);
         }
 
+        if matches!(options.input_from, InputFrom::Terminal) {
+            pass_terminal_resize(session)?;
+        }
+
         match session.try_read(&mut buf).await {
             Ok(n) => {
                 let eof = n == 0;
+                if !eof {
+                    last_output = Instant::now();
+                }
 
                 output_buffer.extend_from_slice(&buf[..n]);
-                options.check_output(session, &mut output_buffer, eof)?;
+                recheck_at = options.check_output(session, &mut output_buffer, eof, last_output)?;
 
                 if n == 0 {
                     return Ok(status);
@@ -612,7 +697,12 @@ where
                 options.output.write_all(&bytes)?;
                 options.output.flush()?;
             }
-            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                if matches!(recheck_at, Some(at) if at <= Instant::now()) {
+                    recheck_at =
+                        options.check_output(session, &mut output_buffer, false, last_output)?;
+                }
+            }
             Err(err) => return Err(err.into()),
         }
 
@@ -715,14 +805,22 @@ where
 
     let mut output_buffer = Vec::new();
 
+    // The output is checked again without new data when a needle asks for it,
+    // e.g. [crate::Idle] waits for a quiet period.
+    let mut last_output = Instant::now();
+    let mut recheck_at = None;
+
     let mut buf = [0; 512];
     loop {
         match session.try_read(&mut buf) {
             Ok(n) => {
                 let eof = n == 0;
+                if !eof {
+                    last_output = Instant::now();
+                }
 
                 output_buffer.extend_from_slice(&buf[..n]);
-                options.check_output(session, &mut output_buffer, eof)?;
+                recheck_at = options.check_output(session, &mut output_buffer, eof, last_output)?;
 
                 if n == 0 {
                     return Ok(());
@@ -737,7 +835,12 @@ where
                 options.output.write_all(&bytes)?;
                 options.output.flush()?;
             }
-            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                if matches!(recheck_at, Some(at) if at <= Instant::now()) {
+                    recheck_at =
+                        options.check_output(session, &mut output_buffer, false, last_output)?;
+                }
+            }
             Err(err) => return Err(err.into()),
         }
 
@@ -799,13 +902,80 @@ where
     }
 }
 
+/// A flag which is set when a terminal was resized.
+#[cfg(unix)]
+static TERMINAL_RESIZED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_sigwinch(_: nix::libc::c_int) {
+    TERMINAL_RESIZED.store(true, Ordering::SeqCst);
+}
+
+/// ResizeHandler listens to `SIGWINCH` of a current process
+/// and restores a previous handler on drop.
+#[cfg(unix)]
+struct ResizeHandler {
+    previous: SigAction,
+}
+
+#[cfg(unix)]
+impl ResizeHandler {
+    fn install() -> Result<Self, Error> {
+        TERMINAL_RESIZED.store(false, Ordering::SeqCst);
+
+        let action = SigAction::new(
+            SigHandler::Handler(handle_sigwinch),
+            SaFlags::SA_RESTART,
+            SigSet::empty(),
+        );
+        // SAFETY: the handler only sets an atomic flag.
+        let previous = unsafe { sigaction(Signal::SIGWINCH, &action) }?;
+
+        Ok(Self { previous })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ResizeHandler {
+    fn drop(&mut self) {
+        // SAFETY: we restore a handler which was set before.
+        let _ = unsafe { sigaction(Signal::SIGWINCH, &self.previous) };
+    }
+}
+
+/// Sets a size of a current terminal to a session if the terminal was resized.
+#[cfg(unix)]
+fn pass_terminal_resize(session: &mut Session) -> Result<(), Error> {
+    if TERMINAL_RESIZED.swap(false, Ordering::SeqCst) {
+        copy_terminal_size(session)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_terminal_size(session: &mut Session) -> Result<(), Error> {
+    nix::ioctl_read_bad!(get_window_size, nix::libc::TIOCGWINSZ, nix::pty::Winsize);
+
+    let mut size = nix::pty::Winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: a pointer to a valid winsize structure is passed.
+    unsafe { get_window_size(STDIN_FILENO, &mut size) }?;
+
+    session.set_window_size(size.ws_col, size.ws_row)
+}
+
 /// A non blocking version of STDIN.
 ///
 /// It's not recomended to be used directly.
 /// But we expose it because its used in [InteractOptions::terminal]
 #[cfg(unix)]
 pub struct NonBlockingStdin {
-    stream: Stream,
+    stream: Stream<ProcessStream>,
 }
 
 #[cfg(unix)]
@@ -821,6 +991,9 @@ impl NonBlockingStdin {
         // https://github.com/zhiburt/expectrl/issues/7#issuecomment-884787229
         let stdin_copy_fd = dup(STDIN_FILENO)?;
         let stdin = unsafe { std::fs::File::from_raw_fd(stdin_copy_fd) };
+        let stdin = ptyprocess::stream::Stream::new(stdin);
+        #[cfg(feature = "async")]
+        let stdin = async_io::Async::new(stdin)?;
         let stream = Stream::new(stdin);
 
         Ok(Self { stream })
@@ -0,0 +1,486 @@
+//! Module contains a [Key] and a [KeyMap] which turns keys into bytes a terminal would send.
+
+use crate::{error::Error, terminfo::Terminfo};
+use std::{collections::HashMap, str::FromStr};
+
+/// KeyCode is a key on a keyboard without modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    /// A key which produces a character.
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    /// A function key, F1 is `F(1)`.
+    F(u8),
+}
+
+/// Key is a key combined with Ctrl, Alt and Shift modifiers.
+///
+/// It's sent by [crate::Session::send_key],
+/// a sequence of keys can be written in a vim like notation, see [Key::parse_sequence].
+///
+/// ```
+/// use expectrl::{Key, KeyCode};
+///
+/// let key = Key::new(KeyCode::Up).ctrl();
+/// assert_eq!("<C-Up>".parse::<Key>().unwrap(), key);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl Key {
+    /// Creates a key without modifiers.
+    pub fn new(code: KeyCode) -> Self {
+        Self {
+            code,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    /// Adds a Ctrl modifier.
+    pub fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    /// Adds an Alt (Meta) modifier.
+    pub fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    /// Adds a Shift modifier.
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    /// Returns a code of a key.
+    pub fn code(&self) -> KeyCode {
+        self.code
+    }
+
+    /// Parses a sequence of keys written in a vim like notation.
+    ///
+    /// A character stands for itself, special keys and combinations are put in angle brackets:
+    ///
+    /// - `<CR>` (`<Enter>`, `<Return>`), `<Esc>`, `<Tab>`, `<BS>`, `<Space>`, `<lt>` for `<`.
+    /// - `<Up>`, `<Down>`, `<Left>`, `<Right>`, `<Home>`, `<End>`,
+    ///   `<Ins>`, `<Del>`, `<PageUp>`, `<PageDown>`, `<F1>`..`<F63>`.
+    /// - Modifiers are prefixes `C-` (Ctrl), `A-` or `M-` (Alt) and `S-` (Shift),
+    ///   e.g. `<C-a>`, `<M-x>`, `<C-S-Left>`.
+    ///
+    /// Names are case insensitive.
+    ///
+    /// ```
+    /// use expectrl::{Key, KeyCode};
+    ///
+    /// let keys = Key::parse_sequence("<C-a>dd<Esc>:wq<CR>").unwrap();
+    /// assert_eq!(keys.len(), 8);
+    /// assert_eq!(keys[0], Key::new(KeyCode::Char('a')).ctrl());
+    /// assert_eq!(keys[3], Key::new(KeyCode::Escape));
+    /// ```
+    pub fn parse_sequence(notation: &str) -> Result<Vec<Key>, Error> {
+        let mut keys = Vec::new();
+        let mut rest = notation;
+        while let Some(c) = rest.chars().next() {
+            if c != '<' {
+                keys.push(Key::from(c));
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+
+            let end = rest
+                .find('>')
+                .ok_or_else(|| Error::KeyParsing(rest.to_owned()))?;
+            keys.push(rest[..=end].parse()?);
+            rest = &rest[end + 1..];
+        }
+
+        Ok(keys)
+    }
+}
+
+impl From<KeyCode> for Key {
+    fn from(code: KeyCode) -> Self {
+        Self::new(code)
+    }
+}
+
+impl From<char> for Key {
+    fn from(c: char) -> Self {
+        Self::new(KeyCode::Char(c))
+    }
+}
+
+impl FromStr for Key {
+    type Err = Error;
+
+    /// Parses a single key, see [Key::parse_sequence].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::KeyParsing(s.to_owned());
+
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Key::from(c));
+        }
+
+        let mut name = s
+            .strip_prefix('<')
+            .and_then(|s| s.strip_suffix('>'))
+            .ok_or_else(err)?;
+
+        let mut modifiers = Vec::new();
+        while let Some((modifier, rest)) = split_modifier(name) {
+            modifiers.push(modifier);
+            name = rest;
+        }
+
+        let mut key = Key::new(parse_key_code(name).ok_or_else(err)?);
+        for modifier in modifiers {
+            key = match modifier {
+                'c' => key.ctrl(),
+                'a' | 'm' => key.alt(),
+                _ => key.shift(),
+            };
+        }
+
+        Ok(key)
+    }
+}
+
+/// Splits a modifier prefix like `C-` off a key name.
+fn split_modifier(name: &str) -> Option<(char, &str)> {
+    let mut chars = name.chars();
+    let modifier = chars.next()?.to_ascii_lowercase();
+    let rest = chars.as_str().strip_prefix('-')?;
+    match modifier {
+        'c' | 'a' | 'm' | 's' if !rest.is_empty() => Some((modifier, rest)),
+        _ => None,
+    }
+}
+
+fn parse_key_code(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    let code = match name.to_ascii_lowercase().as_str() {
+        "cr" | "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Escape,
+        "tab" => KeyCode::Tab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "ins" | "insert" => KeyCode::Insert,
+        "del" | "delete" => KeyCode::Delete,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" => KeyCode::PageDown,
+        name => {
+            let n = name.strip_prefix('f')?.parse().ok()?;
+            match n {
+                1..=63 => KeyCode::F(n),
+                _ => return None,
+            }
+        }
+    };
+
+    Some(code)
+}
+
+/// KeyMap holds sequences a terminal sends for special keys.
+///
+/// By default it has xterm sequences, [KeyMap::from_terminfo] takes them from a terminfo database.
+/// Keys with modifiers are encoded the way xterm does it, e.g. Ctrl+Up is `ESC [ 1 ; 5 A`.
+///
+/// ```
+/// use expectrl::{Key, KeyCode, KeyMap};
+///
+/// let keys = KeyMap::xterm();
+/// assert_eq!(keys.encode(Key::new(KeyCode::Up)).unwrap(), b"\x1b[A");
+/// assert_eq!(keys.encode(Key::new(KeyCode::Up).ctrl()).unwrap(), b"\x1b[1;5A");
+/// assert_eq!(keys.encode(Key::from('a').ctrl()).unwrap(), b"\x01");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    keys: HashMap<KeyCode, Vec<u8>>,
+    back_tab: Vec<u8>,
+}
+
+impl KeyMap {
+    /// Returns sequences which xterm sends in its default mode.
+    pub fn xterm() -> Self {
+        let mut keys = HashMap::new();
+        let mut set = |code, seq: &str| keys.insert(code, seq.as_bytes().to_vec());
+        set(KeyCode::Backspace, "\x7f");
+        set(KeyCode::Up, "\x1b[A");
+        set(KeyCode::Down, "\x1b[B");
+        set(KeyCode::Right, "\x1b[C");
+        set(KeyCode::Left, "\x1b[D");
+        set(KeyCode::Home, "\x1b[H");
+        set(KeyCode::End, "\x1b[F");
+        set(KeyCode::Insert, "\x1b[2~");
+        set(KeyCode::Delete, "\x1b[3~");
+        set(KeyCode::PageUp, "\x1b[5~");
+        set(KeyCode::PageDown, "\x1b[6~");
+        set(KeyCode::F(1), "\x1bOP");
+        set(KeyCode::F(2), "\x1bOQ");
+        set(KeyCode::F(3), "\x1bOR");
+        set(KeyCode::F(4), "\x1bOS");
+        set(KeyCode::F(5), "\x1b[15~");
+        set(KeyCode::F(6), "\x1b[17~");
+        set(KeyCode::F(7), "\x1b[18~");
+        set(KeyCode::F(8), "\x1b[19~");
+        set(KeyCode::F(9), "\x1b[20~");
+        set(KeyCode::F(10), "\x1b[21~");
+        set(KeyCode::F(11), "\x1b[23~");
+        set(KeyCode::F(12), "\x1b[24~");
+
+        Self {
+            keys,
+            back_tab: b"\x1b[Z".to_vec(),
+        }
+    }
+
+    /// Loads sequences of a terminal from a terminfo database.
+    ///
+    /// The database is looked up the way ncurses does it,
+    /// in `$TERMINFO`, `~/.terminfo`, `$TERMINFO_DIRS` and the system directories.
+    /// Keys which are not described by an entry keep xterm sequences.
+    ///
+    /// Terminfo describes keys in a keypad transmit mode,
+    /// so e.g. arrows are usually `ESC O A` rather than `ESC [ A`.
+    pub fn from_terminfo(term: &str) -> Result<Self, Error> {
+        let terminfo = Terminfo::load(term)?;
+
+        let mut map = Self::xterm();
+        let keys = [
+            (KeyCode::Backspace, "kbs"),
+            (KeyCode::Up, "kcuu1"),
+            (KeyCode::Down, "kcud1"),
+            (KeyCode::Right, "kcuf1"),
+            (KeyCode::Left, "kcub1"),
+            (KeyCode::Home, "khome"),
+            (KeyCode::End, "kend"),
+            (KeyCode::Insert, "kich1"),
+            (KeyCode::Delete, "kdch1"),
+            (KeyCode::PageUp, "kpp"),
+            (KeyCode::PageDown, "knp"),
+        ];
+        for (code, name) in keys {
+            if let Some(seq) = terminfo.string(name) {
+                map.keys.insert(code, seq.to_vec());
+            }
+        }
+
+        for n in 1..=63 {
+            if let Some(seq) = terminfo.string(&format!("kf{}", n)) {
+                map.keys.insert(KeyCode::F(n), seq.to_vec());
+            }
+        }
+
+        if let Some(seq) = terminfo.string("kcbt") {
+            map.back_tab = seq.to_vec();
+        }
+
+        Ok(map)
+    }
+
+    /// Returns bytes a terminal sends for a key.
+    ///
+    /// It returns [None] if a map has no sequence for a key, e.g. for `F(20)` in xterm defaults.
+    pub fn encode(&self, key: impl Into<Key>) -> Option<Vec<u8>> {
+        let key = key.into();
+        let mut bytes = match key.code {
+            KeyCode::Char(c) => encode_char(c, key.ctrl, key.shift),
+            KeyCode::Enter => b"\r".to_vec(),
+            KeyCode::Tab if key.shift => return Some(self.back_tab.clone()),
+            KeyCode::Tab => b"\t".to_vec(),
+            KeyCode::Escape => b"\x1b".to_vec(),
+            KeyCode::Backspace if key.ctrl => b"\x08".to_vec(),
+            code => {
+                let seq = self.keys.get(&code)?;
+                match with_modifiers(seq, key.modifiers_param()) {
+                    Some(seq) => return Some(seq),
+                    None => seq.clone(),
+                }
+            }
+        };
+
+        if key.alt {
+            bytes.insert(0, 0x1b);
+        }
+
+        Some(bytes)
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::xterm()
+    }
+}
+
+impl Key {
+    /// Returns a parameter which xterm uses to tell modifiers, 1 means no modifiers.
+    fn modifiers_param(&self) -> u8 {
+        1 + self.shift as u8 + 2 * self.alt as u8 + 4 * self.ctrl as u8
+    }
+}
+
+fn encode_char(c: char, ctrl: bool, shift: bool) -> Vec<u8> {
+    let c = match shift {
+        true => c.to_uppercase().next().unwrap_or(c),
+        false => c,
+    };
+
+    if ctrl {
+        match c.to_ascii_uppercase() {
+            c @ '@'..='_' => return vec![c as u8 & 0x1f],
+            ' ' => return vec![0],
+            '?' => return vec![0x7f],
+            _ => {}
+        }
+    }
+
+    c.to_string().into_bytes()
+}
+
+/// Adds modifiers to a sequence as xterm does.
+///
+/// It returns [None] if there are no modifiers or a sequence can't have them.
+fn with_modifiers(seq: &[u8], param: u8) -> Option<Vec<u8>> {
+    if param == 1 {
+        return None;
+    }
+
+    match seq {
+        // `ESC [ n ~` becomes `ESC [ n ; m ~`.
+        [0x1b, b'[', n @ .., b'~'] if n.iter().all(u8::is_ascii_digit) => {
+            Some(format!("\x1b[{};{}~", String::from_utf8_lossy(n), param).into_bytes())
+        }
+        // `ESC O A` and `ESC [ A` become `ESC [ 1 ; m A`.
+        [0x1b, b'O' | b'[', action] if action.is_ascii_alphabetic() => {
+            Some(format!("\x1b[1;{}{}", param, *action as char).into_bytes())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sequence() {
+        let key = |c| Key::new(KeyCode::Char(c));
+        assert_eq!(
+            Key::parse_sequence("<C-a>dd<Esc>:wq<CR>").unwrap(),
+            vec![
+                key('a').ctrl(),
+                key('d'),
+                key('d'),
+                Key::new(KeyCode::Escape),
+                key(':'),
+                key('w'),
+                key('q'),
+                Key::new(KeyCode::Enter),
+            ]
+        );
+        assert_eq!(
+            Key::parse_sequence("<lt><space>ä<M-x><C-S-left><f12><s-TAB><C-->").unwrap(),
+            vec![
+                key('<'),
+                key(' '),
+                key('ä'),
+                key('x').alt(),
+                Key::new(KeyCode::Left).ctrl().shift(),
+                Key::new(KeyCode::F(12)),
+                Key::new(KeyCode::Tab).shift(),
+                key('-').ctrl(),
+            ]
+        );
+        assert_eq!(Key::parse_sequence("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            Key::parse_sequence("a<Up"),
+            Err(Error::KeyParsing(s)) if s == "<Up"
+        ));
+        assert!(matches!(
+            Key::parse_sequence("<Unknown>"),
+            Err(Error::KeyParsing(s)) if s == "<Unknown>"
+        ));
+        assert!("<F64>".parse::<Key>().is_err());
+        assert!("<X-a>".parse::<Key>().is_err());
+        assert!("ab".parse::<Key>().is_err());
+        assert_eq!("<".parse::<Key>().unwrap(), Key::from('<'));
+    }
+
+    #[test]
+    fn test_encode() {
+        let map = KeyMap::xterm();
+        let encode = |notation: &str| map.encode(notation.parse::<Key>().unwrap()).unwrap();
+
+        assert_eq!(encode("a"), b"a");
+        assert_eq!(encode("<S-a>"), b"A");
+        assert_eq!(encode("<C-a>"), b"\x01");
+        assert_eq!(encode("<C-S-a>"), b"\x01");
+        assert_eq!(encode("<C-[>"), b"\x1b");
+        assert_eq!(encode("<C-Space>"), b"\x00");
+        assert_eq!(encode("<C-?>"), b"\x7f");
+        assert_eq!(encode("<M-x>"), b"\x1bx");
+        assert_eq!(encode("<C-M-a>"), b"\x1b\x01");
+        assert_eq!(encode("<CR>"), b"\r");
+        assert_eq!(encode("<Tab>"), b"\t");
+        assert_eq!(encode("<S-Tab>"), b"\x1b[Z");
+        assert_eq!(encode("<Esc>"), b"\x1b");
+        assert_eq!(encode("<BS>"), b"\x7f");
+        assert_eq!(encode("<C-BS>"), b"\x08");
+
+        assert_eq!(encode("<Up>"), b"\x1b[A");
+        assert_eq!(encode("<S-Up>"), b"\x1b[1;2A");
+        assert_eq!(encode("<M-Left>"), b"\x1b[1;3D");
+        assert_eq!(encode("<C-Right>"), b"\x1b[1;5C");
+        assert_eq!(encode("<C-S-M-Home>"), b"\x1b[1;8H");
+        assert_eq!(encode("<Del>"), b"\x1b[3~");
+        assert_eq!(encode("<C-Del>"), b"\x1b[3;5~");
+        assert_eq!(encode("<PageDown>"), b"\x1b[6~");
+        assert_eq!(encode("<F1>"), b"\x1bOP");
+        assert_eq!(encode("<S-F1>"), b"\x1b[1;2P");
+        assert_eq!(encode("<F12>"), b"\x1b[24~");
+        assert_eq!(encode("<C-F5>"), b"\x1b[15;5~");
+
+        // There're no xterm defaults for them.
+        assert_eq!(map.encode(Key::new(KeyCode::F(20))), None);
+        assert_eq!(map.encode(Key::new(KeyCode::F(0)).ctrl()), None);
+    }
+}
@@ -41,24 +41,49 @@
 //! - It supports logging.
 //! - It supports interact function.
 //! - It has a Windows support.
+//! - It can drive any `Read + Write` stream (e.g. a TCP socket), not only a spawned process.
+//! - It can spawn a process with plain pipes instead of a pseudo terminal.
+//! - It can emulate a terminal screen, to check what a user would actually see.
+//! - It can answer terminal queries (e.g. a cursor position) which some programs wait for.
+//! - It can ignore ANSI escape sequences (e.g. colors) while matching.
+//! - It can send keys with modifiers (e.g. `<C-Up>`) encoded for a terminal type.
 
+pub mod ansi;
+#[cfg(unix)]
+mod builder;
 #[cfg(unix)]
 mod check_macros;
 mod control_code;
 mod error;
 mod found;
 pub mod interact;
+mod key;
 #[cfg(feature = "log")]
 mod log;
 mod needle;
+#[cfg(unix)]
+pub mod pipe;
 pub mod repl;
+mod responder;
+pub mod screen;
 pub mod session;
+#[cfg(unix)]
+mod stderr;
 mod stream;
+mod terminfo;
 
 pub use control_code::ControlCode;
-pub use error::Error;
-pub use found::Found;
-pub use needle::{Any, Eof, NBytes, Needle, Regex};
+pub use error::{Error, ExpectDetails};
+pub use found::{Captures, Found};
+pub use key::{Key, KeyCode, KeyMap};
+pub use needle::{
+    All, Any, AnyEarliest, CompiledRegex, Eof, Idle, NBytes, Needle, Not, Plain, Regex, Repeat,
+    ScreenText, Sequence, StyledText, Within,
+};
+pub use stream::NonBlocking;
+
+#[cfg(unix)]
+pub use builder::SessionBuilder;
 
 #[cfg(windows)]
 pub use conpty::ProcAttr;
@@ -75,7 +100,13 @@ pub use log::SessionWithLog as Session;
 /// Spawn spawnes a new session.
 ///
 /// It accepts a command and possibly arguments just as string.
-/// It doesn't parses ENV variables. For complex constrictions use [`Session::spawn`].
+/// The string is split into words by POSIX shell rules:
+/// single and double quotes, backslash escapes and adjacent quoted segments are supported.
+/// Leading `VAR=value` words are set as environment variables of the process.
+///
+/// No expansions are done (e.g. `$HOME` or `*`), pipes and redirects are not supported as well.
+/// Use [`spawn_shell`] if you need them.
+/// For complex constrictions use [`Session::spawn`].
 ///
 /// # Example
 ///
@@ -100,14 +131,7 @@ pub use log::SessionWithLog as Session;
 pub fn spawn<S: AsRef<str>>(cmd: S) -> Result<Session, Error> {
     #[cfg(unix)]
     {
-        let args = tokenize_command(cmd.as_ref());
-        if args.is_empty() {
-            return Err(Error::CommandParsing);
-        }
-
-        let mut command = std::process::Command::new(&args[0]);
-        command.args(args.iter().skip(1));
-
+        let command = parse_command(cmd.as_ref())?;
         Session::spawn(command)
     }
     #[cfg(windows)]
@@ -116,19 +140,156 @@ pub fn spawn<S: AsRef<str>>(cmd: S) -> Result<Session, Error> {
     }
 }
 
-/// Turn e.g. "prog arg1 arg2" into ["prog", "arg1", "arg2"]
-/// It takes care of single and double quotes but,
+/// Spawn a command line via `/bin/sh -c`.
+///
+/// Unlike [`spawn`] the line is interpreted by a shell,
+/// so pipes, redirects and expansions can be used.
 ///
-/// It doesn't cover all edge cases.
-/// So it may not be compatible with real shell arguments parsing.
+/// ```no_run
+/// let mut p = expectrl::spawn_shell("echo $HOME | tr a-z A-Z").unwrap();
+/// ```
+#[cfg(unix)]
+pub fn spawn_shell<S: AsRef<str>>(cmd: S) -> Result<Session, Error> {
+    let mut command = std::process::Command::new("/bin/sh");
+    command.arg("-c").arg(cmd.as_ref());
+
+    Session::spawn(command)
+}
+
+/// Builds a command from a command line.
 #[cfg(unix)]
-fn tokenize_command(program: &str) -> Vec<String> {
-    let re = regex::Regex::new(r#""[^"]+"|'[^']+'|[^'" ]+"#).unwrap();
-    let mut res = vec![];
-    for cap in re.captures_iter(program) {
-        res.push(cap[0].to_string());
+fn parse_command(line: &str) -> Result<std::process::Command, Error> {
+    let words = tokenize_command(line)?;
+
+    // Leading VAR=value words are environment variables.
+    let count_envs = words
+        .iter()
+        .take_while(|word| word.as_assignment().is_some())
+        .count();
+
+    let mut words = words.iter();
+    let envs = words.by_ref().take(count_envs).collect::<Vec<_>>();
+
+    let program = words.next().ok_or(Error::CommandParsing)?;
+    let mut command = std::process::Command::new(&program.value);
+    command.args(words.map(|word| &word.value));
+    for env in envs {
+        let (key, value) = env.as_assignment().expect("checked before");
+        command.env(key, value);
     }
-    res
+
+    Ok(command)
+}
+
+/// A word of a command line.
+#[cfg(unix)]
+#[derive(Debug, Default, PartialEq)]
+struct Word {
+    value: String,
+    /// A position of the first `=` if it and everything before it wasn't quoted or escaped.
+    unquoted_equal_sign: Option<usize>,
+}
+
+#[cfg(unix)]
+impl Word {
+    /// Returns a pair of name and value if the word is a `NAME=value` assignment.
+    fn as_assignment(&self) -> Option<(&str, &str)> {
+        let pos = self.unquoted_equal_sign?;
+        let name = &self.value[..pos];
+
+        let mut chars = name.chars();
+        let is_first_valid = matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_alphabetic());
+        let is_rest_valid = chars.all(|c| c == '_' || c.is_ascii_alphanumeric());
+        if !is_first_valid || !is_rest_valid {
+            return None;
+        }
+
+        Some((name, &self.value[pos + 1..]))
+    }
+}
+
+/// Turn e.g. "prog arg1 'arg 2'" into ["prog", "arg1", "arg 2"].
+///
+/// It follows POSIX shell rules of quoting:
+///
+/// - Characters in single quotes are taken literally.
+/// - In double quotes a backslash escapes only `$`, `` ` ``, `"`, `\` and a newline.
+/// - Outside of quotes a backslash escapes any character, and a backslash-newline is removed.
+/// - Adjacent quoted and unquoted parts are joined into one word.
+///
+/// It returns an error if a quote is not closed.
+#[cfg(unix)]
+fn tokenize_command(line: &str) -> Result<Vec<Word>, Error> {
+    let mut words = Vec::new();
+    // A word is started by any character or quote, so '' makes an empty word.
+    let mut word: Option<Word> = None;
+    // A flag is true until the first quoted or escaped character in a word.
+    let mut unquoted = true;
+
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+                unquoted = true;
+            }
+            '\'' => {
+                unquoted = false;
+                let word = word.get_or_insert_with(Word::default);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.value.push(c),
+                        None => return Err(Error::CommandParsing),
+                    }
+                }
+            }
+            '"' => {
+                unquoted = false;
+                let word = word.get_or_insert_with(Word::default);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(c @ ('$' | '`' | '"' | '\\')) => word.value.push(c),
+                            Some(c) => {
+                                word.value.push('\\');
+                                word.value.push(c);
+                            }
+                            None => return Err(Error::CommandParsing),
+                        },
+                        Some(c) => word.value.push(c),
+                        None => return Err(Error::CommandParsing),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                // line continuation
+                Some('\n') => {}
+                Some(c) => {
+                    unquoted = false;
+                    word.get_or_insert_with(Word::default).value.push(c);
+                }
+                None => return Err(Error::CommandParsing),
+            },
+            c => {
+                let word = word.get_or_insert_with(Word::default);
+                if c == '=' && unquoted && word.unquoted_equal_sign.is_none() {
+                    word.unquoted_equal_sign = Some(word.value.len());
+                }
+                word.value.push(c);
+            }
+        }
+    }
+
+    if let Some(word) = word {
+        words.push(word);
+    }
+
+    Ok(words)
 }
 
 #[cfg(test)]
@@ -138,17 +299,77 @@ mod tests {
     #[cfg(unix)]
     #[test]
     fn test_tokenize_command() {
-        let res = tokenize_command("prog arg1 arg2");
+        fn tokenize(line: &str) -> Vec<String> {
+            tokenize_command(line)
+                .unwrap()
+                .into_iter()
+                .map(|word| word.value)
+                .collect()
+        }
+
+        let res = tokenize("prog arg1 arg2");
         assert_eq!(vec!["prog", "arg1", "arg2"], res);
 
-        let res = tokenize_command("prog -k=v");
+        let res = tokenize("prog -k=v");
         assert_eq!(vec!["prog", "-k=v"], res);
 
-        let res = tokenize_command("prog 'my text'");
-        assert_eq!(vec!["prog", "'my text'"], res);
+        let res = tokenize("prog 'my text'");
+        assert_eq!(vec!["prog", "my text"], res);
+
+        let res = tokenize(r#"prog "my text""#);
+        assert_eq!(vec!["prog", "my text"], res);
+
+        let res = tokenize("  prog \t arg1\n  arg2  ");
+        assert_eq!(vec!["prog", "arg1", "arg2"], res);
+
+        let res = tokenize(r#"prog --name="John Doe"'s' a"b"'c'"#);
+        assert_eq!(vec!["prog", "--name=John Does", "abc"], res);
+
+        let res = tokenize(r#"prog '' "" ''x"#);
+        assert_eq!(vec!["prog", "", "", "x"], res);
+
+        let res = tokenize(r#"prog my\ text \'q\' \\"#);
+        assert_eq!(vec!["prog", "my text", "'q'", "\\"], res);
+
+        let res = tokenize(r#"prog "a \"b\" \$c \\ \d" 'a \"b\"'"#);
+        assert_eq!(vec!["prog", r#"a "b" $c \ \d"#, r#"a \"b\""#], res);
+
+        let res = tokenize("prog arg1 \\\narg2 ar\\\ng3");
+        assert_eq!(vec!["prog", "arg1", "arg2", "arg3"], res);
+
+        assert!(tokenize_command("prog 'my text").is_err());
+        assert!(tokenize_command(r#"prog "my text"#).is_err());
+        assert!(tokenize_command("prog \\").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_command_envs() {
+        let command = parse_command("A=1 _b_2='x y' prog C=3 arg").unwrap();
+        assert_eq!(command.get_program(), "prog");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), vec!["C=3", "arg"]);
+        assert_eq!(
+            command.get_envs().collect::<Vec<_>>(),
+            vec![
+                (std::ffi::OsStr::new("A"), Some(std::ffi::OsStr::new("1"))),
+                (
+                    std::ffi::OsStr::new("_b_2"),
+                    Some(std::ffi::OsStr::new("x y"))
+                ),
+            ]
+        );
+
+        // quoted or invalid names are not assignments
+        let command = parse_command("'A=1' prog").unwrap();
+        assert_eq!(command.get_program(), "A=1");
+        let command = parse_command("A\\=1 prog").unwrap();
+        assert_eq!(command.get_program(), "A=1");
+        let command = parse_command("1A=1 prog").unwrap();
+        assert_eq!(command.get_program(), "1A=1");
+        let command = parse_command("=1 prog").unwrap();
+        assert_eq!(command.get_program(), "=1");
 
-        let res = tokenize_command(r#"prog "my text""#);
-        assert_eq!(vec!["prog", r#""my text""#], res);
+        assert!(matches!(parse_command("A=1"), Err(Error::CommandParsing)));
     }
 
     #[cfg(unix)]
@@ -1,6 +1,9 @@
 //! A wrapper of [Session] to log a read/write operations
 
-use crate::{error::Error, session::Session};
+use crate::{
+    error::Error,
+    session::{Process, ProcessStream, Session},
+};
 use std::{
     io::{self, Write},
     ops::{Deref, DerefMut},
@@ -8,8 +11,8 @@ use std::{
 };
 
 /// A logging wrapper of session
-pub struct SessionWithLog {
-    inner: Session,
+pub struct SessionWithLog<P = Process, S = ProcessStream> {
+    inner: Session<P, S>,
     logger: Option<Box<dyn Write + Send>>,
 }
 
@@ -19,12 +22,48 @@ impl SessionWithLog {
     /// See [Session].
     pub fn spawn(cmd: Command) -> Result<Self, Error> {
         let session = Session::spawn(cmd)?;
-        Ok(Self {
+        Ok(Self::from(session))
+    }
+
+    /// Spawn session wrapped with logger in a hybrid mode.
+    ///
+    /// See [Session::spawn_with_stderr].
+    pub fn spawn_with_stderr(cmd: Command) -> Result<Self, Error> {
+        let session = Session::spawn_with_stderr(cmd)?;
+        Ok(Self::from(session))
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl<P, S: io::Read> SessionWithLog<P, S> {
+    /// Creates a session wrapped with logger.
+    ///
+    /// See [Session::new].
+    pub fn new(process: P, stream: S) -> Self {
+        Self::from(Session::new(process, stream))
+    }
+}
+
+#[cfg(feature = "async")]
+impl<P, S: futures_lite::AsyncRead + Unpin> SessionWithLog<P, S> {
+    /// Creates a session wrapped with logger.
+    ///
+    /// See [Session::new].
+    pub fn new(process: P, stream: S) -> Self {
+        Self::from(Session::new(process, stream))
+    }
+}
+
+impl<P, S> From<Session<P, S>> for SessionWithLog<P, S> {
+    fn from(session: Session<P, S>) -> Self {
+        Self {
             inner: session,
             logger: None,
-        })
+        }
     }
+}
 
+impl<P, S> SessionWithLog<P, S> {
     /// Set a writer for which is used for logging.
     ///
     /// Logger is suppose to be called on all IO operations.
@@ -43,47 +82,47 @@ impl SessionWithLog {
 }
 
 #[cfg(all(feature = "log", not(feature = "async")))]
-impl SessionWithLog {
-    pub fn send<S: AsRef<str>>(&mut self, s: S) -> io::Result<()> {
+impl<P, S: Write> SessionWithLog<P, S> {
+    pub fn send<Str: AsRef<str>>(&mut self, s: Str) -> io::Result<()> {
         self.log("send", s.as_ref().as_bytes());
         self.inner.send(s)
     }
 
-    pub fn send_line<S: AsRef<str>>(&mut self, s: S) -> io::Result<()> {
+    pub fn send_line<Str: AsRef<str>>(&mut self, s: Str) -> io::Result<()> {
         self.log("send_line", s.as_ref().as_bytes());
         self.inner.send_line(s)
     }
 }
 
 #[cfg(feature = "async")]
-impl SessionWithLog {
-    pub async fn send<S: AsRef<str>>(&mut self, s: S) -> io::Result<()> {
+impl<P, S: futures_lite::AsyncWrite + Unpin> SessionWithLog<P, S> {
+    pub async fn send<Str: AsRef<str>>(&mut self, s: Str) -> io::Result<()> {
         self.log("send", s.as_ref().as_bytes());
         self.inner.send(s).await
     }
 
-    pub async fn send_line<S: AsRef<str>>(&mut self, s: S) -> io::Result<()> {
+    pub async fn send_line<Str: AsRef<str>>(&mut self, s: Str) -> io::Result<()> {
         self.log("send_line", s.as_ref().as_bytes());
         self.inner.send_line(s).await
     }
 }
 
-impl Deref for SessionWithLog {
-    type Target = Session;
+impl<P, S> Deref for SessionWithLog<P, S> {
+    type Target = Session<P, S>;
 
     fn deref(&self) -> &Self::Target {
         &self.inner
     }
 }
 
-impl DerefMut for SessionWithLog {
+impl<P, S> DerefMut for SessionWithLog<P, S> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.inner
     }
 }
 
 #[cfg(not(feature = "async"))]
-impl std::io::Write for SessionWithLog {
+impl<P, S: Write> std::io::Write for SessionWithLog<P, S> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         self.log("write", buf);
         self.deref_mut().write(buf)
@@ -95,7 +134,7 @@ impl std::io::Write for SessionWithLog {
 }
 
 #[cfg(not(feature = "async"))]
-impl std::io::Read for SessionWithLog {
+impl<P, S: io::Read> std::io::Read for SessionWithLog<P, S> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let result = self.deref_mut().read(buf);
         if let Ok(n) = result {
@@ -107,7 +146,7 @@ impl std::io::Read for SessionWithLog {
 }
 
 #[cfg(not(feature = "async"))]
-impl std::io::BufRead for SessionWithLog {
+impl<P, S: io::Read> std::io::BufRead for SessionWithLog<P, S> {
     fn fill_buf(&mut self) -> io::Result<&[u8]> {
         self.inner.fill_buf()
     }
@@ -131,7 +170,9 @@ impl std::io::BufRead for SessionWithLog {
 }
 
 #[cfg(feature = "async")]
-impl futures_lite::io::AsyncWrite for SessionWithLog {
+impl<P: Unpin, S: futures_lite::AsyncWrite + Unpin> futures_lite::io::AsyncWrite
+    for SessionWithLog<P, S>
+{
     fn poll_write(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -157,7 +198,9 @@ impl futures_lite::io::AsyncWrite for SessionWithLog {
 }
 
 #[cfg(feature = "async")]
-impl futures_lite::io::AsyncRead for SessionWithLog {
+impl<P: Unpin, S: futures_lite::AsyncRead + Unpin> futures_lite::io::AsyncRead
+    for SessionWithLog<P, S>
+{
     fn poll_read(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -178,7 +221,9 @@ impl futures_lite::io::AsyncRead for SessionWithLog {
 }
 
 #[cfg(feature = "async")]
-impl futures_lite::io::AsyncBufRead for SessionWithLog {
+impl<P: Unpin, S: futures_lite::AsyncRead + Unpin> futures_lite::io::AsyncBufRead
+    for SessionWithLog<P, S>
+{
     fn poll_fill_buf(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -194,7 +239,7 @@ impl futures_lite::io::AsyncBufRead for SessionWithLog {
 }
 
 #[cfg(feature = "async")]
-impl SessionWithLog {
+impl<P: Unpin, S: futures_lite::AsyncRead + Unpin> SessionWithLog<P, S> {
     /// The function behaives in the same way as [futures_lite::io::AsyncBufReadExt].
     ///
     /// The function is crated as a hack because [futures_lite::io::AsyncBufReadExt] has a default implmentation.
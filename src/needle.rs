@@ -1,9 +1,130 @@
-use crate::error::Error;
+use crate::{
+    ansi::StrippedText,
+    error::Error,
+    screen::{Color, Screen, Style},
+};
+use std::{cell::Cell, time::Duration};
 
 /// Needle an interface for search of a match in a buffer.
+///
+/// A needle must implement either [Needle::check] or [Needle::check_groups_in_context],
+/// the rest of the methods are derived from them.
 pub trait Needle {
     /// Function returns all matches that were occured.
-    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error>;
+    ///
+    /// By default it calls [Needle::check_in_context] with a context which has no information about time.
+    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
+        with_default_context(eof, |ctx| self.check_in_context(buf, ctx))
+    }
+
+    /// Function returns all matches that were occured grouped with their capture groups.
+    ///
+    /// By default it calls [Needle::check_groups_in_context] with a context which has no information about time.
+    fn check_groups(&self, buf: &[u8], eof: bool) -> Result<Vec<MatchGroups>, Error> {
+        with_default_context(eof, |ctx| self.check_groups_in_context(buf, ctx))
+    }
+
+    /// Function returns all matches that were occured taking into account a context of a check.
+    ///
+    /// By default the matches are taken from [Needle::check_groups_in_context].
+    fn check_in_context(&self, buf: &[u8], ctx: &NeedleContext<'_>) -> Result<Vec<Match>, Error> {
+        Ok(flatten_groups(&self.check_groups_in_context(buf, ctx)?))
+    }
+
+    /// Function returns all matches grouped with their capture groups taking into account a context of a check.
+    ///
+    /// It's called by [crate::Session::expect], [crate::Session::check] and an interact,
+    /// so a needle may depend on time, like [Idle] does.
+    ///
+    /// By default each match returned by [Needle::check] is considered as a match without groups.
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        let matches = self.check(buf, ctx.is_eof())?;
+        Ok(matches.into_iter().map(MatchGroups::from).collect())
+    }
+
+    /// Returns a human readable description of a needle.
+    ///
+    /// It's used in errors to tell what was expected.
+    /// By default it's a name of a type.
+    fn description(&self) -> String {
+        std::any::type_name::<Self>().to_owned()
+    }
+}
+
+/// NeedleContext is a state of a stream at the moment of a check.
+///
+/// A needle which depends on time may ask to be checked again after a while
+/// even if no new data arrives, see [NeedleContext::recheck_after].
+#[derive(Debug)]
+pub struct NeedleContext<'a> {
+    eof: bool,
+    idle: Duration,
+    recheck: &'a Cell<Option<Duration>>,
+    screen: Option<&'a Screen>,
+}
+
+impl<'a> NeedleContext<'a> {
+    pub(crate) fn new(eof: bool, idle: Duration, recheck: &'a Cell<Option<Duration>>) -> Self {
+        Self {
+            eof,
+            idle,
+            recheck,
+            screen: None,
+        }
+    }
+
+    /// Sets a screen which reflects a checked buffer.
+    pub(crate) fn with_screen(mut self, screen: Option<&'a Screen>) -> Self {
+        self.screen = screen;
+        self
+    }
+
+    /// Returns true if there will be no more data after a checked buffer.
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Returns a time passed since the last byte of a checked buffer was received.
+    ///
+    /// It's zero if there's more data to be checked after the buffer.
+    pub fn idle(&self) -> Duration {
+        self.idle
+    }
+
+    /// Asks to run a check again after a given time even if no data arrives.
+    ///
+    /// If it's called several times the shortest time is used.
+    pub fn recheck_after(&self, timeout: Duration) {
+        let timeout = match self.recheck.get() {
+            Some(current) => current.min(timeout),
+            None => timeout,
+        };
+
+        self.recheck.set(Some(timeout));
+    }
+
+    /// Returns a virtual screen if it's enabled by [crate::Session::enable_screen].
+    ///
+    /// It's present only if a checked buffer ends with the last byte which was read,
+    /// so the screen shows exactly what the buffer produced.
+    pub fn screen(&self) -> Option<&'a Screen> {
+        self.screen
+    }
+
+    /// Returns a context with a different EOF indicator.
+    ///
+    /// It's meant to be used by combinators which check only a part of a buffer.
+    /// A time and a screen are reset as the part is followed by more data.
+    pub fn with_eof(&self, eof: bool) -> NeedleContext<'a> {
+        match eof {
+            true => Self::new(eof, self.idle, self.recheck).with_screen(self.screen),
+            false => Self::new(eof, Duration::ZERO, self.recheck),
+        }
+    }
 }
 
 /// Match structure represent a range of bytes where match was found.
@@ -28,6 +149,111 @@ impl Match {
     pub fn end(&self) -> usize {
         self.end
     }
+
+    /// Moves a match by an offset.
+    pub(crate) fn shift(self, offset: usize) -> Self {
+        Self::new(self.start + offset, self.end + offset)
+    }
+}
+
+/// MatchGroups is a list of capture groups of a single match.
+///
+/// The group with index 0 always represents the whole match.
+/// A group may be missing if it didn't participate in a match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchGroups {
+    groups: Vec<Option<Match>>,
+    names: Vec<Option<String>>,
+    needle_index: Option<usize>,
+}
+
+impl MatchGroups {
+    /// New constructs a list of groups.
+    ///
+    /// `names` contains a name of a group by the same index, if there's any.
+    pub fn new(groups: Vec<Option<Match>>, names: Vec<Option<String>>) -> Self {
+        Self {
+            groups,
+            names,
+            needle_index: None,
+        }
+    }
+
+    /// Sets an index of a needle which produced the match, see [Any].
+    pub fn with_needle_index(mut self, index: usize) -> Self {
+        self.needle_index = Some(index);
+        self
+    }
+
+    /// Returns an index of a needle which produced the match.
+    pub fn needle_index(&self) -> Option<usize> {
+        self.needle_index
+    }
+
+    /// Get returns a group by index.
+    pub fn get(&self, index: usize) -> Option<&Match> {
+        self.groups.get(index).and_then(|m| m.as_ref())
+    }
+
+    /// Name returns a group by its name.
+    pub fn name(&self, name: &str) -> Option<&Match> {
+        let index = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        self.get(index)
+    }
+
+    /// Len returns a number of groups including the missing ones.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// is_empty verifies if there's no groups.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Matches returns all groups which participated in a match.
+    pub fn matches(&self) -> impl Iterator<Item = &Match> {
+        self.groups.iter().flatten()
+    }
+
+    /// Returns a range of a whole match.
+    ///
+    /// It must be called only on groups with at least one match.
+    fn bounds(&self) -> Match {
+        match self.get(0) {
+            Some(m) => m.clone(),
+            None => {
+                let start = self.matches().map(|m| m.start()).min().unwrap_or_default();
+                let end = self.matches().map(|m| m.end()).max().unwrap_or_default();
+                Match::new(start, end)
+            }
+        }
+    }
+
+    /// Moves all groups by an offset.
+    pub(crate) fn shift(mut self, offset: usize) -> Self {
+        for m in self.groups.iter_mut().flatten() {
+            m.start += offset;
+            m.end += offset;
+        }
+
+        self
+    }
+
+    /// Replaces each group by a result of a function.
+    fn map(mut self, f: impl Fn(Match) -> Match) -> Self {
+        for m in self.groups.iter_mut().flatten() {
+            *m = f(m.clone());
+        }
+
+        self
+    }
+}
+
+impl From<Match> for MatchGroups {
+    fn from(m: Match) -> Self {
+        Self::new(vec![Some(m)], vec![None])
+    }
 }
 
 impl From<regex::bytes::Match<'_>> for Match {
@@ -37,20 +263,104 @@ impl From<regex::bytes::Match<'_>> for Match {
 }
 
 /// Regex checks a match by regex.
+///
+/// A regex is compiled on each check,
+/// consider using [CompiledRegex] if it's used more than once.
 pub struct Regex<Re: AsRef<str>>(pub Re);
 
 impl<Re: AsRef<str>> Needle for Regex<Re> {
     fn check(&self, buf: &[u8], _: bool) -> Result<Vec<Match>, Error> {
         let regex = regex::bytes::Regex::new(self.0.as_ref()).map_err(|_| Error::RegexParsing)?;
-        let matches = regex
-            .captures_iter(buf)
-            .map(|c| c.iter().flatten().map(|m| m.into()).collect::<Vec<Match>>())
-            .flatten()
-            .collect();
-        Ok(matches)
+        Ok(regex_matches(&regex, buf))
+    }
+
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        _: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        let regex = regex::bytes::Regex::new(self.0.as_ref()).map_err(|_| Error::RegexParsing)?;
+        Ok(regex_groups(&regex, buf))
+    }
+
+    fn description(&self) -> String {
+        format!("Regex({:?})", self.0.as_ref())
+    }
+}
+
+/// CompiledRegex checks a match by a regex which is compiled once on construction.
+///
+/// ```
+/// use expectrl::CompiledRegex;
+///
+/// let re = CompiledRegex::new(r"\d+").unwrap();
+/// assert!(CompiledRegex::new(r"\d+(").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompiledRegex(regex::bytes::Regex);
+
+impl CompiledRegex {
+    /// Compiles a regex from a pattern.
+    ///
+    /// It returns [Error::RegexParsing] if a pattern is invalid.
+    pub fn new<Re: AsRef<str>>(pattern: Re) -> Result<Self, Error> {
+        regex::bytes::Regex::new(pattern.as_ref())
+            .map(Self)
+            .map_err(|_| Error::RegexParsing)
+    }
+
+    /// Returns a reference to the compiled regex.
+    pub fn as_regex(&self) -> &regex::bytes::Regex {
+        &self.0
+    }
+}
+
+impl From<regex::bytes::Regex> for CompiledRegex {
+    fn from(regex: regex::bytes::Regex) -> Self {
+        Self(regex)
+    }
+}
+
+impl Needle for CompiledRegex {
+    fn check(&self, buf: &[u8], _: bool) -> Result<Vec<Match>, Error> {
+        Ok(regex_matches(&self.0, buf))
+    }
+
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        _: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        Ok(regex_groups(&self.0, buf))
+    }
+
+    fn description(&self) -> String {
+        format!("CompiledRegex({:?})", self.0.as_str())
     }
 }
 
+fn regex_matches(regex: &regex::bytes::Regex, buf: &[u8]) -> Vec<Match> {
+    regex
+        .captures_iter(buf)
+        .flat_map(|c| c.iter().flatten().map(|m| m.into()).collect::<Vec<Match>>())
+        .collect()
+}
+
+fn regex_groups(regex: &regex::bytes::Regex, buf: &[u8]) -> Vec<MatchGroups> {
+    let names = regex
+        .capture_names()
+        .map(|name| name.map(String::from))
+        .collect::<Vec<_>>();
+
+    regex
+        .captures_iter(buf)
+        .map(|c| {
+            let groups = c.iter().map(|m| m.map(Match::from)).collect();
+            MatchGroups::new(groups, names.clone())
+        })
+        .collect()
+}
+
 /// Eof consider a match when it's reached a EOF.
 pub struct Eof;
 
@@ -61,6 +371,10 @@ impl Needle for Eof {
             false => Ok(Vec::new()),
         }
     }
+
+    fn description(&self) -> String {
+        "Eof".to_owned()
+    }
 }
 
 /// NBytes matches N bytes.
@@ -79,6 +393,212 @@ impl Needle for NBytes {
             false => Ok(Vec::new()),
         }
     }
+
+    fn description(&self) -> String {
+        format!("NBytes({})", self.count())
+    }
+}
+
+/// Idle matches all the data when a stream has been quiet for a given time.
+///
+/// It's useful for programs which don't print a stable prompt.
+/// A time is counted from the moment the last data was read from a process,
+/// so the needle matches only after all the output was checked.
+/// It matches right away on EOF.
+///
+/// ```no_run,ignore
+/// Idle(Duration::from_millis(500))
+/// ```
+///
+/// Idle depends on time, so it matches only when it's checked by
+/// [crate::Session::expect], [crate::Session::check] or an interact call.
+/// A plain [Needle::check] has no information about time.
+pub struct Idle(pub Duration);
+
+impl Needle for Idle {
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        if ctx.is_eof() || ctx.idle() >= self.0 {
+            return Ok(vec![Match::new(0, buf.len()).into()]);
+        }
+
+        ctx.recheck_after(self.0 - ctx.idle());
+
+        Ok(Vec::new())
+    }
+
+    fn description(&self) -> String {
+        format!("Idle({:?})", self.0)
+    }
+}
+
+/// ScreenText matches when a text is visible on a virtual screen.
+///
+/// Unlike other needles it checks what a terminal would show rather than raw bytes,
+/// so cursor movements and redraws are taken into account.
+/// A text may span several rows, they are separated by `\n` as in [Screen::contents].
+///
+/// A match covers the whole checked buffer.
+/// The screen must be enabled by [crate::Session::enable_screen], otherwise it never matches.
+///
+/// Wide characters (e.g. CJK or emoji) take a single cell of a [Screen] rather than two,
+/// so an output which relies on their width, like a redraw by a cursor movement,
+/// may leave a different text on the screen than a real terminal would show.
+///
+/// ```no_run,ignore
+/// use expectrl::{spawn, ScreenText};
+///
+/// let mut p = spawn("top").unwrap();
+/// p.enable_screen(80, 24);
+/// p.expect(ScreenText("load average")).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ScreenText<T>(pub T);
+
+impl<T: AsRef<str>> Needle for ScreenText<T> {
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        match ctx.screen() {
+            Some(screen) if screen.contents().contains(self.0.as_ref()) => {
+                Ok(vec![Match::new(0, buf.len()).into()])
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("ScreenText({:?})", self.0.as_ref())
+    }
+}
+
+/// StyledText matches when a text is visible on a virtual screen and rendered in a given style.
+///
+/// Only attributes which are set are checked,
+/// and every character of a text must have them.
+/// A text is looked up within single rows.
+///
+/// A match covers the whole checked buffer.
+/// The screen must be enabled by [crate::Session::enable_screen], otherwise it never matches.
+/// Wide characters take a single cell, see [ScreenText].
+///
+/// ```no_run,ignore
+/// use expectrl::{screen::Color, spawn, StyledText};
+///
+/// let mut p = spawn("cargo test").unwrap();
+/// p.enable_screen(80, 24);
+/// p.expect(StyledText::new("FAILED").foreground(Color::RED)).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct StyledText<T> {
+    text: T,
+    foreground: Option<Color>,
+    background: Option<Color>,
+    bold: Option<bool>,
+    underline: Option<bool>,
+    reverse: Option<bool>,
+}
+
+impl<T> StyledText<T> {
+    /// Creates a needle which matches a text in any style.
+    pub fn new(text: T) -> Self {
+        Self {
+            text,
+            foreground: None,
+            background: None,
+            bold: None,
+            underline: None,
+            reverse: None,
+        }
+    }
+
+    /// Sets a foreground color a text must be rendered with.
+    pub fn foreground(mut self, color: Color) -> Self {
+        self.foreground = Some(color);
+        self
+    }
+
+    /// Sets a background color a text must be rendered with.
+    pub fn background(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    /// Sets whether a text must be bold or not.
+    pub fn bold(mut self, on: bool) -> Self {
+        self.bold = Some(on);
+        self
+    }
+
+    /// Sets whether a text must be underlined or not.
+    pub fn underline(mut self, on: bool) -> Self {
+        self.underline = Some(on);
+        self
+    }
+
+    /// Sets whether a text must be rendered in reverse video or not.
+    pub fn reverse(mut self, on: bool) -> Self {
+        self.reverse = Some(on);
+        self
+    }
+
+    fn is_accepted(&self, style: &Style) -> bool {
+        fn accepts<V: PartialEq>(expected: Option<V>, actual: V) -> bool {
+            match expected {
+                Some(expected) => expected == actual,
+                None => true,
+            }
+        }
+
+        accepts(self.foreground, style.foreground)
+            && accepts(self.background, style.background)
+            && accepts(self.bold, style.bold)
+            && accepts(self.underline, style.underline)
+            && accepts(self.reverse, style.reverse)
+    }
+}
+
+impl<T: AsRef<str>> Needle for StyledText<T> {
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        let found = ctx.screen().and_then(|screen| {
+            screen.find_styled(self.text.as_ref(), |style| self.is_accepted(style))
+        });
+
+        match found {
+            Some(_) => Ok(vec![Match::new(0, buf.len()).into()]),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn description(&self) -> String {
+        let mut description = format!("StyledText({:?}", self.text.as_ref());
+        if let Some(color) = self.foreground {
+            description.push_str(&format!(", foreground: {:?}", color));
+        }
+        if let Some(color) = self.background {
+            description.push_str(&format!(", background: {:?}", color));
+        }
+        if let Some(on) = self.bold {
+            description.push_str(&format!(", bold: {}", on));
+        }
+        if let Some(on) = self.underline {
+            description.push_str(&format!(", underline: {}", on));
+        }
+        if let Some(on) = self.reverse {
+            description.push_str(&format!(", reverse: {}", on));
+        }
+        description.push(')');
+        description
+    }
 }
 
 impl Needle for [u8] {
@@ -100,46 +620,116 @@ impl Needle for [u8] {
 
         Ok(Vec::new())
     }
+
+    fn description(&self) -> String {
+        format!("b\"{}\"", self.escape_ascii())
+    }
 }
 
 impl Needle for &[u8] {
     fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
         (*self).check(buf, eof)
     }
+
+    fn description(&self) -> String {
+        (*self).description()
+    }
 }
 
 impl Needle for str {
     fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
         self.as_bytes().check(buf, eof)
     }
+
+    fn description(&self) -> String {
+        format!("{:?}", self)
+    }
 }
 
 impl Needle for &str {
     fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
         self.as_bytes().check(buf, eof)
     }
+
+    fn description(&self) -> String {
+        format!("{:?}", self)
+    }
 }
 
 impl Needle for String {
     fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
         self.as_bytes().check(buf, eof)
     }
+
+    fn description(&self) -> String {
+        format!("{:?}", self)
+    }
 }
 
 impl Needle for u8 {
     fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
         (&[*self][..]).check(buf, eof)
     }
+
+    fn description(&self) -> String {
+        format!("b'{}'", self.escape_ascii())
+    }
 }
 
 impl Needle for char {
     fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
         char::to_string(self).check(buf, eof)
     }
+
+    fn description(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// Implements [Needle] for a vector and arrays of needles by forwarding calls to a slice version.
+///
+/// A given expression makes a needle over a slice out of `$this`.
+macro_rules! impl_needle_for_lists {
+    ($needle:ident, |$this:ident| $slice:expr) => {
+        impl_needle_for_lists!(@impl $needle, [T], Vec<T>, |$this| $slice);
+        impl_needle_for_lists!(@impl $needle, [T, const N: usize], [T; N], |$this| $slice);
+        impl_needle_for_lists!(@impl $needle, [T, const N: usize], &[T; N], |$this| $slice);
+    };
+    (@impl $needle:ident, [$($params:tt)*], $list:ty, |$this:ident| $slice:expr) => {
+        impl<$($params)*> Needle for $needle<$list>
+        where
+            T: Needle,
+        {
+            fn check_in_context(
+                &self,
+                buf: &[u8],
+                ctx: &NeedleContext<'_>,
+            ) -> Result<Vec<Match>, Error> {
+                let $this = self;
+                $slice.check_in_context(buf, ctx)
+            }
+
+            fn check_groups_in_context(
+                &self,
+                buf: &[u8],
+                ctx: &NeedleContext<'_>,
+            ) -> Result<Vec<MatchGroups>, Error> {
+                let $this = self;
+                $slice.check_groups_in_context(buf, ctx)
+            }
+
+            fn description(&self) -> String {
+                let $this = self;
+                $slice.description()
+            }
+        }
+    };
 }
 
 /// Any matches first Needle which returns a match.
 ///
+/// An index of the matched needle is available via [crate::Found::needle_index].
+///
 /// ```no_run,ignore
 /// Any(["we", "are", "here"])
 /// ```
@@ -149,6 +739,9 @@ impl Needle for char {
 /// ```no_run,ignore
 /// Any::boxed(vec![Box::new("we"), Box::new(NBytes(3))])
 /// ```
+///
+/// To choose a needle whose match is the earliest in a buffer
+/// use [Any::earliest_start] or [Any::earliest_end].
 pub struct Any<I>(pub I);
 
 impl Any<Vec<Box<dyn Needle>>> {
@@ -161,54 +754,533 @@ impl<T> Needle for Any<&[T]>
 where
     T: Needle,
 {
-    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
+    fn check_in_context(&self, buf: &[u8], ctx: &NeedleContext<'_>) -> Result<Vec<Match>, Error> {
         for needle in self.0.iter() {
-            let found = needle.check(buf, eof)?;
+            let found = needle.check_in_context(buf, ctx)?;
+            if !found.is_empty() {
+                return Ok(found);
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        for (index, needle) in self.0.iter().enumerate() {
+            let found = needle.check_groups_in_context(buf, ctx)?;
             if !found.is_empty() {
+                let found = found
+                    .into_iter()
+                    .map(|groups| groups.with_needle_index(index))
+                    .collect();
                 return Ok(found);
             }
         }
 
         Ok(Vec::new())
     }
+
+    fn description(&self) -> String {
+        format!("Any([{}])", describe_list(self.0))
+    }
 }
 
-impl<T> Needle for Any<Vec<T>>
-where
-    T: Needle,
-{
-    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
-        Any(self.0.as_slice()).check(buf, eof)
+impl_needle_for_lists!(Any, |this| Any(&this.0[..]));
+
+impl<I> Any<I> {
+    /// Makes it choose an alternative whose match starts first in a buffer.
+    ///
+    /// By default [Any] chooses the first needle in the list which has a match.
+    /// Ties are resolved in the list order.
+    ///
+    /// Notice that [crate::Session::expect] checks a buffer byte by byte,
+    /// so a needle whose match ends first is matched anyway.
+    /// The option matters when a buffer is checked all at once like in [crate::Session::check].
+    ///
+    /// ```no_run,ignore
+    /// Any(["World", "Hello"]).earliest_start()
+    /// ```
+    pub fn earliest_start(self) -> AnyEarliest<I> {
+        AnyEarliest::new(self.0, Position::Start)
+    }
+
+    /// Makes it choose an alternative whose match ends first in a buffer.
+    ///
+    /// See [Any::earliest_start].
+    pub fn earliest_end(self) -> AnyEarliest<I> {
+        AnyEarliest::new(self.0, Position::End)
     }
 }
 
-impl<T, const N: usize> Needle for Any<[T; N]>
-where
-    T: Needle,
-{
-    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
-        Any(&self.0[..]).check(buf, eof)
+/// AnyEarliest matches a needle whose match is the earliest in a buffer.
+///
+/// It's constructed by [Any::earliest_start] and [Any::earliest_end].
+pub struct AnyEarliest<I> {
+    needles: I,
+    position: Position,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Position {
+    Start,
+    End,
+}
+
+impl<I> AnyEarliest<I> {
+    fn new(needles: I, position: Position) -> Self {
+        Self { needles, position }
     }
 }
 
-impl<'a, T, const N: usize> Needle for Any<&'a [T; N]>
+impl<T> Needle for AnyEarliest<&[T]>
 where
     T: Needle,
 {
-    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
-        Any(&self.0[..]).check(buf, eof)
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        let mut earliest: Option<(usize, usize, Vec<MatchGroups>)> = None;
+        for (index, needle) in self.needles.iter().enumerate() {
+            let found = needle.check_groups_in_context(buf, ctx)?;
+            let position = found
+                .iter()
+                .filter(|groups| groups.matches().next().is_some())
+                .map(|groups| match self.position {
+                    Position::Start => groups.bounds().start(),
+                    Position::End => groups.bounds().end(),
+                })
+                .min();
+            let position = match position {
+                Some(position) => position,
+                None => continue,
+            };
+
+            let is_earlier = match &earliest {
+                Some((earliest, _, _)) => position < *earliest,
+                None => true,
+            };
+
+            if is_earlier {
+                earliest = Some((position, index, found));
+            }
+        }
+
+        let found = match earliest {
+            Some((_, index, found)) => found
+                .into_iter()
+                .map(|groups| groups.with_needle_index(index))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Ok(found)
     }
-}
 
-impl<T: Needle> Needle for &T {
-    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
-        T::check(self, buf, eof)
+    fn description(&self) -> String {
+        let position = match self.position {
+            Position::Start => "earliest_start",
+            Position::End => "earliest_end",
+        };
+
+        format!("Any([{}]).{}()", describe_list(self.needles), position)
     }
 }
 
-impl Needle for Box<dyn Needle> {
-    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
-        self.as_ref().check(buf, eof)
+impl_needle_for_lists!(AnyEarliest, |this| AnyEarliest::new(
+    &this.needles[..],
+    this.position
+));
+
+/// Sequence matches needles one after another in the given order.
+///
+/// Each needle is looked up after the end of a match of a previous one.
+/// All the matches are reported, so [crate::Found::before] returns bytes before the first one.
+///
+/// ```no_run,ignore
+/// Sequence(["login:", "password:"])
+/// ```
+///
+/// To be able to use a different type of needles you can call [Sequence::boxed].
+///
+/// ```no_run,ignore
+/// Sequence::boxed(vec![Box::new("exit code "), Box::new(Regex("\\d+")), Box::new(Eof)])
+/// ```
+pub struct Sequence<I>(pub I);
+
+impl Sequence<Vec<Box<dyn Needle>>> {
+    /// Constructs a sequence of a different type of needles.
+    pub fn boxed(v: Vec<Box<dyn Needle>>) -> Self {
+        Self(v)
+    }
+}
+
+impl<T> Needle for Sequence<&[T]>
+where
+    T: Needle,
+{
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        let mut groups = Vec::new();
+        let mut offset = 0;
+        for needle in self.0.iter() {
+            let found = match first_match(needle, &buf[offset..], ctx)? {
+                Some(found) => found.shift(offset),
+                None => return Ok(Vec::new()),
+            };
+
+            offset = found.bounds().end();
+            groups.push(found);
+        }
+
+        Ok(groups)
+    }
+
+    fn description(&self) -> String {
+        format!("Sequence([{}])", describe_list(self.0))
+    }
+}
+
+impl_needle_for_lists!(Sequence, |this| Sequence(&this.0[..]));
+
+/// All matches when each of needles has a match, in any order.
+///
+/// The matches are reported in the order of needles.
+///
+/// ```no_run,ignore
+/// All(["ready", "listening"])
+/// ```
+///
+/// To be able to use a different type of needles you can call [All::boxed].
+///
+/// ```no_run,ignore
+/// All::boxed(vec![Box::new("ready"), Box::new(Regex("port \\d+"))])
+/// ```
+pub struct All<I>(pub I);
+
+impl All<Vec<Box<dyn Needle>>> {
+    /// Constructs a list of a different type of needles.
+    pub fn boxed(v: Vec<Box<dyn Needle>>) -> Self {
+        Self(v)
+    }
+}
+
+impl<T> Needle for All<&[T]>
+where
+    T: Needle,
+{
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        let mut groups = Vec::new();
+        for needle in self.0.iter() {
+            match first_match(needle, buf, ctx)? {
+                Some(found) => groups.push(found),
+                None => return Ok(Vec::new()),
+            }
+        }
+
+        Ok(groups)
+    }
+
+    fn description(&self) -> String {
+        format!("All([{}])", describe_list(self.0))
+    }
+}
+
+impl_needle_for_lists!(All, |this| All(&this.0[..]));
+
+/// Not matches a first needle only if a second one doesn't appear before it.
+///
+/// If the second needle is found before a match it will never be matched,
+/// so it's meant to be used with a timeout or together with [Any].
+///
+/// ```no_run,ignore
+/// Not("$ ", "error")
+/// ```
+pub struct Not<N, U>(pub N, pub U);
+
+impl<N: Needle, U: Needle> Needle for Not<N, U> {
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        let found = match first_match(&self.0, buf, ctx)? {
+            Some(found) => found,
+            None => return Ok(Vec::new()),
+        };
+
+        // Only the bytes before the match are considered,
+        // so we know for sure that they are not the end of a stream.
+        let before = &buf[..found.bounds().start()];
+        if first_match(&self.1, before, &ctx.with_eof(false))?.is_some() {
+            return Ok(Vec::new());
+        }
+
+        Ok(vec![found])
+    }
+
+    fn description(&self) -> String {
+        format!("Not({}, {})", self.0.description(), self.1.description())
+    }
+}
+
+/// Within matches a needle only if its match ends within the first N bytes.
+///
+/// ```no_run,ignore
+/// Within("$ ", 100)
+/// ```
+pub struct Within<N>(pub N, pub usize);
+
+impl<N: Needle> Needle for Within<N> {
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        // EOF is relevant only if all the data fits in the window.
+        let (buf, ctx) = match buf.len() > self.1 {
+            true => (&buf[..self.1], ctx.with_eof(false)),
+            false => (buf, ctx.with_eof(ctx.is_eof())),
+        };
+
+        match first_match(&self.0, buf, &ctx)? {
+            Some(found) => Ok(vec![found]),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("Within({}, {})", self.0.description(), self.1)
+    }
+}
+
+/// Repeat matches N consequent non-overlapping occurrences of a needle.
+///
+/// ```no_run,ignore
+/// Repeat("\r\n", 3)
+/// ```
+pub struct Repeat<N>(pub N, pub usize);
+
+impl<N: Needle> Needle for Repeat<N> {
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        if self.1 == 0 {
+            return Ok(vec![MatchGroups::from(Match::new(0, 0))]);
+        }
+
+        let mut groups = Vec::with_capacity(self.1);
+        let mut offset = 0;
+        for _ in 0..self.1 {
+            let found = match first_match(&self.0, &buf[offset..], ctx)? {
+                Some(found) => found.shift(offset),
+                None => return Ok(Vec::new()),
+            };
+
+            offset = found.bounds().end();
+            groups.push(found);
+        }
+
+        Ok(groups)
+    }
+
+    fn description(&self) -> String {
+        format!("Repeat({}, {})", self.0.description(), self.1)
+    }
+}
+
+/// Plain matches a needle against a text with ANSI escape sequences removed.
+///
+/// So a colored output like `h\x1b[1mello` is matched by `"hello"`.
+/// The matches are mapped back to the original bytes.
+/// A match covers the sequences around it only at the beginning and at the end of a text.
+///
+/// ```no_run,ignore
+/// Plain("hello")
+/// ```
+pub struct Plain<N>(pub N);
+
+impl<N: Needle> Needle for Plain<N> {
+    fn check_in_context(&self, buf: &[u8], ctx: &NeedleContext<'_>) -> Result<Vec<Match>, Error> {
+        let text = StrippedText::new(buf);
+        let matches = self.0.check_in_context(text.as_bytes(), ctx)?;
+        Ok(matches.into_iter().map(|m| text.to_original(m)).collect())
+    }
+
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        let text = StrippedText::new(buf);
+        let groups = self.0.check_groups_in_context(text.as_bytes(), ctx)?;
+        Ok(groups
+            .into_iter()
+            .map(|groups| groups.map(|m| text.to_original(m)))
+            .collect())
+    }
+
+    fn description(&self) -> String {
+        format!("Plain({})", self.0.description())
+    }
+}
+
+/// MaybePlain is a needle which is wrapped in [Plain] if it's asked to.
+///
+/// It's used by a session which strips ANSI escape sequences.
+pub(crate) enum MaybePlain<N> {
+    Raw(N),
+    Plain(Plain<N>),
+}
+
+impl<N> MaybePlain<N> {
+    pub(crate) fn new(needle: N, plain: bool) -> Self {
+        match plain {
+            true => Self::Plain(Plain(needle)),
+            false => Self::Raw(needle),
+        }
+    }
+}
+
+impl<N: Needle> Needle for MaybePlain<N> {
+    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
+        match self {
+            Self::Raw(needle) => needle.check(buf, eof),
+            Self::Plain(needle) => needle.check(buf, eof),
+        }
+    }
+
+    fn check_groups(&self, buf: &[u8], eof: bool) -> Result<Vec<MatchGroups>, Error> {
+        match self {
+            Self::Raw(needle) => needle.check_groups(buf, eof),
+            Self::Plain(needle) => needle.check_groups(buf, eof),
+        }
+    }
+
+    fn check_in_context(&self, buf: &[u8], ctx: &NeedleContext<'_>) -> Result<Vec<Match>, Error> {
+        match self {
+            Self::Raw(needle) => needle.check_in_context(buf, ctx),
+            Self::Plain(needle) => needle.check_in_context(buf, ctx),
+        }
+    }
+
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        match self {
+            Self::Raw(needle) => needle.check_groups_in_context(buf, ctx),
+            Self::Plain(needle) => needle.check_groups_in_context(buf, ctx),
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            Self::Raw(needle) => needle.description(),
+            Self::Plain(needle) => needle.description(),
+        }
+    }
+}
+
+/// Returns a first match of a needle with its groups.
+fn first_match<N: Needle + ?Sized>(
+    needle: &N,
+    buf: &[u8],
+    ctx: &NeedleContext<'_>,
+) -> Result<Option<MatchGroups>, Error> {
+    let groups = needle.check_groups_in_context(buf, ctx)?;
+    Ok(groups
+        .into_iter()
+        .find(|groups| groups.matches().next().is_some()))
+}
+
+/// Returns descriptions of needles separated by comma.
+fn describe_list<T: Needle>(needles: &[T]) -> String {
+    needles
+        .iter()
+        .map(|needle| needle.description())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Runs a function with a context which has no information about time.
+fn with_default_context<R>(eof: bool, f: impl FnOnce(&NeedleContext<'_>) -> R) -> R {
+    let recheck = Cell::new(None);
+    f(&NeedleContext::new(eof, Duration::ZERO, &recheck))
+}
+
+/// Returns all matches of groups in a flat list.
+pub(crate) fn flatten_groups(groups: &[MatchGroups]) -> Vec<Match> {
+    groups
+        .iter()
+        .flat_map(|groups| groups.matches().cloned())
+        .collect()
+}
+
+impl<T: Needle> Needle for &T {
+    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
+        T::check(self, buf, eof)
+    }
+
+    fn check_groups(&self, buf: &[u8], eof: bool) -> Result<Vec<MatchGroups>, Error> {
+        T::check_groups(self, buf, eof)
+    }
+
+    fn check_in_context(&self, buf: &[u8], ctx: &NeedleContext<'_>) -> Result<Vec<Match>, Error> {
+        T::check_in_context(self, buf, ctx)
+    }
+
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        T::check_groups_in_context(self, buf, ctx)
+    }
+
+    fn description(&self) -> String {
+        T::description(self)
+    }
+}
+
+impl Needle for Box<dyn Needle> {
+    fn check(&self, buf: &[u8], eof: bool) -> Result<Vec<Match>, Error> {
+        self.as_ref().check(buf, eof)
+    }
+
+    fn check_groups(&self, buf: &[u8], eof: bool) -> Result<Vec<MatchGroups>, Error> {
+        self.as_ref().check_groups(buf, eof)
+    }
+
+    fn check_in_context(&self, buf: &[u8], ctx: &NeedleContext<'_>) -> Result<Vec<Match>, Error> {
+        self.as_ref().check_in_context(buf, ctx)
+    }
+
+    fn check_groups_in_context(
+        &self,
+        buf: &[u8],
+        ctx: &NeedleContext<'_>,
+    ) -> Result<Vec<MatchGroups>, Error> {
+        self.as_ref().check_groups_in_context(buf, ctx)
+    }
+
+    fn description(&self) -> String {
+        self.as_ref().description()
     }
 }
 
@@ -250,6 +1322,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_regex_groups() {
+        let groups = Regex(r"(?P<key>\w+)=(\d+)?")
+            .check_groups(b"asd=123 qwe=", false)
+            .unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].get(0), Some(&Match::new(0, 7)));
+        assert_eq!(groups[0].name("key"), Some(&Match::new(0, 3)));
+        assert_eq!(groups[0].get(2), Some(&Match::new(4, 7)));
+        assert_eq!(groups[1].name("key"), Some(&Match::new(8, 11)));
+        assert_eq!(groups[1].get(2), None);
+        assert_eq!(groups[1].len(), 3);
+
+        let groups = Any::boxed(vec![Box::new("zzz"), Box::new(Regex(r"(\d)(\d)"))])
+            .check_groups(b"asd=123", false)
+            .unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].get(2), Some(&Match::new(5, 6)));
+
+        let groups = "123".check_groups(b"asd=123", false).unwrap();
+        assert_eq!(groups, vec![MatchGroups::from(Match::new(4, 7))]);
+    }
+
+    #[test]
+    fn test_compiled_regex() {
+        let re = CompiledRegex::new(r"(\w+)=(\w+)").unwrap();
+        assert_eq!(
+            re.check(b"asd=123", false).unwrap(),
+            vec![Match::new(0, 7), Match::new(0, 3), Match::new(4, 7)]
+        );
+        assert_eq!(re.check(b"asd", false).unwrap(), vec![]);
+
+        let re = CompiledRegex::from(regex::bytes::Regex::new("[0-9]+").unwrap());
+        assert_eq!(re.check(b"+012345", false).unwrap(), vec![Match::new(1, 7)]);
+
+        assert!(matches!(
+            CompiledRegex::new("[0-9"),
+            Err(Error::RegexParsing)
+        ));
+    }
+
     #[test]
     fn test_eof() {
         assert_eq!(Eof.check(b"qwe", true).unwrap(), vec![Match::new(0, 3)]);
@@ -353,4 +1466,488 @@ mod tests {
             vec![Match::new(3, 6)]
         );
     }
+
+    #[test]
+    fn test_any_needle_index() {
+        let groups = Any(["123", "rty", "qwe"])
+            .check_groups(b"qwerty", false)
+            .unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].needle_index(), Some(1));
+        assert_eq!(groups[0].get(0), Some(&Match::new(3, 6)));
+
+        let groups = Any::boxed(vec![Box::new("123"), Box::new(Regex("(w)(e)"))])
+            .check_groups(b"qwerty", false)
+            .unwrap();
+        assert_eq!(groups[0].needle_index(), Some(1));
+
+        let groups = "rty".check_groups(b"qwerty", false).unwrap();
+        assert_eq!(groups[0].needle_index(), None);
+    }
+
+    #[test]
+    fn test_any_list_order() {
+        // The first needle in the list wins even though another one is found earlier.
+        let groups = Any(["rty", "we"]).check_groups(b"qwerty", false).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].get(0), Some(&Match::new(3, 6)));
+        assert_eq!(groups[0].needle_index(), Some(0));
+    }
+
+    #[test]
+    fn test_any_earliest_start() {
+        let groups = Any(["rty", "we"])
+            .earliest_start()
+            .check_groups(b"qwerty", false)
+            .unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].get(0), Some(&Match::new(1, 3)));
+        assert_eq!(groups[0].needle_index(), Some(1));
+
+        // A match which starts first wins even though it ends later.
+        assert_eq!(
+            Any(["er", "wert"])
+                .earliest_start()
+                .check(b"qwerty", false)
+                .unwrap(),
+            vec![Match::new(1, 5)]
+        );
+
+        // Ties are resolved in the list order.
+        let groups = Any(vec!["wer", "we"])
+            .earliest_start()
+            .check_groups(b"qwerty", false)
+            .unwrap();
+        assert_eq!(groups[0].needle_index(), Some(0));
+
+        assert_eq!(
+            Any(&["123", "345"])
+                .earliest_start()
+                .check(b"qwerty", false)
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_any_earliest_end() {
+        let groups = Any(["wert", "er"])
+            .earliest_end()
+            .check_groups(b"qwerty", false)
+            .unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].get(0), Some(&Match::new(2, 4)));
+        assert_eq!(groups[0].needle_index(), Some(1));
+
+        // Ties are resolved in the list order.
+        let groups = Any::boxed(vec![Box::new("wer"), Box::new(Regex("e(r)"))])
+            .earliest_end()
+            .check_groups(b"qwerty", false)
+            .unwrap();
+        assert_eq!(groups[0].needle_index(), Some(0));
+
+        assert_eq!(
+            Any(&["123", "345"][..])
+                .earliest_end()
+                .check(b"qwerty", false)
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_sequence() {
+        assert_eq!(
+            Sequence(["we", "ty"]).check(b"qwerty", false).unwrap(),
+            vec![Match::new(1, 3), Match::new(4, 6)]
+        );
+        // The order matters.
+        assert_eq!(
+            Sequence(["ty", "we"]).check(b"qwerty", false).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            Sequence::boxed(vec![
+                Box::new("w"),
+                Box::new(Regex(r"\w(\w)")),
+                Box::new(Eof)
+            ])
+            .check(b"qwerty", true)
+            .unwrap(),
+            vec![
+                Match::new(1, 2),
+                Match::new(2, 4),
+                Match::new(3, 4),
+                Match::new(4, 6)
+            ]
+        );
+        assert_eq!(
+            Sequence::boxed(vec![Box::new("w"), Box::new(Eof)])
+                .check(b"qwerty", false)
+                .unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            Sequence(&["q", "q"]).check(b"qwerty", false).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            Sequence(vec![NBytes(2), NBytes(3)])
+                .check(b"qwerty", false)
+                .unwrap(),
+            vec![Match::new(0, 2), Match::new(2, 5)]
+        );
+    }
+
+    #[test]
+    fn test_all() {
+        assert_eq!(
+            All(["ty", "we"]).check(b"qwerty", false).unwrap(),
+            vec![Match::new(4, 6), Match::new(1, 3)]
+        );
+        assert_eq!(All(["ty", "123"]).check(b"qwerty", false).unwrap(), vec![]);
+        assert_eq!(
+            All::boxed(vec![Box::new(Regex("r.")), Box::new(Eof)])
+                .check(b"qwerty", true)
+                .unwrap(),
+            vec![Match::new(3, 5), Match::new(0, 6)]
+        );
+        assert_eq!(
+            All::boxed(vec![Box::new(Regex("r.")), Box::new(Eof)])
+                .check(b"qwerty", false)
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_not() {
+        assert_eq!(
+            Not("rty", "abc").check(b"qwerty", false).unwrap(),
+            vec![Match::new(3, 6)]
+        );
+        assert_eq!(Not("rty", "we").check(b"qwerty", false).unwrap(), vec![]);
+        // The second needle after the match doesn't matter.
+        assert_eq!(
+            Not("we", "ty").check(b"qwerty", false).unwrap(),
+            vec![Match::new(1, 3)]
+        );
+        // The overlap isn't considered as appearing before.
+        assert_eq!(
+            Not("wer", "we").check(b"qwerty", false).unwrap(),
+            vec![Match::new(1, 4)]
+        );
+        assert_eq!(
+            Not(Regex("t."), "e").check(b"qwerty", true).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            Not(Eof, "1").check(b"qwerty", true).unwrap(),
+            vec![Match::new(0, 6)]
+        );
+    }
+
+    #[test]
+    fn test_within() {
+        assert_eq!(
+            Within("er", 4).check(b"qwerty", false).unwrap(),
+            vec![Match::new(2, 4)]
+        );
+        assert_eq!(Within("ert", 4).check(b"qwerty", false).unwrap(), vec![]);
+        assert_eq!(Within(Eof, 4).check(b"qwerty", true).unwrap(), vec![]);
+        assert_eq!(
+            Within(Eof, 6).check(b"qwerty", true).unwrap(),
+            vec![Match::new(0, 6)]
+        );
+        assert_eq!(
+            Within(Regex(r"\w+"), 3).check(b"qwerty", false).unwrap(),
+            vec![Match::new(0, 3)]
+        );
+    }
+
+    #[test]
+    fn test_repeat() {
+        assert_eq!(
+            Repeat("\n", 2).check(b"1\n2\n3\n", false).unwrap(),
+            vec![Match::new(1, 2), Match::new(3, 4)]
+        );
+        assert_eq!(Repeat("\n", 4).check(b"1\n2\n3\n", false).unwrap(), vec![]);
+        // Occurrences don't overlap.
+        assert_eq!(Repeat("aa", 2).check(b"aaa", false).unwrap(), vec![]);
+        assert_eq!(
+            Repeat(Regex(r"(\d)\n"), 2).check(b"1\n2\n", false).unwrap(),
+            vec![
+                Match::new(0, 2),
+                Match::new(0, 1),
+                Match::new(2, 4),
+                Match::new(2, 3)
+            ]
+        );
+        assert_eq!(
+            Repeat("a", 0).check(b"qwerty", false).unwrap(),
+            vec![Match::new(0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_check_in_context_uses_groups() {
+        /// A needle which matches only by a context.
+        struct Context;
+
+        impl Needle for Context {
+            fn check(&self, _: &[u8], _: bool) -> Result<Vec<Match>, Error> {
+                Ok(Vec::new())
+            }
+
+            fn check_groups_in_context(
+                &self,
+                buf: &[u8],
+                ctx: &NeedleContext<'_>,
+            ) -> Result<Vec<MatchGroups>, Error> {
+                match ctx.is_eof() {
+                    true => Ok(vec![MatchGroups::from(Match::new(0, buf.len()))]),
+                    false => Ok(Vec::new()),
+                }
+            }
+        }
+
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(true, Duration::ZERO, &recheck);
+        assert_eq!(
+            Context.check_in_context(b"qwe", &ctx).unwrap(),
+            vec![Match::new(0, 3)]
+        );
+        assert_eq!(
+            Any([Context]).check_in_context(b"qwe", &ctx).unwrap(),
+            vec![Match::new(0, 3)]
+        );
+        assert_eq!(Context.check(b"qwe", true).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_idle() {
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(false, Duration::from_millis(100), &recheck);
+        assert_eq!(
+            Idle(Duration::from_millis(300))
+                .check_in_context(b"qwe", &ctx)
+                .unwrap(),
+            vec![]
+        );
+        assert_eq!(recheck.get(), Some(Duration::from_millis(200)));
+
+        let ctx = NeedleContext::new(false, Duration::from_millis(300), &recheck);
+        assert_eq!(
+            Idle(Duration::from_millis(300))
+                .check_in_context(b"qwe", &ctx)
+                .unwrap(),
+            vec![Match::new(0, 3)]
+        );
+
+        // EOF means there will be no more output.
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(true, Duration::ZERO, &recheck);
+        assert_eq!(
+            Idle(Duration::from_secs(1))
+                .check_in_context(b"qwe", &ctx)
+                .unwrap(),
+            vec![Match::new(0, 3)]
+        );
+        assert_eq!(recheck.get(), None);
+
+        // There's no information about time in a plain check.
+        assert_eq!(
+            Idle(Duration::from_secs(1)).check(b"qwe", false).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            Idle(Duration::ZERO).check(b"qwe", false).unwrap(),
+            vec![Match::new(0, 3)]
+        );
+    }
+
+    #[test]
+    fn test_idle_in_combinators() {
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(false, Duration::from_millis(500), &recheck);
+        let needle = Sequence::boxed(vec![
+            Box::new("$ "),
+            Box::new(Idle(Duration::from_millis(100))),
+        ]);
+        assert_eq!(
+            needle.check_in_context(b"$ ls", &ctx).unwrap(),
+            vec![Match::new(0, 2), Match::new(2, 4)]
+        );
+
+        // Bytes before a match are followed by more data so they are never idle.
+        assert_eq!(
+            Not("$ ", Idle(Duration::from_millis(100)))
+                .check_in_context(b"qwe$ ", &ctx)
+                .unwrap(),
+            vec![Match::new(3, 5)]
+        );
+
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(false, Duration::from_millis(100), &recheck);
+        let needle = Any::boxed(vec![
+            Box::new(Idle(Duration::from_millis(500))),
+            Box::new(Idle(Duration::from_millis(300))),
+            Box::new("$ "),
+        ]);
+        assert_eq!(
+            needle.check_groups_in_context(b"qwe", &ctx).unwrap(),
+            vec![]
+        );
+        // The shortest time is asked for.
+        assert_eq!(recheck.get(), Some(Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn test_screen_text() {
+        let mut screen = Screen::new(20, 2);
+        screen.process(b"loading...\r\x1b[Kdone");
+
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(false, Duration::ZERO, &recheck).with_screen(Some(&screen));
+        assert_eq!(
+            ScreenText("done").check_in_context(b"qwe", &ctx).unwrap(),
+            vec![Match::new(0, 3)]
+        );
+        assert_eq!(
+            ScreenText("loading")
+                .check_in_context(b"qwe", &ctx)
+                .unwrap(),
+            vec![]
+        );
+
+        // A part of a buffer doesn't correspond to a screen.
+        assert_eq!(
+            Not("e", ScreenText("done"))
+                .check_in_context(b"qwe", &ctx)
+                .unwrap(),
+            vec![Match::new(2, 3)]
+        );
+
+        // There's no screen in a plain check.
+        assert_eq!(ScreenText("done").check(b"qwe", false).unwrap(), vec![]);
+        assert_eq!(
+            ScreenText(String::from("done")).description(),
+            r#"ScreenText("done")"#
+        );
+    }
+
+    #[test]
+    fn test_styled_text() {
+        let mut screen = Screen::new(20, 2);
+        screen.process(b"failed\r\n\x1b[1;31mfailed\x1b[m \x1b[7mok");
+
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(false, Duration::ZERO, &recheck).with_screen(Some(&screen));
+        let check = |needle: StyledText<&str>| needle.check_in_context(b"qwe", &ctx).unwrap();
+
+        assert_eq!(
+            check(StyledText::new("failed").foreground(Color::RED)),
+            vec![Match::new(0, 3)]
+        );
+        assert_eq!(
+            check(StyledText::new("failed").bold(true).underline(false)),
+            vec![Match::new(0, 3)]
+        );
+        assert_eq!(
+            check(StyledText::new("ok").reverse(true)),
+            vec![Match::new(0, 3)]
+        );
+        assert_eq!(
+            check(StyledText::new("failed").background(Color::RED)),
+            vec![]
+        );
+        assert_eq!(check(StyledText::new("ok").reverse(false)), vec![]);
+        assert_eq!(check(StyledText::new("missing")), vec![]);
+
+        // There's no screen in a plain check.
+        assert_eq!(
+            StyledText::new("failed").check(b"qwe", false).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            StyledText::new("failed")
+                .foreground(Color::RED)
+                .bold(true)
+                .description(),
+            r#"StyledText("failed", foreground: Indexed(1), bold: true)"#
+        );
+    }
+
+    #[test]
+    fn test_plain() {
+        let buf = b"\x1b[32mok\x1b[0m: h\x1b[1mello\x1b[0m world";
+
+        assert_eq!("hello".check(buf, false).unwrap(), vec![]);
+        let m = Plain("hello").check(buf, false).unwrap();
+        assert_eq!(m, vec![Match::new(13, 22)]);
+        assert_eq!(&buf[13..22], b"h\x1b[1mello");
+
+        let groups = Plain(Regex(r"(\w+): (?P<word>\w+)"))
+            .check_groups(buf, false)
+            .unwrap();
+        let m = groups[0].get(1).unwrap();
+        assert_eq!(&buf[m.start()..m.end()], b"\x1b[32mok");
+        let m = groups[0].name("word").unwrap();
+        assert_eq!(&buf[m.start()..m.end()], b"h\x1b[1mello");
+
+        // A match which reaches the end covers trailing sequences.
+        let buf = b"\x1b[31mfailed\x1b[0m";
+        assert_eq!(
+            Plain("ailed").check(buf, false).unwrap(),
+            vec![Match::new(6, 15)]
+        );
+        assert_eq!(
+            Plain(Eof).check(buf, true).unwrap(),
+            vec![Match::new(0, 15)]
+        );
+
+        assert_eq!(Plain("hello").description(), r#"Plain("hello")"#);
+    }
+
+    #[test]
+    fn test_description() {
+        assert_eq!("Hello".description(), r#""Hello""#);
+        assert_eq!(String::from("Hello").description(), r#""Hello""#);
+        assert_eq!(b"\r\n"[..].description(), r#"b"\r\n""#);
+        assert_eq!(b'\n'.description(), r#"b'\n'"#);
+        assert_eq!('a'.description(), "'a'");
+        assert_eq!(Regex(r"\d+").description(), r#"Regex("\\d+")"#);
+        assert_eq!(Eof.description(), "Eof");
+        assert_eq!(NBytes(3).description(), "NBytes(3)");
+        assert_eq!(
+            Idle(Duration::from_millis(500)).description(),
+            "Idle(500ms)"
+        );
+        assert_eq!(Any(["a", "b"]).description(), r#"Any(["a", "b"])"#);
+        assert_eq!(
+            Any(["a", "b"]).earliest_end().description(),
+            r#"Any(["a", "b"]).earliest_end()"#
+        );
+        assert_eq!(
+            Sequence::boxed(vec![Box::new("a"), Box::new(Eof)]).description(),
+            r#"Sequence(["a", Eof])"#
+        );
+        assert_eq!(
+            Not(Within("$ ", 10), Repeat("error", 2)).description(),
+            r#"Not(Within("$ ", 10), Repeat("error", 2))"#
+        );
+
+        struct Custom;
+
+        impl Needle for Custom {
+            fn check(&self, _: &[u8], _: bool) -> Result<Vec<Match>, Error> {
+                Ok(Vec::new())
+            }
+        }
+
+        assert!(Custom.description().ends_with("Custom"));
+        assert!((Box::new(Custom) as Box<dyn Needle>)
+            .description()
+            .ends_with("Custom"));
+    }
 }
@@ -0,0 +1,373 @@
+//! Module contains a pipe based backend of a [Session].
+//!
+//! A process is spawned with plain pipes instead of a pseudo terminal,
+//! so it sees that its stdin and stdout are not a TTY.
+//! It may be usefull for programs which behave differently when run interactively.
+//!
+//! ```no_run
+//! # #[cfg(not(feature = "async"))]
+//! # {
+//! use expectrl::pipe::PipeSession;
+//! use std::process::Command;
+//!
+//! let mut session = PipeSession::spawn_pipe(Command::new("tty")).unwrap();
+//! session.expect("not a tty").unwrap();
+//! # }
+//! ```
+
+use crate::{
+    error::Error,
+    session::{wait_process_timeout, Session},
+    stream::nix_error_to_io,
+};
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::{self, Signal},
+        wait::{waitpid, WaitPidFlag},
+    },
+    unistd::{setpgid, Pid},
+};
+use ptyprocess::WaitStatus;
+use std::{
+    io,
+    os::unix::process::CommandExt,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    time::Duration,
+};
+
+#[cfg(not(feature = "async"))]
+use crate::{
+    session::terminate_process_group,
+    stream::{make_non_blocking, wait_fd_readable, NonBlocking},
+};
+#[cfg(not(feature = "async"))]
+use std::{
+    io::{Read, Write},
+    os::unix::prelude::AsRawFd,
+};
+
+#[cfg(feature = "async")]
+use crate::session::terminate_process_group_async;
+#[cfg(feature = "async")]
+use async_io::Async;
+#[cfg(feature = "async")]
+use futures_lite::{AsyncRead, AsyncWrite};
+#[cfg(feature = "async")]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A [Session] whose process is connected via pipes.
+pub type PipeSession = Session<PipeProcess, PipeStream>;
+
+impl Session<PipeProcess, PipeStream> {
+    /// Spawns a command with its stdin and stdout connected to pipes.
+    ///
+    /// A stderr is not touched, so it's inherited by default.
+    /// It can be changed by [Command::stderr].
+    pub fn spawn_pipe(mut command: Command) -> Result<Self, Error> {
+        command.stdin(Stdio::piped()).stdout(Stdio::piped());
+        // A process gets its own process group,
+        // so it can be terminated together with its children.
+        //
+        // SAFETY: the closure is called after fork,
+        // it only calls setpgid which is async-signal-safe.
+        unsafe {
+            command
+                .pre_exec(|| setpgid(Pid::from_raw(0), Pid::from_raw(0)).map_err(nix_error_to_io));
+        }
+
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let stream = PipeStream::new(stdin, stdout)?;
+        let process = PipeProcess::new(child);
+        let process_group = process.pid();
+
+        let mut session = Self::new(process, stream);
+        session.set_process_group(process_group);
+
+        Ok(session)
+    }
+
+    /// Terminates a process and its process group.
+    ///
+    /// See [Session::terminate].
+    #[cfg(not(feature = "async"))]
+    pub fn terminate(&mut self, grace: Duration) -> Result<WaitStatus, Error> {
+        let status = terminate_process_group(self.pid(), grace)?;
+        self.unset_process_group();
+        Ok(status)
+    }
+
+    /// Terminates a process and its process group.
+    ///
+    /// See [Session::terminate].
+    #[cfg(feature = "async")]
+    pub async fn terminate(&mut self, grace: Duration) -> Result<WaitStatus, Error> {
+        let status = terminate_process_group_async(self.pid(), grace).await?;
+        self.unset_process_group();
+        Ok(status)
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl Session<PipeProcess, PipeStream> {
+    /// Waits for a process to exit but not longer than a given timeout.
+    ///
+    /// See [Session::wait_timeout].
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<Option<WaitStatus>, Error> {
+        wait_process_timeout(self.pid(), timeout)
+    }
+}
+
+#[cfg(feature = "async")]
+impl Session<PipeProcess, PipeStream> {
+    /// Waits for a process to exit but not longer than a given timeout.
+    ///
+    /// See [Session::wait_timeout].
+    pub async fn wait_timeout(&self, timeout: Duration) -> Result<Option<WaitStatus>, Error> {
+        wait_process_timeout(self.pid(), timeout).await
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl Session<PipeProcess, PipeStream> {
+    /// Closes a stdin of a process.
+    ///
+    /// It's an equivalent of sending EOF to a pseudo terminal.
+    pub fn send_eof(&mut self) -> io::Result<()> {
+        self.get_stream_mut().close_stdin();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl Session<PipeProcess, PipeStream> {
+    /// Closes a stdin of a process.
+    ///
+    /// It's an equivalent of sending EOF to a pseudo terminal.
+    pub async fn send_eof(&mut self) -> io::Result<()> {
+        self.get_stream_mut().close_stdin();
+        Ok(())
+    }
+}
+
+/// PipeProcess is a handle of a process spawned with pipes.
+///
+/// Its interface mirrors the one of [ptyprocess::PtyProcess],
+/// so an exit status is reported as [WaitStatus].
+///
+/// If a process is still alive on drop it will be killed.
+#[derive(Debug)]
+pub struct PipeProcess {
+    child: Child,
+    pid: Pid,
+}
+
+impl PipeProcess {
+    fn new(child: Child) -> Self {
+        let pid = Pid::from_raw(child.id() as i32);
+        Self { child, pid }
+    }
+
+    /// Returns a pid of a child process.
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// Returns a [std::process::Child] handle of a process.
+    ///
+    /// Be carefull calling its `wait` methods,
+    /// because a status can be collected only once.
+    pub fn get_child(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    /// Status returns a status a of child process.
+    pub fn status(&self) -> nix::Result<WaitStatus> {
+        waitpid(self.pid, Some(WaitPidFlag::WNOHANG))
+    }
+
+    /// Wait blocks until a child process exits.
+    ///
+    /// It returns a error if the child was DEAD or not exist
+    /// at the time of a call.
+    pub fn wait(&self) -> nix::Result<WaitStatus> {
+        waitpid(self.pid, None)
+    }
+
+    /// Checks if a process is still exists.
+    ///
+    /// It's a non blocking operation.
+    pub fn is_alive(&self) -> nix::Result<bool> {
+        match self.status() {
+            Ok(WaitStatus::StillAlive) => Ok(true),
+            Ok(_) | Err(nix::Error::Sys(Errno::ECHILD)) | Err(nix::Error::Sys(Errno::ESRCH)) => {
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Kill sends a signal to a child process.
+    ///
+    /// The operation is non-blocking.
+    pub fn kill(&mut self, signal: Signal) -> nix::Result<()> {
+        signal::kill(self.pid, signal)
+    }
+
+    /// Signal is an alias to [PipeProcess::kill].
+    pub fn signal(&mut self, signal: Signal) -> nix::Result<()> {
+        self.kill(signal)
+    }
+}
+
+impl Drop for PipeProcess {
+    fn drop(&mut self) {
+        if let Ok(WaitStatus::StillAlive) = self.status() {
+            let _ = self.kill(Signal::SIGKILL);
+            let _ = self.wait();
+        }
+    }
+}
+
+/// PipeStream is a duplex stream built from a stdin and stdout pipes of a process.
+#[derive(Debug)]
+pub struct PipeStream {
+    #[cfg(not(feature = "async"))]
+    stdin: Option<ChildStdin>,
+    #[cfg(not(feature = "async"))]
+    stdout: ChildStdout,
+    #[cfg(feature = "async")]
+    stdin: Option<Async<ChildStdin>>,
+    #[cfg(feature = "async")]
+    stdout: Async<ChildStdout>,
+}
+
+impl PipeStream {
+    #[cfg(not(feature = "async"))]
+    fn new(stdin: ChildStdin, stdout: ChildStdout) -> io::Result<Self> {
+        Ok(Self {
+            stdin: Some(stdin),
+            stdout,
+        })
+    }
+
+    #[cfg(feature = "async")]
+    fn new(stdin: ChildStdin, stdout: ChildStdout) -> io::Result<Self> {
+        Ok(Self {
+            stdin: Some(Async::new(stdin)?),
+            stdout: Async::new(stdout)?,
+        })
+    }
+
+    /// Closes a stdin pipe.
+    ///
+    /// All further writes will fail with [io::ErrorKind::BrokenPipe].
+    pub fn close_stdin(&mut self) {
+        self.stdin = None;
+    }
+}
+
+fn closed_stdin_error() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "stdin of a process is closed")
+}
+
+#[cfg(not(feature = "async"))]
+impl Write for PipeStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.stdin {
+            Some(stdin) => stdin.write(buf),
+            None => Err(closed_stdin_error()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stdin {
+            Some(stdin) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        match &mut self.stdin {
+            Some(stdin) => stdin.write_vectored(bufs),
+            None => Err(closed_stdin_error()),
+        }
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl Read for PipeStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdout.read(buf)
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl NonBlocking for PipeStream {
+    fn set_non_blocking(&mut self) -> io::Result<()> {
+        make_non_blocking(self.stdout.as_raw_fd(), true)
+    }
+
+    fn set_blocking(&mut self) -> io::Result<()> {
+        make_non_blocking(self.stdout.as_raw_fd(), false)
+    }
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        wait_fd_readable(self.stdout.as_raw_fd(), timeout)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncWrite for PipeStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.stdin {
+            Some(stdin) => Pin::new(stdin).poll_write(cx, buf),
+            None => Poll::Ready(Err(closed_stdin_error())),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.stdin {
+            Some(stdin) => Pin::new(stdin).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.stdin {
+            Some(stdin) => Pin::new(stdin).poll_close(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.stdin {
+            Some(stdin) => Pin::new(stdin).poll_write_vectored(cx, bufs),
+            None => Poll::Ready(Err(closed_stdin_error())),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncRead for PipeStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stdout).poll_read(cx, buf)
+    }
+}
@@ -1,7 +1,10 @@
 //! This module contains a list of special Sessions that can be spawned.
 
 use crate::{error::Error, Found, Session};
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    time::{Duration, Instant},
+};
 
 #[cfg(unix)]
 use std::process::Command;
@@ -144,6 +147,18 @@ impl ReplSession {
         quit_command: Option<Q>,
     ) -> Result<Self, Error> {
         let session = Session::spawn(cmd)?;
+        Self::new(session, prompt, quit_command)
+    }
+
+    /// Creates a repl session from an already spawned session.
+    ///
+    /// It doesn't wait for a prompt.
+    #[cfg(unix)]
+    pub fn new<P: AsRef<str>, Q: AsRef<str>>(
+        session: Session,
+        prompt: P,
+        quit_command: Option<Q>,
+    ) -> Result<Self, Error> {
         let is_echo_on = session.get_echo()?;
         let prompt = prompt.as_ref().to_owned();
         let quit_command = quit_command.map(|q| q.as_ref().to_owned());
@@ -224,6 +239,50 @@ impl ReplSession {
         Ok(found.before().to_vec())
     }
 
+    /// Executes a command as [ReplSession::execute] does,
+    /// but fails if it's not finished within a given timeout.
+    ///
+    /// The timeout covers both an echo of the command and a following prompt.
+    #[cfg(not(feature = "async"))]
+    pub fn execute_timeout<S: AsRef<str> + Clone>(
+        &mut self,
+        cmd: S,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, Error> {
+        let deadline = Instant::now() + timeout;
+
+        self.session.send_line(cmd.as_ref())?;
+        if self.is_echo_on {
+            self.session.expect_until(cmd.as_ref(), deadline)?;
+        }
+
+        let prompt = self.prompt.clone();
+        let found = self.session.expect_until(prompt, deadline)?;
+        Ok(found.before().to_vec())
+    }
+
+    /// Executes a command as [ReplSession::execute] does,
+    /// but fails if it's not finished within a given timeout.
+    ///
+    /// See sync version [ReplSession::execute_timeout].
+    #[cfg(feature = "async")]
+    pub async fn execute_timeout<S: AsRef<str> + Clone>(
+        &mut self,
+        cmd: S,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, Error> {
+        let deadline = Instant::now() + timeout;
+
+        self.session.send_line(cmd.as_ref()).await?;
+        if self.is_echo_on {
+            self.session.expect_until(cmd.as_ref(), deadline).await?;
+        }
+
+        let prompt = self.prompt.clone();
+        let found = self.session.expect_until(prompt, deadline).await?;
+        Ok(found.before().to_vec())
+    }
+
     /// Sends line to repl (and flush the output).
     ///
     /// If echo_on=true wait for the input to appear.
@@ -0,0 +1,375 @@
+//! Module contains a [Responder] which answers queries a process sends to a terminal.
+//!
+//! Some programs ask a terminal about a cursor position, its type or colors
+//! and block until they get an answer.
+//! There's no terminal behind a session, so the answers are made up here.
+
+use crate::screen::Screen;
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+/// A maximum length of a sequence which is kept while looking for a query.
+///
+/// Longer sequences are not queries anyway, so they are dropped.
+const MAX_SEQUENCE_LEN: usize = 64;
+
+/// A color reported as a foreground one.
+const FOREGROUND: &str = "rgb:ffff/ffff/ffff";
+
+/// A color reported as a background one.
+const BACKGROUND: &str = "rgb:0000/0000/0000";
+
+/// A query which is answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Query {
+    /// `CSI 5 n`, a device status report.
+    Status,
+    /// `CSI 6 n`, a cursor position report.
+    CursorPosition,
+    /// `CSI ? 6 n`, an extended cursor position report.
+    ExtendedCursorPosition,
+    /// `CSI c`, primary device attributes.
+    DeviceAttributes,
+    /// `OSC 10 ; ?`, a foreground color.
+    /// The flag tells whether a query was terminated by BEL rather than ST.
+    ForegroundColor(bool),
+    /// `OSC 11 ; ?`, a background color.
+    BackgroundColor(bool),
+}
+
+impl Query {
+    /// Returns an answer a terminal would give.
+    ///
+    /// A cursor position is taken from a screen, if there's no one it's the top left corner.
+    pub(crate) fn reply(&self, screen: Option<&Screen>) -> Vec<u8> {
+        let (col, row) = screen.map_or((0, 0), |screen| screen.cursor_position());
+        let osc = |code: u8, color: &str, bel: bool| {
+            let terminator = if bel { "\x07" } else { "\x1b\\" };
+            format!("\x1b]{};{}{}", code, color, terminator)
+        };
+
+        let reply = match self {
+            Query::Status => "\x1b[0n".to_owned(),
+            Query::CursorPosition => format!("\x1b[{};{}R", row + 1, col + 1),
+            Query::ExtendedCursorPosition => format!("\x1b[?{};{}R", row + 1, col + 1),
+            // A VT220 with ANSI colors.
+            Query::DeviceAttributes => "\x1b[?62;22c".to_owned(),
+            Query::ForegroundColor(bel) => osc(10, FOREGROUND, *bel),
+            Query::BackgroundColor(bel) => osc(11, BACKGROUND, *bel),
+        };
+
+        reply.into_bytes()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+/// Responder spots queries in an output of a process and writes replies to its input.
+///
+/// Sequences may be split across reads.
+/// Replies which a writer doesn't accept right away are kept until a next flush,
+/// a stream retries them before it reads or waits for an output.
+pub(crate) struct Responder {
+    writer: Box<dyn Write + Send>,
+    state: State,
+    sequence: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl Responder {
+    /// Creates a responder which writes replies to a given writer.
+    pub(crate) fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Box::new(writer),
+            state: State::default(),
+            sequence: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Looks for a query in an output.
+    ///
+    /// It returns a number of bytes which were looked at,
+    /// and a query if it ends at the last of them.
+    pub(crate) fn scan(&mut self, bytes: &[u8]) -> (usize, Option<Query>) {
+        for (i, &b) in bytes.iter().enumerate() {
+            if let Some(query) = self.advance(b) {
+                return (i + 1, Some(query));
+            }
+        }
+
+        (bytes.len(), None)
+    }
+
+    /// Queues a reply to a query, it's written by [Responder::flush].
+    pub(crate) fn reply(&mut self, query: Query, screen: Option<&Screen>) {
+        self.pending.extend(query.reply(screen));
+    }
+
+    /// Writes queued replies as far as a writer accepts them without blocking.
+    ///
+    /// A writer may share a descriptor which is in a non-blocking mode,
+    /// so the rest is kept for a next call if it's not ready.
+    /// If a writer fails the replies are dropped, as a process is likely gone.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        let result = write_pending(&mut self.writer, &mut self.pending);
+        if result.is_err() {
+            self.pending.clear();
+        }
+
+        result
+    }
+
+    /// Returns true if some replies are not written yet.
+    pub(crate) fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    fn advance(&mut self, b: u8) -> Option<Query> {
+        match (self.state, b) {
+            (State::OscEscape, b'\\') => {
+                self.state = State::Ground;
+                return osc_query(&self.sequence, false);
+            }
+            (State::Osc, 0x07) => {
+                self.state = State::Ground;
+                return osc_query(&self.sequence, true);
+            }
+            (State::Osc, 0x1b) => self.state = State::OscEscape,
+            // It's not a string terminator but a start of a new sequence.
+            (State::OscEscape, _) => {
+                self.state = State::Escape;
+                return self.advance(b);
+            }
+            (_, 0x1b) => self.state = State::Escape,
+            (State::Escape, b'[') => self.start(State::Csi),
+            (State::Escape, b']') => self.start(State::Osc),
+            (State::Csi, 0x20..=0x3f) | (State::Osc, _) => {
+                if self.sequence.len() < MAX_SEQUENCE_LEN {
+                    self.sequence.push(b);
+                } else {
+                    self.state = State::Ground;
+                }
+            }
+            (State::Csi, 0x40..=0x7e) => {
+                self.state = State::Ground;
+                return csi_query(&self.sequence, b);
+            }
+            _ => self.state = State::Ground,
+        }
+
+        None
+    }
+
+    fn start(&mut self, state: State) {
+        self.state = state;
+        self.sequence.clear();
+    }
+}
+
+impl fmt::Debug for Responder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Responder")
+            .field("state", &self.state)
+            .field("sequence", &self.sequence)
+            .finish()
+    }
+}
+
+fn csi_query(params: &[u8], action: u8) -> Option<Query> {
+    match (params, action) {
+        (b"5", b'n') => Some(Query::Status),
+        (b"6", b'n') => Some(Query::CursorPosition),
+        (b"?6", b'n') => Some(Query::ExtendedCursorPosition),
+        (b"" | b"0", b'c') => Some(Query::DeviceAttributes),
+        _ => None,
+    }
+}
+
+fn osc_query(data: &[u8], bel: bool) -> Option<Query> {
+    match data {
+        b"10;?" => Some(Query::ForegroundColor(bel)),
+        b"11;?" => Some(Query::BackgroundColor(bel)),
+        _ => None,
+    }
+}
+
+/// Writes a buffer until a writer would block, the written bytes are removed from it.
+fn write_pending(writer: &mut impl Write, buf: &mut Vec<u8>) -> io::Result<()> {
+    let mut written = 0;
+    let result = loop {
+        if written == buf.len() {
+            break writer.flush();
+        }
+
+        match writer.write(&buf[written..]) {
+            Ok(0) => break Err(io::Error::from(io::ErrorKind::WriteZero)),
+            Ok(n) => written += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => break Err(err),
+        }
+    };
+
+    buf.drain(..written);
+
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn scan_all(output: &[&[u8]]) -> Vec<(usize, Query)> {
+        let mut responder = Responder::new(io::sink());
+        let mut queries = Vec::new();
+        let mut offset = 0;
+        for mut chunk in output.iter().copied() {
+            while !chunk.is_empty() {
+                let (n, query) = responder.scan(chunk);
+                offset += n;
+                chunk = &chunk[n..];
+                if let Some(query) = query {
+                    queries.push((offset, query));
+                }
+            }
+        }
+
+        queries
+    }
+
+    #[test]
+    fn test_scan() {
+        assert_eq!(
+            scan_all(&[b"abc\x1b[6ndef\x1b[c\x1b[?6n\x1b[5n"]),
+            vec![
+                (7, Query::CursorPosition),
+                (13, Query::DeviceAttributes),
+                (18, Query::ExtendedCursorPosition),
+                (22, Query::Status),
+            ]
+        );
+        assert_eq!(
+            scan_all(&[b"\x1b]10;?\x07\x1b]11;?\x1b\\"]),
+            vec![
+                (7, Query::ForegroundColor(true)),
+                (15, Query::BackgroundColor(false)),
+            ]
+        );
+
+        // Sequences split across reads.
+        assert_eq!(
+            scan_all(&[b"a\x1b", b"[", b"6", b"nb\x1b]11;", b"?\x1b", b"\\"]),
+            vec![
+                (5, Query::CursorPosition),
+                (14, Query::BackgroundColor(false))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_ignores_other_sequences() {
+        assert_eq!(
+            scan_all(&[
+                b"\x1b[31m\x1b[2J\x1b[1;1H\x1b[>c\x1b[16n\x1b]0;title\x07\x1b]11;rgb:0/0/0\x07"
+            ]),
+            vec![]
+        );
+
+        // A long sequence is dropped.
+        let mut output = b"\x1b]".to_vec();
+        output.extend([b'1'; 100]);
+        output.extend(b"\x07\x1b[6n");
+        assert_eq!(
+            scan_all(&[&output]),
+            vec![(output.len(), Query::CursorPosition)]
+        );
+    }
+
+    /// A writer which accepts a limited number of bytes, [None] means it's closed.
+    #[derive(Clone, Default)]
+    struct LimitedWriter {
+        written: Arc<Mutex<Vec<u8>>>,
+        limit: Arc<Mutex<Option<usize>>>,
+    }
+
+    impl Write for LimitedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut limit = self.limit.lock().unwrap();
+            let n = match *limit {
+                Some(0) => return Err(io::ErrorKind::WouldBlock.into()),
+                Some(limit) => buf.len().min(limit),
+                None => return Err(io::ErrorKind::BrokenPipe.into()),
+            };
+
+            *limit = limit.map(|limit| limit - n);
+            self.written.lock().unwrap().extend(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_flush() {
+        let writer = LimitedWriter::default();
+        *writer.limit.lock().unwrap() = Some(3);
+
+        let mut responder = Responder::new(writer.clone());
+        responder.reply(Query::Status, None);
+        responder.reply(Query::CursorPosition, None);
+        responder.flush().unwrap();
+        assert_eq!(*writer.written.lock().unwrap(), b"\x1b[0");
+
+        *writer.limit.lock().unwrap() = Some(100);
+        responder.flush().unwrap();
+        assert_eq!(*writer.written.lock().unwrap(), b"\x1b[0n\x1b[1;1R");
+
+        *writer.limit.lock().unwrap() = None;
+        responder.reply(Query::Status, None);
+        assert!(responder.flush().is_err());
+
+        // Replies are dropped after a failure.
+        *writer.limit.lock().unwrap() = Some(100);
+        responder.flush().unwrap();
+        assert_eq!(*writer.written.lock().unwrap(), b"\x1b[0n\x1b[1;1R");
+    }
+
+    #[test]
+    fn test_reply() {
+        let mut screen = Screen::new(10, 5);
+        screen.process(b"abc\r\nde");
+
+        assert_eq!(Query::CursorPosition.reply(None), b"\x1b[1;1R");
+        assert_eq!(Query::CursorPosition.reply(Some(&screen)), b"\x1b[2;3R");
+        assert_eq!(
+            Query::ExtendedCursorPosition.reply(Some(&screen)),
+            b"\x1b[?2;3R"
+        );
+        assert_eq!(Query::Status.reply(None), b"\x1b[0n");
+        assert_eq!(Query::DeviceAttributes.reply(None), b"\x1b[?62;22c");
+        assert_eq!(
+            Query::ForegroundColor(true).reply(None),
+            b"\x1b]10;rgb:ffff/ffff/ffff\x07"
+        );
+        assert_eq!(
+            Query::BackgroundColor(false).reply(None),
+            b"\x1b]11;rgb:0000/0000/0000\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,1094 @@
+//! Module contains a [Screen], a virtual terminal which is fed by an output of a process.
+//!
+//! Programs which redraw a terminal by moving a cursor produce an output
+//! which is hard to match byte by byte.
+//! A screen interprets control sequences the way a VT100/xterm compatible terminal does,
+//! so it's possible to check what a user would actually see.
+//!
+//! ```
+//! use expectrl::screen::Screen;
+//!
+//! let mut screen = Screen::new(20, 3);
+//! screen.process(b"[1/2] file0\r[2/2] file1\r\n\x1b[1Adone");
+//!
+//! assert_eq!(screen.row(0).unwrap(), "done] file1");
+//! assert_eq!(screen.cursor_position(), (4, 0));
+//! ```
+//!
+//! Text attributes are kept per cell, so it's possible to check how a text is rendered.
+//!
+//! ```
+//! use expectrl::screen::{Color, Screen};
+//!
+//! let mut screen = Screen::new(20, 1);
+//! screen.process(b"ok \x1b[1;31mfailed\x1b[0m");
+//!
+//! let style = screen.cell(3, 0).unwrap().style();
+//! assert_eq!(style.foreground, Color::RED);
+//! assert!(style.bold);
+//! assert_eq!(screen.cell(0, 0).unwrap().style().foreground, Color::Default);
+//! ```
+
+use std::mem;
+
+/// A number of columns between tab stops.
+const TAB_WIDTH: u16 = 8;
+
+/// Screen is an in memory VT100/xterm like terminal.
+///
+/// It keeps a grid of cells, a cursor position and text attributes set by SGR sequences.
+/// Every character takes a single cell, wide characters (e.g. CJK or emoji) included,
+/// while a real terminal gives them two.
+#[derive(Debug, Clone)]
+pub struct Screen {
+    cols: u16,
+    rows: u16,
+    grid: Vec<Vec<Cell>>,
+    /// A grid of a main screen which is kept while an alternate screen is in use.
+    main_grid: Option<Vec<Vec<Cell>>>,
+    cursor: Cursor,
+    saved_cursor: Cursor,
+    /// A style which is applied to printed characters.
+    style: Style,
+    scroll_top: u16,
+    scroll_bottom: u16,
+    autowrap: bool,
+    parser: Parser,
+}
+
+impl Screen {
+    /// Creates an empty screen of a given size.
+    ///
+    /// A size can't be zero, so it's at least 1x1.
+    pub fn new(cols: u16, rows: u16) -> Self {
+        let cols = cols.max(1);
+        let rows = rows.max(1);
+
+        Self {
+            cols,
+            rows,
+            grid: blank_grid(cols, rows),
+            main_grid: None,
+            cursor: Cursor::default(),
+            saved_cursor: Cursor::default(),
+            style: Style::default(),
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            autowrap: true,
+            parser: Parser::default(),
+        }
+    }
+
+    /// Processes an output of a process.
+    ///
+    /// A sequence may be split across calls.
+    pub fn process(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.advance(b);
+        }
+    }
+
+    /// Returns a size of a screen as (columns, rows).
+    pub fn size(&self) -> (u16, u16) {
+        (self.cols, self.rows)
+    }
+
+    /// Changes a size of a screen.
+    ///
+    /// The content is truncated or padded,
+    /// rows are removed from the top if it's necessary to keep a cursor on a screen.
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        let cols = cols.max(1);
+        let rows = rows.max(1);
+
+        while self.grid.len() > rows as usize {
+            if self.cursor.row >= rows {
+                self.grid.remove(0);
+                self.cursor.row -= 1;
+            } else {
+                self.grid.pop();
+            }
+        }
+
+        resize_grid(&mut self.grid, cols, rows);
+        if let Some(grid) = &mut self.main_grid {
+            grid.truncate(rows as usize);
+            resize_grid(grid, cols, rows);
+        }
+
+        self.cols = cols;
+        self.rows = rows;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.cursor = self.cursor.clamp(cols, rows);
+        self.saved_cursor = self.saved_cursor.clamp(cols, rows);
+    }
+
+    /// Returns a cursor position as (column, row).
+    ///
+    /// Both are 0 based.
+    pub fn cursor_position(&self) -> (u16, u16) {
+        (self.cursor.col, self.cursor.row)
+    }
+
+    /// Returns a text of a row without trailing whitespace.
+    ///
+    /// It returns [None] if there's no such row.
+    pub fn row(&self, row: u16) -> Option<String> {
+        self.grid.get(row as usize).map(|line| line_text(line))
+    }
+
+    /// Returns a text of a whole screen.
+    ///
+    /// Rows are separated by `\n`.
+    /// Trailing whitespace of each row and trailing empty rows are not included.
+    pub fn contents(&self) -> String {
+        let mut contents = self
+            .grid
+            .iter()
+            .map(|line| line_text(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let len = contents.trim_end_matches('\n').len();
+        contents.truncate(len);
+        contents
+    }
+
+    /// Returns a cell at a given position.
+    ///
+    /// It returns [None] if the position is out of a screen.
+    pub fn cell(&self, col: u16, row: u16) -> Option<Cell> {
+        self.grid
+            .get(row as usize)
+            .and_then(|line| line.get(col as usize))
+            .copied()
+    }
+
+    /// Returns a position as (column, row) of a first occurrence of a text
+    /// which every character has a style accepted by a predicate.
+    ///
+    /// A text is looked up within single rows.
+    pub fn find_styled<F>(&self, text: &str, predicate: F) -> Option<(u16, u16)>
+    where
+        F: Fn(&Style) -> bool,
+    {
+        let text = text.chars().collect::<Vec<_>>();
+        if text.is_empty() {
+            return None;
+        }
+
+        for (row, line) in self.grid.iter().enumerate() {
+            let found = line.windows(text.len()).position(|cells| {
+                cells
+                    .iter()
+                    .zip(&text)
+                    .all(|(cell, &c)| cell.c == c && predicate(&cell.style))
+            });
+
+            if let Some(col) = found {
+                return Some((col as u16, row as u16));
+            }
+        }
+
+        None
+    }
+
+    /// Returns true if an alternate screen is in use.
+    ///
+    /// Full screen programs usually switch to it, so a main screen is restored when they exit.
+    pub fn is_alternate_screen(&self) -> bool {
+        self.main_grid.is_some()
+    }
+
+    fn advance(&mut self, b: u8) {
+        match self.parser.state {
+            State::Ground => self.advance_ground(b),
+            State::Escape => match b {
+                b'[' => self.parser.start(State::Csi),
+                b']' | b'P' | b'X' | b'^' | b'_' => self.parser.start(State::Str),
+                0x20..=0x2f => {
+                    self.parser.intermediates.push(b);
+                    self.parser.state = State::EscapeIntermediate;
+                }
+                0x18 | 0x1a => self.parser.state = State::Ground,
+                0x1b => {}
+                0x00..=0x1f => self.execute(b),
+                _ => {
+                    self.parser.state = State::Ground;
+                    self.esc_dispatch(b);
+                }
+            },
+            State::EscapeIntermediate => match b {
+                0x20..=0x2f => self.parser.intermediates.push(b),
+                0x18 | 0x1a => self.parser.state = State::Ground,
+                0x1b => self.parser.start(State::Escape),
+                0x00..=0x1f => self.execute(b),
+                // Character set designations and alike are ignored.
+                _ => self.parser.state = State::Ground,
+            },
+            State::Csi => match b {
+                0x30..=0x3f => self.parser.params.push(b),
+                0x20..=0x2f => self.parser.intermediates.push(b),
+                0x40..=0x7e => {
+                    self.parser.state = State::Ground;
+                    self.csi_dispatch(b);
+                }
+                0x18 | 0x1a => self.parser.state = State::Ground,
+                0x1b => self.parser.start(State::Escape),
+                0x00..=0x1f => self.execute(b),
+                _ => {}
+            },
+            State::Str => match b {
+                0x07 | 0x18 | 0x1a => self.parser.state = State::Ground,
+                0x1b => self.parser.state = State::StrEscape,
+                _ => {}
+            },
+            State::StrEscape => {
+                if b == b'\\' {
+                    self.parser.state = State::Ground;
+                } else {
+                    // It's not a string terminator but a start of a new sequence.
+                    self.parser.start(State::Escape);
+                    self.advance(b);
+                }
+            }
+        }
+    }
+
+    fn advance_ground(&mut self, b: u8) {
+        if b < 0x80 && !self.parser.utf8.is_empty() {
+            self.parser.utf8.clear();
+            self.print(char::REPLACEMENT_CHARACTER);
+        }
+
+        match b {
+            0x1b => self.parser.start(State::Escape),
+            0x00..=0x1f => self.execute(b),
+            0x7f => {}
+            0x20..=0x7e => self.print(b as char),
+            _ => {
+                if let Some(c) = self.parser.decode_utf8(b) {
+                    self.print(c);
+                }
+            }
+        }
+    }
+
+    fn execute(&mut self, b: u8) {
+        match b {
+            0x08 => {
+                self.cursor.col = self.cursor.col.saturating_sub(1);
+                self.cursor.wrap_pending = false;
+            }
+            0x09 => {
+                let col = (self.cursor.col / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.cursor.col = col.min(self.cols - 1);
+                self.cursor.wrap_pending = false;
+            }
+            0x0a..=0x0c => self.linefeed(),
+            0x0d => {
+                self.cursor.col = 0;
+                self.cursor.wrap_pending = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, b: u8) {
+        match b {
+            b'7' => self.saved_cursor = self.cursor,
+            b'8' => self.cursor = self.saved_cursor,
+            b'D' => self.linefeed(),
+            b'E' => {
+                self.cursor.col = 0;
+                self.linefeed();
+            }
+            b'M' => self.reverse_index(),
+            b'c' => *self = Self::new(self.cols, self.rows),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, action: u8) {
+        let (marker, groups) = self.parser.take_params();
+        if !self.parser.intermediates.is_empty() {
+            return;
+        }
+
+        if marker.is_none() && action == b'm' {
+            self.select_graphic_rendition(&groups);
+            return;
+        }
+
+        let params = groups.iter().map(|group| group[0]).collect::<Vec<_>>();
+
+        // A count of zero is the same as a default one.
+        let count = |i: usize| match params.get(i) {
+            Some(&n) if n > 0 => n,
+            _ => 1,
+        };
+        let mode = |i: usize| params.get(i).copied().unwrap_or(0);
+
+        let Cursor { row, col, .. } = self.cursor;
+
+        match (marker, action) {
+            (None, b'A') => {
+                let top = if row >= self.scroll_top {
+                    self.scroll_top
+                } else {
+                    0
+                };
+                self.move_to(row.saturating_sub(count(0)).max(top), col);
+            }
+            (None, b'B') | (None, b'e') => {
+                let bottom = if row <= self.scroll_bottom {
+                    self.scroll_bottom
+                } else {
+                    self.rows - 1
+                };
+                self.move_to(row.saturating_add(count(0)).min(bottom), col);
+            }
+            (None, b'C') | (None, b'a') => self.move_to(row, col.saturating_add(count(0))),
+            (None, b'D') => self.move_to(row, col.saturating_sub(count(0))),
+            (None, b'E') => self.move_to(row.saturating_add(count(0)), 0),
+            (None, b'F') => self.move_to(row.saturating_sub(count(0)), 0),
+            (None, b'G') | (None, b'`') => self.move_to(row, count(0) - 1),
+            (None, b'H') | (None, b'f') => self.move_to(count(0) - 1, count(1) - 1),
+            (None, b'd') => self.move_to(count(0) - 1, col),
+            (None, b'J') => self.erase_display(mode(0)),
+            (None, b'K') => self.erase_line(mode(0)),
+            (None, b'L') => self.insert_lines(count(0)),
+            (None, b'M') => self.delete_lines(count(0)),
+            (None, b'@') => self.insert_chars(count(0)),
+            (None, b'P') => self.delete_chars(count(0)),
+            (None, b'X') => self.erase_chars(count(0)),
+            (None, b'S') => self.scroll_up(count(0)),
+            (None, b'T') => self.scroll_down(count(0)),
+            (None, b'r') => {
+                let top = count(0) - 1;
+                let bottom = match params.get(1) {
+                    Some(&n) if n > 0 => n.min(self.rows) - 1,
+                    _ => self.rows - 1,
+                };
+
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            (None, b's') => self.saved_cursor = self.cursor,
+            (None, b'u') => self.cursor = self.saved_cursor,
+            (Some(b'?'), b'h') => params.iter().for_each(|&m| self.set_mode(m, true)),
+            (Some(b'?'), b'l') => params.iter().for_each(|&m| self.set_mode(m, false)),
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params: &[Vec<u16>]) {
+        let mut params = params.iter();
+        while let Some(param) = params.next() {
+            let style = &mut self.style;
+            match param[0] {
+                0 => *style = Style::default(),
+                1 => style.bold = true,
+                22 => style.bold = false,
+                // `4:0` is a way to turn underline off.
+                4 => style.underline = param.get(1) != Some(&0),
+                24 => style.underline = false,
+                7 => style.reverse = true,
+                27 => style.reverse = false,
+                n @ 30..=37 => style.foreground = Color::Indexed((n - 30) as u8),
+                38 => style.foreground = extended_color(param, &mut params),
+                39 => style.foreground = Color::Default,
+                n @ 40..=47 => style.background = Color::Indexed((n - 40) as u8),
+                48 => style.background = extended_color(param, &mut params),
+                49 => style.background = Color::Default,
+                n @ 90..=97 => style.foreground = Color::Indexed((n - 90 + 8) as u8),
+                n @ 100..=107 => style.background = Color::Indexed((n - 100 + 8) as u8),
+                _ => {}
+            }
+        }
+    }
+
+    fn set_mode(&mut self, mode: u16, on: bool) {
+        match mode {
+            7 => self.autowrap = on,
+            47 | 1047 => self.switch_screen(on),
+            1049 => {
+                if on {
+                    self.saved_cursor = self.cursor;
+                    self.switch_screen(true);
+                } else {
+                    self.switch_screen(false);
+                    self.cursor = self.saved_cursor;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn switch_screen(&mut self, alternate: bool) {
+        if alternate && self.main_grid.is_none() {
+            let grid = mem::replace(&mut self.grid, blank_grid(self.cols, self.rows));
+            self.main_grid = Some(grid);
+        } else if !alternate {
+            if let Some(grid) = self.main_grid.take() {
+                self.grid = grid;
+            }
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        if self.cursor.wrap_pending {
+            self.cursor.col = 0;
+            self.linefeed();
+        }
+
+        self.grid[self.cursor.row as usize][self.cursor.col as usize] = Cell {
+            c,
+            style: self.style,
+        };
+
+        if self.cursor.col + 1 < self.cols {
+            self.cursor.col += 1;
+        } else if self.autowrap {
+            self.cursor.wrap_pending = true;
+        }
+    }
+
+    fn move_to(&mut self, row: u16, col: u16) {
+        self.cursor.row = row.min(self.rows - 1);
+        self.cursor.col = col.min(self.cols - 1);
+        self.cursor.wrap_pending = false;
+    }
+
+    fn linefeed(&mut self) {
+        if self.cursor.row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+
+        self.cursor.wrap_pending = false;
+    }
+
+    fn reverse_index(&mut self) {
+        if self.cursor.row == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.cursor.row > 0 {
+            self.cursor.row -= 1;
+        }
+
+        self.cursor.wrap_pending = false;
+    }
+
+    fn scroll_up(&mut self, n: u16) {
+        let (top, bottom) = (self.scroll_top as usize, self.scroll_bottom as usize);
+        for _ in 0..(n as usize).min(bottom - top + 1) {
+            self.grid.remove(top);
+            self.grid.insert(bottom, blank_line(self.cols));
+        }
+    }
+
+    fn scroll_down(&mut self, n: u16) {
+        let (top, bottom) = (self.scroll_top as usize, self.scroll_bottom as usize);
+        for _ in 0..(n as usize).min(bottom - top + 1) {
+            self.grid.remove(bottom);
+            self.grid.insert(top, blank_line(self.cols));
+        }
+    }
+
+    fn insert_lines(&mut self, n: u16) {
+        let (row, bottom) = (self.cursor.row as usize, self.scroll_bottom as usize);
+        if self.cursor.row < self.scroll_top || row > bottom {
+            return;
+        }
+
+        for _ in 0..(n as usize).min(bottom - row + 1) {
+            self.grid.remove(bottom);
+            self.grid.insert(row, blank_line(self.cols));
+        }
+
+        self.move_to(self.cursor.row, 0);
+    }
+
+    fn delete_lines(&mut self, n: u16) {
+        let (row, bottom) = (self.cursor.row as usize, self.scroll_bottom as usize);
+        if self.cursor.row < self.scroll_top || row > bottom {
+            return;
+        }
+
+        for _ in 0..(n as usize).min(bottom - row + 1) {
+            self.grid.remove(row);
+            self.grid.insert(bottom, blank_line(self.cols));
+        }
+
+        self.move_to(self.cursor.row, 0);
+    }
+
+    fn insert_chars(&mut self, n: u16) {
+        let col = self.cursor.col as usize;
+        let line = &mut self.grid[self.cursor.row as usize];
+        for _ in 0..(n as usize).min(line.len() - col) {
+            line.pop();
+            line.insert(col, Cell::default());
+        }
+    }
+
+    fn delete_chars(&mut self, n: u16) {
+        let col = self.cursor.col as usize;
+        let line = &mut self.grid[self.cursor.row as usize];
+        for _ in 0..(n as usize).min(line.len() - col) {
+            line.remove(col);
+            line.push(Cell::default());
+        }
+    }
+
+    fn erase_chars(&mut self, n: u16) {
+        let col = self.cursor.col as usize;
+        let end = col.saturating_add(n as usize);
+        self.clear_cells(self.cursor.row, col, end);
+    }
+
+    fn erase_line(&mut self, mode: u16) {
+        let col = self.cursor.col as usize;
+        let cols = self.cols as usize;
+        match mode {
+            0 => self.clear_cells(self.cursor.row, col, cols),
+            1 => self.clear_cells(self.cursor.row, 0, col + 1),
+            2 => self.clear_cells(self.cursor.row, 0, cols),
+            _ => {}
+        }
+    }
+
+    fn erase_display(&mut self, mode: u16) {
+        let row = self.cursor.row;
+        match mode {
+            0 => {
+                self.erase_line(0);
+                (row + 1..self.rows).for_each(|row| self.clear_cells(row, 0, self.cols as usize));
+            }
+            1 => {
+                (0..row).for_each(|row| self.clear_cells(row, 0, self.cols as usize));
+                self.erase_line(1);
+            }
+            2 | 3 => self.grid = blank_grid(self.cols, self.rows),
+            _ => {}
+        }
+    }
+
+    fn clear_cells(&mut self, row: u16, start: usize, end: usize) {
+        let line = &mut self.grid[row as usize];
+        let end = end.min(line.len());
+        if start < end {
+            line[start..end].fill(Cell::default());
+        }
+    }
+}
+
+/// A single character place on a screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    c: char,
+    style: Style,
+}
+
+impl Cell {
+    /// Returns a character of a cell.
+    ///
+    /// An empty cell is a space.
+    pub fn char(&self) -> char {
+        self.c
+    }
+
+    /// Returns a style a character is rendered with.
+    pub fn style(&self) -> Style {
+        self.style
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            c: ' ',
+            style: Style::default(),
+        }
+    }
+}
+
+/// Style is a set of attributes a character is rendered with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Style {
+    /// A color of a text.
+    pub foreground: Color,
+    /// A color of a cell.
+    pub background: Color,
+    /// A text is bold.
+    pub bold: bool,
+    /// A text is underlined.
+    pub underline: bool,
+    /// Foreground and background colors are swapped.
+    pub reverse: bool,
+}
+
+/// Color of a text or a background.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    /// A color a terminal uses when none is set.
+    #[default]
+    Default,
+    /// A color from a 256 color palette.
+    ///
+    /// The first 16 are the standard and bright colors.
+    Indexed(u8),
+    /// A true color.
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    pub const BLACK: Color = Color::Indexed(0);
+    pub const RED: Color = Color::Indexed(1);
+    pub const GREEN: Color = Color::Indexed(2);
+    pub const YELLOW: Color = Color::Indexed(3);
+    pub const BLUE: Color = Color::Indexed(4);
+    pub const MAGENTA: Color = Color::Indexed(5);
+    pub const CYAN: Color = Color::Indexed(6);
+    pub const WHITE: Color = Color::Indexed(7);
+    pub const BRIGHT_BLACK: Color = Color::Indexed(8);
+    pub const BRIGHT_RED: Color = Color::Indexed(9);
+    pub const BRIGHT_GREEN: Color = Color::Indexed(10);
+    pub const BRIGHT_YELLOW: Color = Color::Indexed(11);
+    pub const BRIGHT_BLUE: Color = Color::Indexed(12);
+    pub const BRIGHT_MAGENTA: Color = Color::Indexed(13);
+    pub const BRIGHT_CYAN: Color = Color::Indexed(14);
+    pub const BRIGHT_WHITE: Color = Color::Indexed(15);
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Cursor {
+    row: u16,
+    col: u16,
+    /// A character was printed in the last column,
+    /// so the next one goes to a new line.
+    wrap_pending: bool,
+}
+
+impl Cursor {
+    fn clamp(self, cols: u16, rows: u16) -> Self {
+        Self {
+            row: self.row.min(rows - 1),
+            col: self.col.min(cols - 1),
+            wrap_pending: false,
+        }
+    }
+}
+
+/// A state of parsing of escape sequences.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    Csi,
+    /// OSC, DCS, SOS, PM and APC strings, which are ignored.
+    Str,
+    StrEscape,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Parser {
+    state: State,
+    params: Vec<u8>,
+    intermediates: Vec<u8>,
+    utf8: Vec<u8>,
+}
+
+impl Parser {
+    fn start(&mut self, state: State) {
+        self.state = state;
+        self.params.clear();
+        self.intermediates.clear();
+    }
+
+    /// Returns a private marker and numeric parameters of a control sequence.
+    ///
+    /// Each parameter is followed by its sub parameters separated by `:`,
+    /// so a group is never empty.
+    fn take_params(&mut self) -> (Option<u8>, Vec<Vec<u16>>) {
+        let params = mem::take(&mut self.params);
+        let (marker, params) = match params.first() {
+            Some(&b @ b'<'..=b'?') => (Some(b), &params[1..]),
+            _ => (None, &params[..]),
+        };
+
+        let params = params
+            .split(|&b| b == b';')
+            .map(|param| {
+                param
+                    .split(|&b| b == b':')
+                    .map(|sub| {
+                        sub.iter()
+                            .take_while(|b| b.is_ascii_digit())
+                            .fold(0u16, |n, b| {
+                                n.saturating_mul(10).saturating_add((b - b'0') as u16)
+                            })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        (marker, params)
+    }
+
+    /// Collects a UTF-8 encoded character.
+    ///
+    /// An invalid sequence is replaced by [char::REPLACEMENT_CHARACTER].
+    fn decode_utf8(&mut self, b: u8) -> Option<char> {
+        let is_continuation = b & 0xc0 == 0x80;
+        if is_continuation && self.utf8.is_empty() {
+            return Some(char::REPLACEMENT_CHARACTER);
+        }
+
+        if !is_continuation {
+            let had_pending = !self.utf8.is_empty();
+            self.utf8.clear();
+
+            if utf8_len(b).is_none() {
+                return Some(char::REPLACEMENT_CHARACTER);
+            }
+
+            self.utf8.push(b);
+
+            return match had_pending {
+                true => Some(char::REPLACEMENT_CHARACTER),
+                false => None,
+            };
+        }
+
+        self.utf8.push(b);
+        if Some(self.utf8.len()) != utf8_len(self.utf8[0]) {
+            return None;
+        }
+
+        let c = std::str::from_utf8(&self.utf8)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        self.utf8.clear();
+
+        Some(c)
+    }
+}
+
+/// Parses a color of `38` and `48` SGR parameters.
+///
+/// Both `38;5;n` and `38:5:n` forms are supported, as well as `38;2;r;g;b` and `38:2::r:g:b`.
+fn extended_color<'a, I>(param: &[u16], rest: &mut I) -> Color
+where
+    I: Iterator<Item = &'a Vec<u16>>,
+{
+    let color = |kind: u16, values: &[u16]| match (kind, values) {
+        (5, [n, ..]) => Some(Color::Indexed(*n as u8)),
+        (2, [r, g, b, ..]) => Some(Color::Rgb(*r as u8, *g as u8, *b as u8)),
+        _ => None,
+    };
+
+    let color = match param {
+        // A color space id may precede RGB values.
+        [_, 2, _, r, g, b, ..] => color(2, &[*r, *g, *b]),
+        [_, kind, values @ ..] => color(*kind, values),
+        _ => {
+            let kind = rest.next().map_or(0, |group| group[0]);
+            let count = match kind {
+                5 => 1,
+                2 => 3,
+                _ => 0,
+            };
+            let values = rest.take(count).map(|group| group[0]).collect::<Vec<_>>();
+            color(kind, &values)
+        }
+    };
+
+    color.unwrap_or_default()
+}
+
+fn utf8_len(first: u8) -> Option<usize> {
+    match first {
+        0xc2..=0xdf => Some(2),
+        0xe0..=0xef => Some(3),
+        0xf0..=0xf4 => Some(4),
+        _ => None,
+    }
+}
+
+fn blank_line(cols: u16) -> Vec<Cell> {
+    vec![Cell::default(); cols as usize]
+}
+
+fn blank_grid(cols: u16, rows: u16) -> Vec<Vec<Cell>> {
+    vec![blank_line(cols); rows as usize]
+}
+
+fn resize_grid(grid: &mut Vec<Vec<Cell>>, cols: u16, rows: u16) {
+    for line in grid.iter_mut() {
+        line.resize(cols as usize, Cell::default());
+    }
+
+    grid.resize(rows as usize, blank_line(cols));
+}
+
+fn line_text(line: &[Cell]) -> String {
+    let text = line.iter().map(|cell| cell.c).collect::<String>();
+    text.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(cols: u16, rows: u16, output: &[u8]) -> Screen {
+        let mut screen = Screen::new(cols, rows);
+        screen.process(output);
+        screen
+    }
+
+    #[test]
+    fn test_text() {
+        let s = screen(10, 3, b"Hello\r\nWorld");
+        assert_eq!(s.contents(), "Hello\nWorld");
+        assert_eq!(s.row(0).unwrap(), "Hello");
+        assert_eq!(s.row(1).unwrap(), "World");
+        assert_eq!(s.row(2).unwrap(), "");
+        assert_eq!(s.row(3), None);
+        assert_eq!(s.cursor_position(), (5, 1));
+    }
+
+    #[test]
+    fn test_carriage_return_overwrites() {
+        let s = screen(20, 2, b"[1/5]: file0\r[2/5]: file1\r");
+        assert_eq!(s.contents(), "[2/5]: file1");
+        assert_eq!(s.cursor_position(), (0, 0));
+    }
+
+    #[test]
+    fn test_wrap_and_scroll() {
+        let s = screen(3, 2, b"abcdef");
+        assert_eq!(s.contents(), "abc\ndef");
+        assert_eq!(s.cursor_position(), (2, 1));
+
+        let s = screen(3, 2, b"abcdefg");
+        assert_eq!(s.contents(), "def\ng");
+
+        let s = screen(3, 2, b"1\r\n2\r\n3\r\n");
+        assert_eq!(s.contents(), "3");
+
+        let s = screen(3, 2, b"\x1b[?7labcdef");
+        assert_eq!(s.contents(), "abf");
+    }
+
+    #[test]
+    fn test_cursor_movement() {
+        let s = screen(10, 3, b"\x1b[2;3Hx\x1b[Ay\x1b[2Bz\x1b[10D<\x1b[5C>");
+        assert_eq!(s.contents(), "   y\n  x\n<   z >");
+
+        let s = screen(10, 3, b"abc\r\n\x1b[Fd\x1b[3Gx\x1b[3d!");
+        assert_eq!(s.contents(), "dbx\n\n   !");
+
+        let s = screen(10, 3, b"\x1b[100;100Hx");
+        assert_eq!(s.row(2).unwrap(), "         x");
+
+        let s = screen(10, 3, b"abc\x1b7\r\ndef\x1b8x\x1b[sgh\x1b[uy");
+        assert_eq!(s.contents(), "abcxyh\ndef");
+
+        let s = screen(20, 1, b"a\tb\x08c");
+        assert_eq!(s.contents(), "a       c");
+    }
+
+    #[test]
+    fn test_same_line_escape() {
+        let mut s = Screen::new(20, 4);
+        for i in 0..5 {
+            s.process(format!("\x1b[F[{}/5]: file{}\r\n", i + 1, i).as_bytes());
+        }
+
+        assert_eq!(s.contents(), "[5/5]: file4");
+    }
+
+    #[test]
+    fn test_erase() {
+        let s = screen(5, 3, b"aaaaa\r\nbbbbb\r\nccccc\x1b[2;3H\x1b[K");
+        assert_eq!(s.contents(), "aaaaa\nbb\nccccc");
+
+        let s = screen(5, 3, b"aaaaa\r\nbbbbb\r\nccccc\x1b[2;3H\x1b[1K");
+        assert_eq!(s.contents(), "aaaaa\n   bb\nccccc");
+
+        let s = screen(5, 3, b"aaaaa\r\nbbbbb\r\nccccc\x1b[2;3H\x1b[J");
+        assert_eq!(s.contents(), "aaaaa\nbb");
+
+        let s = screen(5, 3, b"aaaaa\r\nbbbbb\r\nccccc\x1b[2;3H\x1b[1J");
+        assert_eq!(s.contents(), "\n   bb\nccccc");
+
+        let s = screen(5, 3, b"aaaaa\r\nbbbbb\x1b[2J");
+        assert_eq!(s.contents(), "");
+        assert_eq!(s.cursor_position(), (4, 1));
+
+        let s = screen(5, 1, b"abcde\x1b[2G\x1b[2X");
+        assert_eq!(s.contents(), "a  de");
+    }
+
+    #[test]
+    fn test_insert_and_delete() {
+        let s = screen(5, 1, b"abcde\x1b[2G\x1b[2@");
+        assert_eq!(s.contents(), "a  bc");
+
+        let s = screen(5, 1, b"abcde\x1b[2G\x1b[2P");
+        assert_eq!(s.contents(), "ade");
+
+        let s = screen(3, 3, b"a\r\nb\r\nc\x1b[2H\x1b[L");
+        assert_eq!(s.contents(), "a\n\nb");
+
+        let s = screen(3, 3, b"a\r\nb\r\nc\x1b[1H\x1b[M");
+        assert_eq!(s.contents(), "b\nc");
+    }
+
+    #[test]
+    fn test_scroll_region() {
+        let s = screen(5, 4, b"head\x1b[2;3r\x1b[2H1\r\n2\r\n3\x1b[4Hfoot");
+        assert_eq!(s.contents(), "head\n2\n3\nfoot");
+
+        let s = screen(5, 4, b"1\r\n2\r\n3\r\n4\x1b[2;3r\x1b[2H\x1bM");
+        assert_eq!(s.contents(), "1\n\n2\n4");
+
+        let s = screen(5, 3, b"1\r\n2\r\n3\x1b[S");
+        assert_eq!(s.contents(), "2\n3");
+
+        let s = screen(5, 3, b"1\r\n2\r\n3\x1b[T");
+        assert_eq!(s.contents(), "\n1\n2");
+    }
+
+    #[test]
+    fn test_alternate_screen() {
+        let mut s = screen(10, 2, b"shell$ ");
+        s.process(b"\x1b[?1049h\x1b[Hfull screen");
+        assert!(s.is_alternate_screen());
+        assert_eq!(s.contents(), "full scree\nn");
+
+        s.process(b"\x1b[?1049l");
+        assert!(!s.is_alternate_screen());
+        assert_eq!(s.contents(), "shell$");
+        assert_eq!(s.cursor_position(), (7, 0));
+    }
+
+    #[test]
+    fn test_ignored_sequences() {
+        let s = screen(
+            20,
+            1,
+            b"\x1b[32mgreen\x1b[0m \x1b]0;title\x07\x1b]2;x\x1b\\\x1b(Bok\x1b[?25l\x1b[2 q",
+        );
+        assert_eq!(s.contents(), "green ok");
+    }
+
+    #[test]
+    fn test_split_sequences() {
+        let mut s = Screen::new(10, 2);
+        for &b in "ab\x1b[2;2Hпривет".as_bytes() {
+            s.process(&[b]);
+        }
+
+        assert_eq!(s.contents(), "ab\n привет");
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let s = screen(10, 1, b"a\xffb\xd0c\x80");
+        assert_eq!(s.contents(), "a\u{fffd}b\u{fffd}c\u{fffd}");
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut s = screen(5, 3, b"1\r\n2\r\n3");
+        s.resize(2, 2);
+        assert_eq!(s.contents(), "2\n3");
+        assert_eq!(s.cursor_position(), (1, 1));
+
+        s.resize(4, 3);
+        assert_eq!(s.size(), (4, 3));
+        s.process(b"\r\nnew");
+        assert_eq!(s.contents(), "2\n3\nnew");
+    }
+
+    #[test]
+    fn test_reset() {
+        let s = screen(5, 2, b"abc\x1bc");
+        assert_eq!(s.contents(), "");
+        assert_eq!(s.cursor_position(), (0, 0));
+    }
+
+    #[test]
+    fn test_sgr() {
+        let s = screen(
+            20,
+            1,
+            b"a\x1b[1;4;7;31;42mb\x1b[22;24;27;39;49mc\x1b[93;104md\x1b[me",
+        );
+        let style = |col| s.cell(col, 0).unwrap().style();
+
+        assert_eq!(style(0), Style::default());
+        assert_eq!(
+            style(1),
+            Style {
+                foreground: Color::RED,
+                background: Color::GREEN,
+                bold: true,
+                underline: true,
+                reverse: true,
+            }
+        );
+        assert_eq!(style(2), Style::default());
+        assert_eq!(style(3).foreground, Color::BRIGHT_YELLOW);
+        assert_eq!(style(3).background, Color::BRIGHT_BLUE);
+        assert_eq!(style(4), Style::default());
+        assert_eq!(s.cell(4, 0).unwrap().char(), 'e');
+        assert_eq!(s.cell(20, 0), None);
+    }
+
+    #[test]
+    fn test_sgr_extended_colors() {
+        let s = screen(
+            10,
+            1,
+            b"\x1b[38;5;208ma\x1b[48;2;1;2;3mb\x1b[0;38:5:9mc\x1b[38:2::4:5:6md\x1b[4:0m\x1b[38:2:7:8:9me",
+        );
+        let style = |col| s.cell(col, 0).unwrap().style();
+
+        assert_eq!(style(0).foreground, Color::Indexed(208));
+        assert_eq!(style(1).foreground, Color::Indexed(208));
+        assert_eq!(style(1).background, Color::Rgb(1, 2, 3));
+        assert_eq!(style(2).foreground, Color::BRIGHT_RED);
+        assert_eq!(style(2).background, Color::Default);
+        assert_eq!(style(3).foreground, Color::Rgb(4, 5, 6));
+        assert_eq!(style(4).foreground, Color::Rgb(7, 8, 9));
+        assert!(!style(4).underline);
+    }
+
+    #[test]
+    fn test_find_styled() {
+        let s = screen(20, 2, b"failed\r\nok \x1b[31mfailed\x1b[0m");
+
+        assert_eq!(s.find_styled("failed", |_| true), Some((0, 0)));
+        assert_eq!(
+            s.find_styled("failed", |style| style.foreground == Color::RED),
+            Some((3, 1))
+        );
+        assert_eq!(s.find_styled("ok", |style| style.bold), None);
+        assert_eq!(s.find_styled("", |_| true), None);
+
+        // Erased cells lose their attributes.
+        let s = screen(20, 1, b"\x1b[31mfailed\r\x1b[0m\x1b[K");
+        assert_eq!(s.cell(0, 0).unwrap(), Cell::default());
+    }
+}
@@ -1,63 +1,300 @@
 //! Module contains a Session structure.
 
-use crate::{control_code::ControlCode, error::Error, needle::Needle, stream::Stream, Found};
+use crate::{
+    control_code::ControlCode,
+    error::{Error, ExpectDetails},
+    key::{Key, KeyMap},
+    needle::{flatten_groups, Match, MaybePlain, Needle, NeedleContext},
+    responder::Responder,
+    screen::Screen,
+    stream::Stream,
+    Found,
+};
 use std::{
+    cell::Cell,
     convert::TryInto,
     io,
     ops::{Deref, DerefMut},
     time::{self, Duration},
 };
 
+#[cfg(unix)]
+use crate::stderr::StderrFifo;
+#[cfg(unix)]
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::{killpg, Signal},
+        wait::waitpid,
+    },
+    unistd::Pid,
+};
 #[cfg(unix)]
 use ptyprocess::{PtyProcess, WaitStatus};
 #[cfg(unix)]
-use std::process::Command;
+use std::{env, process::Command};
 
 #[cfg(not(feature = "async"))]
-use io::Write;
+use crate::stream::NonBlocking;
+#[cfg(not(feature = "async"))]
+use io::{Read, Write};
 
+#[cfg(feature = "async")]
+use crate::stream::REPLY_RETRY_INTERVAL;
 #[cfg(all(unix, feature = "async"))]
 use futures_lite::AsyncWriteExt;
+#[cfg(feature = "async")]
+use futures_lite::{AsyncRead, AsyncWrite};
+
+/// A default process of a [Session] which is spawned in a pseudo terminal.
+#[cfg(unix)]
+pub(crate) type Process = PtyProcess;
+/// A default process of a [Session] which is spawned in a pseudo terminal.
+#[cfg(windows)]
+pub(crate) type Process = conpty::Process;
+
+/// A default stream of a [Session], it's a stream of a pseudo terminal.
+#[cfg(all(unix, not(feature = "async")))]
+pub(crate) type ProcessStream = ptyprocess::stream::Stream;
+/// A default stream of a [Session], it's a stream of a pseudo terminal.
+#[cfg(all(unix, feature = "async"))]
+pub(crate) type ProcessStream = async_io::Async<ptyprocess::stream::Stream>;
+/// A default stream of a [Session], it's a stream of a pseudo terminal.
+#[cfg(windows)]
+pub(crate) type ProcessStream = crate::stream::ProcessStream;
+
+/// A stream of a stderr pipe of a process.
+#[cfg(all(unix, not(feature = "async")))]
+pub(crate) type StderrStream = std::fs::File;
+/// A stream of a stderr pipe of a process.
+#[cfg(all(unix, feature = "async"))]
+pub(crate) type StderrStream = async_io::Async<std::fs::File>;
+
+/// A size of a chunk which is read from a stream at once while expecting.
+const EXPECT_READ_CHUNK_SIZE: usize = 4096;
 
 /// Session represents a process and its streams.
 /// It controlls process and communication with it.
+///
+/// By default it's a process spawned in a pseudo terminal,
+/// but it can be built from any process handle `P` and any IO stream `S` via [Session::new].
+/// So the same `expect` machinery can be used with sockets, pipes or in-memory buffers.
+///
+/// A sync version requires a stream to implement [std::io::Read], [std::io::Write]
+/// and [crate::NonBlocking],
+/// when an async version requires `futures_lite::AsyncRead` and `futures_lite::AsyncWrite`.
 #[derive(Debug)]
-pub struct Session {
+pub struct Session<P = Process, S = ProcessStream> {
+    proc: P,
+    stream: Stream<S>,
     #[cfg(unix)]
-    proc: PtyProcess,
-    #[cfg(windows)]
-    proc: conpty::Process,
-    stream: Stream,
+    stderr: Option<Stream<StderrStream>>,
+    #[cfg(unix)]
+    process_group: Option<Pid>,
+    #[cfg(unix)]
+    drop_policy: DropPolicy,
     expect_timeout: Option<Duration>,
+    buffer_limits: BufferLimits,
+    /// Needles are checked against an output without ANSI escape sequences.
+    strip_ansi: bool,
+    /// Sequences which keys are encoded with.
+    key_map: KeyMap,
+}
+
+/// DropPolicy defines what happens with a spawned process when a [Session] is dropped.
+///
+/// A policy is applied to a whole process group,
+/// so processes started by a child are cleaned up as well.
+///
+/// A drop blocks a calling thread while a group is terminated,
+/// up to a grace period of [DropPolicy::Terminate].
+/// It's so with the `async` feature as well,
+/// call [Session::terminate] to not block an executor.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Terminate a process group gracefully, see [Session::terminate].
+    Terminate(Duration),
+    /// Kill a process group immediately by `SIGKILL`.
+    Kill,
+    /// Leave it to a process handle.
+    ///
+    /// [PtyProcess] terminates only a process itself, not its children.
+    Ignore,
+}
+
+#[cfg(unix)]
+impl Default for DropPolicy {
+    fn default() -> Self {
+        Self::Terminate(Duration::from_millis(300))
+    }
+}
+
+/// DiscardPolicy defines what happens when unmatched bytes exceed a maximum buffer size.
+///
+/// Only bytes which were already checked against a needle are taken into account,
+/// see [Session::set_max_buffer_size].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DiscardPolicy {
+    /// Drop the oldest bytes, so the buffer is kept within its size.
+    ///
+    /// A match which starts in dropped bytes will never be found.
+    #[default]
+    DropOldest,
+    /// Fail with [Error::BufferOverflow].
+    ///
+    /// The buffer is left untouched,
+    /// so next calls fail as well until the limit is changed.
+    Fail,
+}
+
+/// Limits of a search in a buffer.
+#[derive(Debug, Default, Clone, Copy)]
+struct BufferLimits {
+    search_window: Option<usize>,
+    max_size: Option<usize>,
+    policy: DiscardPolicy,
+}
+
+impl BufferLimits {
+    /// Returns a start of a buffer part which is searched.
+    fn window_start(&self, len: usize) -> usize {
+        match self.search_window {
+            Some(window) => len.saturating_sub(window),
+            None => 0,
+        }
+    }
+
+    /// Returns a number of bytes which exceed a maximum size.
+    fn excess(&self, len: usize) -> usize {
+        match self.max_size {
+            Some(max_size) => len.saturating_sub(max_size),
+            None => 0,
+        }
+    }
 }
 
+#[cfg(unix)]
 impl Session {
     /// Spawn spawns a command
-    #[cfg(unix)]
+    ///
+    /// Keys sent by [Session::send_key] are encoded for a `TERM` of the command,
+    /// see [KeyMap::from_terminfo].
+    /// If it's not set xterm sequences are used.
+    ///
+    /// A [Command] doesn't tell whether its environment was cleared,
+    /// so an inherited `TERM` is used unless the command sets or removes it.
+    /// [crate::SessionBuilder::env_clear] takes it into account.
     pub fn spawn(command: Command) -> Result<Self, Error> {
+        let key_map = command_key_map(&command, true);
         let ptyproc = PtyProcess::spawn(command)?;
-        let stream = Stream::new(ptyproc.get_raw_handle()?);
+        let stream = ptyproc.get_pty_stream()?;
+        #[cfg(feature = "async")]
+        let stream = async_io::Async::new(stream)?;
 
-        Ok(Self {
-            proc: ptyproc,
-            stream,
-            expect_timeout: Some(Duration::from_millis(10000)),
-        })
+        // A process is a session leader so its pid is an id of its process group.
+        let process_group = ptyproc.pid();
+        let mut session = Self::new(ptyproc, stream);
+        session.set_process_group(process_group);
+        session.set_key_map(key_map);
+
+        Ok(session)
     }
 
+    /// Spawns a command in a hybrid mode.
+    ///
+    /// Its stdin and stdout are connected to a pseudo terminal as in [Session::spawn],
+    /// but its stderr is redirected to a separate pipe.
+    /// So the output of the process is available via [Session::expect]
+    /// and its errors via [Session::expect_stderr].
+    pub fn spawn_with_stderr(mut command: Command) -> Result<Self, Error> {
+        let fifo = StderrFifo::new()?;
+        fifo.redirect(&mut command);
+
+        let mut session = Self::spawn(command)?;
+        session.set_stderr(fifo)?;
+
+        Ok(session)
+    }
+
+    /// Sets a FIFO which a process was redirected to as its stderr.
+    pub(crate) fn set_stderr(&mut self, fifo: StderrFifo) -> Result<(), Error> {
+        let stderr = fifo.into_reader();
+        #[cfg(feature = "async")]
+        let stderr = async_io::Async::new(stderr)?;
+        self.stderr = Some(Stream::new(stderr));
+
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+impl Session {
     /// Spawn spawns a command
-    #[cfg(windows)]
     pub fn spawn(attr: conpty::ProcAttr) -> Result<Self, Error> {
         let proc = attr.spawn()?;
-        let stream = Stream::new(proc.input()?, proc.output()?);
+        let stream = ProcessStream::new(proc.input()?, proc.output()?);
 
-        Ok(Self {
-            proc,
-            stream,
+        Ok(Self::new(proc, stream))
+    }
+}
+
+#[cfg(not(feature = "async"))]
+impl<P, S: Read> Session<P, S> {
+    /// Creates a session from a process handle and a stream to communicate with it.
+    ///
+    /// The process can be anything, e.g. `()` if there's no process behind a stream.
+    ///
+    /// ```
+    /// use expectrl::Session;
+    /// use std::io::Cursor;
+    ///
+    /// let mut session = Session::new((), Cursor::new(b"Hello World".to_vec()));
+    /// let m = session.expect("World").unwrap();
+    /// assert_eq!(m.before(), b"Hello ");
+    /// ```
+    pub fn new(process: P, stream: S) -> Self {
+        Self {
+            proc: process,
+            stream: Stream::new(stream),
+            #[cfg(unix)]
+            stderr: None,
+            #[cfg(unix)]
+            process_group: None,
+            #[cfg(unix)]
+            drop_policy: DropPolicy::default(),
             expect_timeout: Some(Duration::from_millis(10000)),
-        })
+            buffer_limits: BufferLimits::default(),
+            strip_ansi: false,
+            key_map: KeyMap::default(),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<P, S: AsyncRead + Unpin> Session<P, S> {
+    /// Creates a session from a process handle and a stream to communicate with it.
+    ///
+    /// The process can be anything, e.g. `()` if there's no process behind a stream.
+    pub fn new(process: P, stream: S) -> Self {
+        Self {
+            proc: process,
+            stream: Stream::new(stream),
+            #[cfg(unix)]
+            stderr: None,
+            #[cfg(unix)]
+            process_group: None,
+            #[cfg(unix)]
+            drop_policy: DropPolicy::default(),
+            expect_timeout: Some(Duration::from_millis(10000)),
+            buffer_limits: BufferLimits::default(),
+            strip_ansi: false,
+            key_map: KeyMap::default(),
+        }
     }
+}
 
+impl<P, S> Session<P, S> {
     /// Expect waits until a pattern is matched.
     ///
     /// If the method returns [Ok] it is guaranteed that at least 1 match was found.
@@ -81,57 +318,19 @@ impl Session {
     /// It return an error if timeout is reached.
     /// You can specify a timeout value by [Session::set_expect_timeout] method.
     #[cfg(feature = "async")]
-    pub async fn expect<E: Needle>(&mut self, expect: E) -> Result<Found, Error> {
-        let mut checking_data_length = 0;
-        let mut eof = false;
-        let start = time::Instant::now();
-        loop {
-            let mut available = self.stream.get_available();
-            if checking_data_length == available.len() {
-                // We read by byte to make things as lazy as possible.
-                //
-                // It's chose is important in using Regex as a Needle.
-                // Imagine we have a `\d+` regex.
-                // Using such buffer will match string `2` imidiately eventhough right after might be other digit.
-                //
-                // The second reason is
-                // if we wouldn't read by byte EOF indication could be lost.
-                // And next blocking std::io::Read operation could be blocked forever.
-                //
-                // We could read all data available via `read_available` to reduce IO operations,
-                // but in such case we would need to keep a EOF indicator internally in stream,
-                // which is OK if EOF happens onces, but I am not sure if this is a case.
-                eof = self.stream.read_available_once(&mut [0; 1]).await? == Some(0);
-                available = self.stream.get_available();
-            }
-
-            // We intentinally not increase the counter
-            // and run check one more time even though the data isn't changed.
-            // Because it may be important for custom implementations of Needle.
-            if checking_data_length < available.len() {
-                checking_data_length += 1;
-            }
-
-            let data = &available[..checking_data_length];
-
-            let found = expect.check(data, eof)?;
-            if !found.is_empty() {
-                let end_index = Found::right_most_index(&found);
-                let involved_bytes = data[..end_index].to_vec();
-                self.stream.consume_from_buffer(end_index);
-                return Ok(Found::new(involved_bytes, found));
-            }
-
-            if eof {
-                return Err(Error::Eof);
-            }
-
-            if let Some(timeout) = self.expect_timeout {
-                if start.elapsed() > timeout {
-                    return Err(Error::ExpectTimeout);
-                }
-            }
-        }
+    pub async fn expect<E: Needle>(&mut self, expect: E) -> Result<Found, Error>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let result = expect_in_stream(
+            &mut self.stream,
+            MaybePlain::new(expect, self.strip_ansi),
+            NoGuard,
+            self.expect_timeout,
+            self.buffer_limits,
+        )
+        .await;
+        result.map_err(|err| self.with_process_status(err))
     }
 
     /// Expect waits until a pattern is matched.
@@ -155,57 +354,164 @@ impl Session {
     /// It return an error if timeout is reached.
     /// You can specify a timeout value by [Session::set_expect_timeout] method.
     #[cfg(not(feature = "async"))]
-    pub fn expect<E: Needle>(&mut self, expect: E) -> Result<Found, Error> {
-        let mut checking_data_length = 0;
-        let mut eof = false;
-        let start = time::Instant::now();
-        loop {
-            let mut available = self.stream.get_available();
-            if checking_data_length == available.len() {
-                // We read by byte to make things as lazy as possible.
-                //
-                // It's chose is important in using Regex as a Needle.
-                // Imagine we have a `\d+` regex.
-                // Using such buffer will match string `2` imidiately eventhough right after might be other digit.
-                //
-                // The second reason is
-                // if we wouldn't read by byte EOF indication could be lost.
-                // And next blocking std::io::Read operation could be blocked forever.
-                //
-                // We could read all data available via `read_available` to reduce IO operations,
-                // but in such case we would need to keep a EOF indicator internally in stream,
-                // which is OK if EOF happens onces, but I am not sure if this is a case.
-                eof = self.stream.read_available_once(&mut [0; 1])? == Some(0);
-                available = self.stream.get_available();
-            }
+    pub fn expect<E: Needle>(&mut self, expect: E) -> Result<Found, Error>
+    where
+        S: Read + NonBlocking,
+    {
+        let result = expect_in_stream(
+            &mut self.stream,
+            MaybePlain::new(expect, self.strip_ansi),
+            NoGuard,
+            self.expect_timeout,
+            self.buffer_limits,
+        );
+        result.map_err(|err| self.with_process_status(err))
+    }
 
-            // We intentinally not increase the counter
-            // and run check one more time even though the data isn't changed.
-            // Because it may be important for custom implementations of Needle.
-            if checking_data_length < available.len() {
-                checking_data_length += 1;
-            }
+    /// Expect waits until a pattern is matched or a given timeout is reached.
+    ///
+    /// It's the same as [Session::expect],
+    /// but a timeout set by [Session::set_expect_timeout] is not used.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let mut p = expectrl::spawn("echo 123").unwrap();
+    /// let m = p.expect_timeout("123", Duration::from_secs(1)).unwrap();
+    /// assert_eq!(m.first(), b"123");
+    /// ```
+    #[cfg(not(feature = "async"))]
+    pub fn expect_timeout<E: Needle>(
+        &mut self,
+        expect: E,
+        timeout: Duration,
+    ) -> Result<Found, Error>
+    where
+        S: Read + NonBlocking,
+    {
+        let result = expect_in_stream(
+            &mut self.stream,
+            MaybePlain::new(expect, self.strip_ansi),
+            NoGuard,
+            Some(timeout),
+            self.buffer_limits,
+        );
+        result.map_err(|err| self.with_process_status(err))
+    }
 
-            let data = &available[..checking_data_length];
+    /// Expect waits until a pattern is matched or a given timeout is reached.
+    ///
+    /// See sync version [Session::expect_timeout].
+    #[cfg(feature = "async")]
+    pub async fn expect_timeout<E: Needle>(
+        &mut self,
+        expect: E,
+        timeout: Duration,
+    ) -> Result<Found, Error>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let result = expect_in_stream(
+            &mut self.stream,
+            MaybePlain::new(expect, self.strip_ansi),
+            NoGuard,
+            Some(timeout),
+            self.buffer_limits,
+        )
+        .await;
+        result.map_err(|err| self.with_process_status(err))
+    }
 
-            let found = expect.check(data, eof)?;
-            if !found.is_empty() {
-                let end_index = Found::right_most_index(&found);
-                let involved_bytes = data[..end_index].to_vec();
-                self.stream.consume_from_buffer(end_index);
-                return Ok(Found::new(involved_bytes, found));
-            }
+    /// Expect waits until a pattern is matched or a deadline is reached.
+    ///
+    /// If a deadline has already passed, the data which is available is still checked once.
+    /// See [Session::expect_timeout].
+    #[cfg(not(feature = "async"))]
+    pub fn expect_until<E: Needle>(
+        &mut self,
+        expect: E,
+        deadline: time::Instant,
+    ) -> Result<Found, Error>
+    where
+        S: Read + NonBlocking,
+    {
+        let timeout = deadline.saturating_duration_since(time::Instant::now());
+        self.expect_timeout(expect, timeout)
+    }
 
-            if eof {
-                return Err(Error::Eof);
-            }
+    /// Expect waits until a pattern is matched or a deadline is reached.
+    ///
+    /// See sync version [Session::expect_until].
+    #[cfg(feature = "async")]
+    pub async fn expect_until<E: Needle>(
+        &mut self,
+        expect: E,
+        deadline: time::Instant,
+    ) -> Result<Found, Error>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let timeout = deadline.saturating_duration_since(time::Instant::now());
+        self.expect_timeout(expect, timeout).await
+    }
 
-            if let Some(timeout) = self.expect_timeout {
-                if start.elapsed() > timeout {
-                    return Err(Error::ExpectTimeout);
-                }
-            }
-        }
+    /// Expect waits until a pattern is matched,
+    /// but fails at once if a guard is matched first.
+    ///
+    /// A guard match is returned as [Error::GuardMatched]
+    /// and its bytes are consumed as they would be by a successful match.
+    /// If both needles are matched at the same point the expected one wins.
+    ///
+    /// Several guards can be combined by [crate::Any].
+    ///
+    /// ```
+    /// use expectrl::{Any, Error};
+    ///
+    /// let mut p = expectrl::spawn("echo Error: no such file").unwrap();
+    /// let err = p.expect_guarded("Success", Any(["Error:", "Traceback"])).unwrap_err();
+    /// assert!(matches!(err, Error::GuardMatched(found) if found.first() == b"Error:"));
+    /// ```
+    #[cfg(not(feature = "async"))]
+    pub fn expect_guarded<E: Needle, G: Needle>(
+        &mut self,
+        expect: E,
+        guard: G,
+    ) -> Result<Found, Error>
+    where
+        S: Read + NonBlocking,
+    {
+        let result = expect_in_stream(
+            &mut self.stream,
+            MaybePlain::new(expect, self.strip_ansi),
+            MaybePlain::new(guard, self.strip_ansi),
+            self.expect_timeout,
+            self.buffer_limits,
+        );
+        result.map_err(|err| self.with_process_status(err))
+    }
+
+    /// Expect waits until a pattern is matched,
+    /// but fails at once if a guard is matched first.
+    ///
+    /// See sync version [Session::expect_guarded].
+    #[cfg(feature = "async")]
+    pub async fn expect_guarded<E: Needle, G: Needle>(
+        &mut self,
+        expect: E,
+        guard: G,
+    ) -> Result<Found, Error>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let result = expect_in_stream(
+            &mut self.stream,
+            MaybePlain::new(expect, self.strip_ansi),
+            MaybePlain::new(guard, self.strip_ansi),
+            self.expect_timeout,
+            self.buffer_limits,
+        )
+        .await;
+        result.map_err(|err| self.with_process_status(err))
     }
 
     /// Check checks if a pattern is matched.
@@ -223,23 +529,13 @@ impl Session {
     /// assert_eq!(m.first(), b"123");
     /// ```
     #[cfg(not(feature = "async"))]
-    pub fn check<E: Needle>(&mut self, needle: E) -> Result<Found, Error> {
-        let eof = self.stream.read_available()?;
-        let buf = self.stream.get_available();
-
-        let found = needle.check(buf, eof)?;
-        if !found.is_empty() {
-            let end_index = Found::right_most_index(&found);
-            let involved_bytes = buf[..end_index].to_vec();
-            self.stream.consume_from_buffer(end_index);
-            return Ok(Found::new(involved_bytes, found));
-        }
-
-        if eof {
-            return Err(Error::Eof);
-        }
-
-        Ok(Found::new(Vec::new(), Vec::new()))
+    pub fn check<E: Needle>(&mut self, needle: E) -> Result<Found, Error>
+    where
+        S: Read + NonBlocking,
+    {
+        let needle = MaybePlain::new(needle, self.strip_ansi);
+        let result = check_in_stream(&mut self.stream, needle, self.buffer_limits);
+        result.map_err(|err| self.with_process_status(err))
     }
 
     /// Check checks if a pattern is matched.
@@ -259,23 +555,13 @@ impl Session {
     /// # });
     /// ```
     #[cfg(feature = "async")]
-    pub async fn check<E: Needle>(&mut self, needle: E) -> Result<Found, Error> {
-        let eof = self.stream.read_available().await?;
-        let buf = self.stream.get_available();
-
-        let found = needle.check(buf, eof)?;
-        if !found.is_empty() {
-            let end_index = Found::right_most_index(&found);
-            let involved_bytes = buf[..end_index].to_vec();
-            self.stream.consume_from_buffer(end_index);
-            return Ok(Found::new(involved_bytes, found));
-        }
-
-        if eof {
-            return Err(Error::Eof);
-        }
-
-        Ok(Found::new(Vec::new(), Vec::new()))
+    pub async fn check<E: Needle>(&mut self, needle: E) -> Result<Found, Error>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let needle = MaybePlain::new(needle, self.strip_ansi);
+        let result = check_in_stream(&mut self.stream, needle, self.buffer_limits).await;
+        result.map_err(|err| self.with_process_status(err))
     }
 
     /// Is matched checks if a pattern is matched.
@@ -305,17 +591,28 @@ impl Session {
     /// assert_eq!(m, true);
     /// ```
     #[cfg(not(feature = "async"))]
-    pub fn is_matched<E: Needle>(&mut self, needle: E) -> Result<bool, Error> {
+    pub fn is_matched<E: Needle>(&mut self, needle: E) -> Result<bool, Error>
+    where
+        S: Read + NonBlocking,
+    {
+        let needle = MaybePlain::new(needle, self.strip_ansi);
+        let start = time::Instant::now();
         let eof = self.stream.read_available()?;
+        let idle = self.stream.last_read().elapsed();
         let buf = self.stream.get_available();
 
-        let found = needle.check(buf, eof)?;
+        let window = &buf[self.buffer_limits.window_start(buf.len())..];
+
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(eof, idle, &recheck).with_screen(self.stream.screen());
+        let found = needle.check_in_context(window, &ctx)?;
         if !found.is_empty() {
             return Ok(true);
         }
 
         if eof {
-            return Err(Error::Eof);
+            let err = eof_error(&needle, buf, start);
+            return Err(self.with_process_status(err));
         }
 
         Ok(false)
@@ -326,48 +623,314 @@ impl Session {
     ///
     /// See sync version [Session::is_matched].
     #[cfg(feature = "async")]
-    pub async fn is_matched<E: Needle>(&mut self, needle: E) -> Result<bool, Error> {
+    pub async fn is_matched<E: Needle>(&mut self, needle: E) -> Result<bool, Error>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let needle = MaybePlain::new(needle, self.strip_ansi);
+        let start = time::Instant::now();
         let eof = self.stream.read_available().await?;
+        let idle = self.stream.last_read().elapsed();
         let buf = self.stream.get_available();
 
-        let found = needle.check(buf, eof)?;
+        let window = &buf[self.buffer_limits.window_start(buf.len())..];
+
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(eof, idle, &recheck).with_screen(self.stream.screen());
+        let found = needle.check_in_context(window, &ctx)?;
         if !found.is_empty() {
             return Ok(true);
         }
 
         if eof {
-            return Err(Error::Eof);
+            let err = eof_error(&needle, buf, start);
+            return Err(self.with_process_status(err));
         }
 
         Ok(false)
     }
 
+    /// Expect waits until a pattern is matched in a stderr of a process.
+    ///
+    /// It works the same way as [Session::expect] but with a different stream.
+    /// The stderr is available only if a session was spawned by [Session::spawn_with_stderr].
+    ///
+    /// ```no_run
+    /// use std::process::Command;
+    ///
+    /// let mut cmd = Command::new("sh");
+    /// cmd.args(["-c", "echo 'Hello'; echo 'World' >&2"]);
+    ///
+    /// let mut p = expectrl::Session::spawn_with_stderr(cmd).unwrap();
+    /// p.expect_stderr("World").unwrap();
+    /// p.expect("Hello").unwrap();
+    /// ```
+    #[cfg(all(unix, not(feature = "async")))]
+    pub fn expect_stderr<E: Needle>(&mut self, expect: E) -> Result<Found, Error> {
+        let expect = MaybePlain::new(expect, self.strip_ansi);
+        let (timeout, limits) = (self.expect_timeout, self.buffer_limits);
+        let result = expect_in_stream(self.stderr_stream()?, expect, NoGuard, timeout, limits);
+        result.map_err(|err| self.with_process_status(err))
+    }
+
+    /// Expect waits until a pattern is matched in a stderr of a process.
+    ///
+    /// See sync version [Session::expect_stderr].
+    #[cfg(all(unix, feature = "async"))]
+    pub async fn expect_stderr<E: Needle>(&mut self, expect: E) -> Result<Found, Error> {
+        let expect = MaybePlain::new(expect, self.strip_ansi);
+        let (timeout, limits) = (self.expect_timeout, self.buffer_limits);
+        let result =
+            expect_in_stream(self.stderr_stream()?, expect, NoGuard, timeout, limits).await;
+        result.map_err(|err| self.with_process_status(err))
+    }
+
+    /// Check checks if a pattern is matched in a stderr of a process.
+    /// Returns empty found structure if nothing found.
+    ///
+    /// It works the same way as [Session::check] but with a different stream.
+    /// The stderr is available only if a session was spawned by [Session::spawn_with_stderr].
+    #[cfg(all(unix, not(feature = "async")))]
+    pub fn check_stderr<E: Needle>(&mut self, needle: E) -> Result<Found, Error> {
+        let needle = MaybePlain::new(needle, self.strip_ansi);
+        let limits = self.buffer_limits;
+        let result = check_in_stream(self.stderr_stream()?, needle, limits);
+        result.map_err(|err| self.with_process_status(err))
+    }
+
+    /// Check checks if a pattern is matched in a stderr of a process.
+    /// Returns empty found structure if nothing found.
+    ///
+    /// See sync version [Session::check_stderr].
+    #[cfg(all(unix, feature = "async"))]
+    pub async fn check_stderr<E: Needle>(&mut self, needle: E) -> Result<Found, Error> {
+        let needle = MaybePlain::new(needle, self.strip_ansi);
+        let limits = self.buffer_limits;
+        let result = check_in_stream(self.stderr_stream()?, needle, limits).await;
+        result.map_err(|err| self.with_process_status(err))
+    }
+
+    /// Attaches a status of a process to an expect error.
+    ///
+    /// The status is peeked without being collected,
+    /// so it's still available to [Session::wait].
+    #[cfg(unix)]
+    fn with_process_status(&self, mut err: Error) -> Error {
+        if let Error::ExpectTimeout(details)
+        | Error::Eof(details)
+        | Error::BufferOverflow(details) = &mut err
+        {
+            details.set_status(
+                self.process_group
+                    .and_then(|pid| peek_process_status(pid).ok()),
+            );
+        }
+
+        err
+    }
+
+    #[cfg(windows)]
+    fn with_process_status(&self, err: Error) -> Error {
+        err
+    }
+
+    #[cfg(unix)]
+    fn stderr_stream(&mut self) -> Result<&mut Stream<StderrStream>, Error> {
+        self.stderr.as_mut().ok_or(Error::StderrNotCaptured)
+    }
+
+    /// Sets a policy which is applied to a process group on drop.
+    ///
+    /// It has effect only for spawned sessions.
+    #[cfg(unix)]
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
+    }
+
+    /// Sets a process group which is cleaned up on drop.
+    #[cfg(unix)]
+    pub(crate) fn set_process_group(&mut self, group: Pid) {
+        self.process_group = Some(group);
+    }
+
+    /// Forgets a process group once its leader is collected.
+    ///
+    /// An id of the group may be reused afterwards, so it must not be signaled.
+    #[cfg(unix)]
+    pub(crate) fn unset_process_group(&mut self) {
+        self.process_group = None;
+    }
+
     /// Set the pty session's expect timeout.
     pub fn set_expect_timeout(&mut self, expect_timeout: Option<Duration>) {
         self.expect_timeout = expect_timeout;
     }
+
+    /// Sets a number of the last bytes of a buffer which are searched for a match.
+    ///
+    /// By default the whole buffer is searched.
+    /// As [Session::expect] checks a buffer byte by byte,
+    /// a window makes each check take a constant time instead of growing with the buffer.
+    ///
+    /// A needle which needs to see more than a window will never match.
+    /// [crate::Found::before] still returns all the bytes before a match.
+    pub fn set_search_window(&mut self, size: Option<usize>) {
+        self.buffer_limits.search_window = size;
+    }
+
+    /// Sets a maximum number of checked bytes which are kept in a buffer if no match was found.
+    ///
+    /// By default it's not limited.
+    /// What happens when the limit is exceeded is set by [Session::set_discard_policy].
+    ///
+    /// Bytes which were not checked yet are never discarded,
+    /// so a buffer may temporarily be bigger by the size of a single read.
+    pub fn set_max_buffer_size(&mut self, size: Option<usize>) {
+        self.buffer_limits.max_size = size;
+    }
+
+    /// Sets a policy which is applied when a buffer exceeds its maximum size.
+    ///
+    /// See [Session::set_max_buffer_size].
+    pub fn set_discard_policy(&mut self, policy: DiscardPolicy) {
+        self.buffer_limits.policy = policy;
+    }
+
+    /// Sets whether needles are checked against an output with ANSI escape sequences removed.
+    ///
+    /// It's off by default.
+    /// When it's on every needle is wrapped in [crate::Plain],
+    /// so `"hello"` matches a colored `h\x1b[1mello`.
+    /// A [Found] still refers to the original bytes.
+    ///
+    /// ```no_run
+    /// # #[cfg(not(feature = "async"))]
+    /// # {
+    /// use expectrl::Session;
+    /// use std::io::Cursor;
+    ///
+    /// let mut session = Session::new((), Cursor::new(b"\x1b[32mok\x1b[0m: h\x1b[1mello".to_vec()));
+    /// session.set_strip_ansi(true);
+    /// let m = session.expect("hello").unwrap();
+    /// assert_eq!(m.before(), b"\x1b[32mok\x1b[0m: ");
+    /// assert_eq!(m.first(), b"h\x1b[1mello");
+    /// # }
+    /// ```
+    pub fn set_strip_ansi(&mut self, on: bool) {
+        self.strip_ansi = on;
+    }
+
+    /// Sets a key map which is used by [Session::send_key] and [Session::send_keys].
+    ///
+    /// A spawned session gets a map for a `TERM` of its process,
+    /// otherwise it has xterm sequences.
+    pub fn set_key_map(&mut self, key_map: KeyMap) {
+        self.key_map = key_map;
+    }
+
+    /// Returns a key map which is used by [Session::send_key] and [Session::send_keys].
+    pub fn get_key_map(&self) -> &KeyMap {
+        &self.key_map
+    }
+
+    /// Encodes all keys or fails if any of them has no sequence.
+    fn encode_keys(&self, keys: impl IntoIterator<Item = Key>) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for key in keys {
+            let seq = self.key_map.encode(key).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("A key {:?} has no sequence in a key map", key),
+                )
+            })?;
+            bytes.extend(seq);
+        }
+
+        Ok(bytes)
+    }
+
+    /// Enables a virtual terminal screen which is fed by an output of a process.
+    ///
+    /// It makes [Session::screen] available and it's required by [crate::ScreenText].
+    /// Only the output which is read after the call is processed,
+    /// so it's better to enable it right after a session is spawned.
+    ///
+    /// A size is expected to be the same as a window size of a pseudo terminal.
+    pub fn enable_screen(&mut self, cols: u16, rows: u16) {
+        self.stream.set_screen(Some(Screen::new(cols, rows)));
+    }
+
+    /// Disables a virtual terminal screen.
+    pub fn disable_screen(&mut self) {
+        self.stream.set_screen(None);
+    }
+
+    /// Returns a virtual terminal screen if it's enabled by [Session::enable_screen].
+    ///
+    /// It reflects all the output which was read so far,
+    /// including the data which is buffered but not matched yet.
+    pub fn screen(&self) -> Option<&Screen> {
+        self.stream.screen()
+    }
+
+    /// Makes a session answer queries which a process sends to a terminal.
+    ///
+    /// Some programs ask for a cursor position (`ESC[6n`), device attributes (`ESC[c`)
+    /// or colors (`OSC 10` and `OSC 11`) and block until a terminal answers.
+    /// The queries are spotted in an output as it's read,
+    /// and replies are written to a given writer, which is expected to be an input of a process.
+    ///
+    /// A cursor position is taken from a screen if it's enabled by [Session::enable_screen],
+    /// otherwise it's reported to be in the top left corner.
+    ///
+    /// Queries are answered during [Session::interact] as well,
+    /// so it's better to disable the replies if a real terminal answers them there.
+    pub fn enable_query_replies_with<W>(&mut self, writer: W)
+    where
+        W: io::Write + Send + 'static,
+    {
+        self.stream.set_responder(Some(Responder::new(writer)));
+    }
+
+    /// Stops answering queries of a process.
+    pub fn disable_query_replies(&mut self) {
+        self.stream.set_responder(None);
+    }
+
+    /// Returns a reference to an underlying stream.
+    pub fn get_stream(&self) -> &S {
+        self.stream.get_ref()
+    }
+
+    /// Returns a mutable reference to an underlying stream.
+    ///
+    /// Be carefull reading from it directly,
+    /// because the session may have already buffered some of the data.
+    pub fn get_stream_mut(&mut self) -> &mut S {
+        self.stream.inner_mut()
+    }
 }
 
 #[cfg(not(feature = "async"))]
-impl Session {
+impl<P, S: Write> Session<P, S> {
     /// Send text to child's `STDIN`.
     ///
     /// To write bytes you can use a [std::io::Write] operations instead.
-    pub fn send<S: AsRef<str>>(&mut self, s: S) -> io::Result<()> {
+    pub fn send<Str: AsRef<str>>(&mut self, s: Str) -> io::Result<()> {
         self.stream.write_all(s.as_ref().as_bytes())
     }
 
     /// Send a line to child's `STDIN`.
-    pub fn send_line<S: AsRef<str>>(&mut self, s: S) -> io::Result<()> {
+    pub fn send_line<Str: AsRef<str>>(&mut self, s: Str) -> io::Result<()> {
         #[cfg(windows)]
         {
             // win32 has writefilegather function which could be used as write_vectored but it asyncronos which may involve some issue?
             // https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-writefilegather
 
             const LINE_ENDING: &[u8] = b"\r\n";
-            let _ = self.write_all(s.as_ref().as_bytes())?;
-            let _ = self.write_all(LINE_ENDING)?;
-            self.flush()?;
+            let _ = self.stream.write_all(s.as_ref().as_bytes())?;
+            let _ = self.stream.write_all(LINE_ENDING)?;
+            self.stream.flush()?;
             Ok(())
         }
         #[cfg(not(windows))]
@@ -385,8 +948,8 @@ impl Session {
             // But we rely on this fact not explicitely.
             //
             // todo: check amount of written bytes ands write the rest if not everyting was written already.
-            let _ = self.write_vectored(bufs)?;
-            self.flush()?;
+            let _ = self.stream.write_vectored(bufs)?;
+            self.stream.flush()?;
 
             Ok(())
         }
@@ -417,22 +980,158 @@ impl Session {
         self.stream.write_all(&[code.into()])
     }
 
-    /// Send `EOF` indicator to a child process.
+    /// Sends a key the way a terminal encodes it.
     ///
-    /// Often `eof` char handled as it would be a CTRL-C.
-    #[cfg(unix)]
-    pub fn send_eof(&mut self) -> io::Result<()> {
-        self.stream.write_all(&[self.proc.get_eof_char()])
+    /// See [Session::set_key_map].
+    ///
+    /// ```no_run
+    /// use expectrl::{Key, KeyCode, Session};
+    /// use std::process::Command;
+    ///
+    /// let mut process = Session::spawn(Command::new("vim")).unwrap();
+    /// process.send_key(Key::new(KeyCode::Up).ctrl()).unwrap();
+    /// process.send_key('i').unwrap();
+    /// ```
+    pub fn send_key(&mut self, key: impl Into<Key>) -> io::Result<()> {
+        let bytes = self.encode_keys(Some(key.into()))?;
+        self.stream.write_all(&bytes)?;
+        self.stream.flush()
+    }
+
+    /// Sends keys written in a vim like notation, see [Key::parse_sequence].
+    ///
+    /// Nothing is sent if a notation can't be parsed.
+    ///
+    /// ```no_run
+    /// use expectrl::Session;
+    /// use std::process::Command;
+    ///
+    /// let mut process = Session::spawn(Command::new("vim")).unwrap();
+    /// process.send_keys("ihello<Esc>:wq<CR>").unwrap();
+    /// ```
+    pub fn send_keys(&mut self, keys: &str) -> io::Result<()> {
+        let keys = parse_keys(keys)?;
+        let bytes = self.encode_keys(keys)?;
+        self.stream.write_all(&bytes)?;
+        self.stream.flush()
+    }
+}
+
+#[cfg(unix)]
+impl<S> Session<PtyProcess, S> {
+    /// Terminates a process and its process group.
+    ///
+    /// It sends `SIGHUP` and `SIGTERM` to the group and waits for the group to exit.
+    /// Processes which are still alive after `grace` are killed by `SIGKILL`.
+    ///
+    /// It returns an exit status of a process.
+    #[cfg(not(feature = "async"))]
+    pub fn terminate(&mut self, grace: Duration) -> Result<WaitStatus, Error> {
+        let status = terminate_process_group(self.proc.pid(), grace)?;
+        self.unset_process_group();
+        Ok(status)
+    }
+
+    /// Terminates a process and its process group.
+    ///
+    /// See sync version [Session::terminate].
+    #[cfg(feature = "async")]
+    pub async fn terminate(&mut self, grace: Duration) -> Result<WaitStatus, Error> {
+        let status = terminate_process_group_async(self.proc.pid(), grace).await?;
+        self.unset_process_group();
+        Ok(status)
+    }
+
+    /// Waits for a process to exit but not longer than a given timeout.
+    ///
+    /// It returns [None] if a process is still alive after the timeout.
+    /// A status is not collected,
+    /// so it can still be retrieved by [PtyProcess::wait] or [Session::terminate].
+    #[cfg(not(feature = "async"))]
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<Option<WaitStatus>, Error> {
+        wait_process_timeout(self.proc.pid(), timeout)
+    }
+
+    /// Waits for a process to exit but not longer than a given timeout.
+    ///
+    /// See sync version [Session::wait_timeout].
+    #[cfg(feature = "async")]
+    pub async fn wait_timeout(&self, timeout: Duration) -> Result<Option<WaitStatus>, Error> {
+        wait_process_timeout(self.proc.pid(), timeout).await
+    }
+
+    /// Makes a session answer queries which a process sends to a terminal.
+    ///
+    /// Replies are written to the pseudo terminal.
+    /// See [Session::enable_query_replies_with].
+    ///
+    /// ```
+    /// use expectrl::Session;
+    /// use std::process::Command;
+    ///
+    /// let mut p = Session::spawn(Command::new("cat")).unwrap();
+    /// p.enable_screen(80, 24);
+    /// p.enable_query_replies().unwrap();
+    /// ```
+    pub fn enable_query_replies(&mut self) -> Result<(), Error> {
+        let writer = self.proc.get_pty_stream()?;
+        self.enable_query_replies_with(writer);
+        Ok(())
+    }
+
+    /// Returns a window size of a pseudo terminal as (columns, rows).
+    pub fn get_window_size(&self) -> Result<(u16, u16), Error> {
+        let size = self.proc.get_window_size()?;
+        Ok(size)
+    }
+
+    /// Sets a window size of a pseudo terminal.
+    ///
+    /// A foreground process group of the terminal is notified by `SIGWINCH`,
+    /// so the process may redraw itself.
+    pub fn set_window_size(&mut self, cols: u16, rows: u16) -> Result<(), Error> {
+        use nix::unistd::tcgetpgrp;
+        use std::os::unix::prelude::AsRawFd;
+
+        self.proc.set_window_size(cols, rows)?;
+
+        if let Some(screen) = self.stream.screen_mut() {
+            screen.resize(cols, rows);
+        }
+
+        // A kernel usually sends SIGWINCH on its own, but only if a size was actually changed.
+        // So we send it explicitly.
+        let pty = self.proc.get_raw_handle()?;
+        let result = tcgetpgrp(pty.as_raw_fd()).and_then(|group| killpg(group, Signal::SIGWINCH));
+        match result {
+            // A process might have exited already.
+            Ok(()) | Err(nix::Error::Sys(Errno::ENOTTY)) | Err(nix::Error::Sys(Errno::ESRCH)) => {
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(all(unix, not(feature = "async")))]
+impl<S: Write> Session<PtyProcess, S> {
+    /// Send `EOF` indicator to a child process.
+    ///
+    /// Often `eof` char handled as it would be a CTRL-C.
+    pub fn send_eof(&mut self) -> io::Result<()> {
+        self.stream.write_all(&[self.proc.get_eof_char()])
     }
 
     /// Send `INTR` indicator to a child process.
     ///
     /// Often `intr` char handled as it would be a CTRL-D.
-    #[cfg(unix)]
     pub fn send_intr(&mut self) -> io::Result<()> {
         self.stream.write_all(&[self.proc.get_intr_char()])
     }
+}
 
+#[cfg(not(feature = "async"))]
+impl Session {
     /// Interact gives control of the child process to the interactive user (the
     /// human at the keyboard).
     ///
@@ -463,24 +1162,24 @@ impl Session {
 }
 
 #[cfg(all(feature = "async", not(windows)))]
-impl Session {
+impl<P, S: AsyncWrite + Unpin> Session<P, S> {
     /// Send text to child's `STDIN`.
     ///
     /// To write bytes you can use a [std::io::Write] operations instead.
-    pub async fn send<S: AsRef<str>>(&mut self, s: S) -> io::Result<()> {
+    pub async fn send<Str: AsRef<str>>(&mut self, s: Str) -> io::Result<()> {
         self.stream.write_all(s.as_ref().as_bytes()).await
     }
 
     /// Send a line to child's `STDIN`.
-    pub async fn send_line<S: AsRef<str>>(&mut self, s: S) -> io::Result<()> {
+    pub async fn send_line<Str: AsRef<str>>(&mut self, s: Str) -> io::Result<()> {
         #[cfg(windows)]
         const LINE_ENDING: &[u8] = b"\r\n";
         #[cfg(not(windows))]
         const LINE_ENDING: &[u8] = b"\n";
 
-        let _ = self.write_all(s.as_ref().as_bytes()).await?;
-        let _ = self.write_all(LINE_ENDING).await?;
-        self.flush().await?;
+        self.stream.write_all(s.as_ref().as_bytes()).await?;
+        self.stream.write_all(LINE_ENDING).await?;
+        self.stream.flush().await?;
 
         Ok(())
     }
@@ -509,6 +1208,49 @@ impl Session {
         self.stream.write_all(&[code.into()]).await
     }
 
+    /// Sends a key the way a terminal encodes it.
+    ///
+    /// See [Session::set_key_map].
+    ///
+    /// ```no_run
+    /// use expectrl::{Key, KeyCode, Session};
+    /// use std::process::Command;
+    ///
+    /// # futures_lite::future::block_on(async {
+    /// let mut process = Session::spawn(Command::new("vim")).unwrap();
+    /// process.send_key(Key::new(KeyCode::Up).ctrl()).await.unwrap();
+    /// process.send_key('i').await.unwrap();
+    /// # });
+    /// ```
+    pub async fn send_key(&mut self, key: impl Into<Key>) -> io::Result<()> {
+        let bytes = self.encode_keys(Some(key.into()))?;
+        self.stream.write_all(&bytes).await?;
+        self.stream.flush().await
+    }
+
+    /// Sends keys written in a vim like notation, see [Key::parse_sequence].
+    ///
+    /// Nothing is sent if a notation can't be parsed.
+    ///
+    /// ```no_run
+    /// use expectrl::Session;
+    /// use std::process::Command;
+    ///
+    /// # futures_lite::future::block_on(async {
+    /// let mut process = Session::spawn(Command::new("vim")).unwrap();
+    /// process.send_keys("ihello<Esc>:wq<CR>").await.unwrap();
+    /// # });
+    /// ```
+    pub async fn send_keys(&mut self, keys: &str) -> io::Result<()> {
+        let keys = parse_keys(keys)?;
+        let bytes = self.encode_keys(keys)?;
+        self.stream.write_all(&bytes).await?;
+        self.stream.flush().await
+    }
+}
+
+#[cfg(all(feature = "async", not(windows)))]
+impl<S: AsyncWrite + Unpin> Session<PtyProcess, S> {
     /// Send `EOF` indicator to a child process.
     ///
     /// Often `eof` char handled as it would be a CTRL-C.
@@ -522,7 +1264,10 @@ impl Session {
     pub async fn send_intr(&mut self) -> io::Result<()> {
         self.stream.write_all(&[self.proc.get_intr_char()]).await
     }
+}
 
+#[cfg(all(feature = "async", not(windows)))]
+impl Session {
     /// Interact gives control of the child process to the interactive user (the
     /// human at the keyboard).
     ///
@@ -544,60 +1289,44 @@ impl Session {
             .interact(self)
             .await
     }
-
-    pub(crate) async fn read_available_once(
-        &mut self,
-        buf: &mut [u8],
-    ) -> Result<Option<usize>, Error> {
-        self.stream
-            .read_available_once(buf)
-            .await
-            .map_err(|err| err.into())
-    }
-
-    pub(crate) fn get_available(&mut self) -> &[u8] {
-        self.stream.get_available()
-    }
-
-    pub(crate) fn consume_from_buffer(&mut self, n: usize) {
-        self.stream.consume_from_buffer(n);
-    }
-}
-
-#[cfg(unix)]
-impl Deref for Session {
-    type Target = PtyProcess;
-
-    fn deref(&self) -> &Self::Target {
-        &self.proc
-    }
 }
 
 #[cfg(unix)]
-impl DerefMut for Session {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.proc
+impl<P, S> Drop for Session<P, S> {
+    fn drop(&mut self) {
+        let group = match self.process_group {
+            Some(group) => group,
+            None => return,
+        };
+
+        let grace = match self.drop_policy {
+            DropPolicy::Terminate(grace) => grace,
+            DropPolicy::Kill => Duration::from_secs(0),
+            DropPolicy::Ignore => return,
+        };
+
+        // It fails if a process was already collected, e.g. by `wait`.
+        // Then the group is left alone because its id may be reused.
+        let _ = terminate_process_group(group, grace);
     }
 }
 
-#[cfg(windows)]
-impl Deref for Session {
-    type Target = conpty::Process;
+impl<P, S> Deref for Session<P, S> {
+    type Target = P;
 
     fn deref(&self) -> &Self::Target {
         &self.proc
     }
 }
 
-#[cfg(windows)]
-impl DerefMut for Session {
+impl<P, S> DerefMut for Session<P, S> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.proc
     }
 }
 
 #[cfg(feature = "async")]
-impl Session {
+impl<P, S: AsyncRead + Unpin> Session<P, S> {
     /// Try to read in a non-blocking mode.
     ///
     /// Returns `[std::io::ErrorKind::WouldBlock]`
@@ -613,7 +1342,7 @@ impl Session {
 }
 
 #[cfg(not(feature = "async"))]
-impl Session {
+impl<P, S: Read + NonBlocking> Session<P, S> {
     /// Try to read in a non-blocking mode.
     ///
     /// Returns `[std::io::ErrorKind::WouldBlock]`
@@ -629,7 +1358,7 @@ impl Session {
 }
 
 #[cfg(not(feature = "async"))]
-impl std::io::Write for Session {
+impl<P, S: Write> Write for Session<P, S> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         self.stream.write(buf)
     }
@@ -644,14 +1373,14 @@ impl std::io::Write for Session {
 }
 
 #[cfg(not(feature = "async"))]
-impl std::io::Read for Session {
+impl<P, S: Read> Read for Session<P, S> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         self.stream.read(buf)
     }
 }
 
 #[cfg(not(feature = "async"))]
-impl std::io::BufRead for Session {
+impl<P, S: Read> std::io::BufRead for Session<P, S> {
     fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
         self.stream.fill_buf()
     }
@@ -662,7 +1391,7 @@ impl std::io::BufRead for Session {
 }
 
 #[cfg(feature = "async")]
-impl futures_lite::io::AsyncWrite for Session {
+impl<P: Unpin, S: AsyncWrite + Unpin> AsyncWrite for Session<P, S> {
     fn poll_write(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -687,7 +1416,7 @@ impl futures_lite::io::AsyncWrite for Session {
 }
 
 #[cfg(feature = "async")]
-impl futures_lite::io::AsyncRead for Session {
+impl<P: Unpin, S: AsyncRead + Unpin> AsyncRead for Session<P, S> {
     fn poll_read(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -698,7 +1427,7 @@ impl futures_lite::io::AsyncRead for Session {
 }
 
 #[cfg(feature = "async")]
-impl futures_lite::io::AsyncBufRead for Session {
+impl<P: Unpin, S: AsyncRead + Unpin> futures_lite::io::AsyncBufRead for Session<P, S> {
     fn poll_fill_buf(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -712,3 +1441,622 @@ impl futures_lite::io::AsyncBufRead for Session {
         std::pin::Pin::new(&mut self.stream).consume(amt);
     }
 }
+
+#[cfg(not(feature = "async"))]
+fn expect_in_stream<S, E, G>(
+    stream: &mut Stream<S>,
+    expect: E,
+    guard: G,
+    timeout: Option<Duration>,
+    limits: BufferLimits,
+) -> Result<Found, Error>
+where
+    S: Read + NonBlocking,
+    E: Needle,
+    G: Needle,
+{
+    let mut checking_data_length = 0;
+    let mut nothing_to_read = false;
+    let mut buf = [0; EXPECT_READ_CHUNK_SIZE];
+    let start = time::Instant::now();
+    loop {
+        if checking_data_length == stream.get_available().len() {
+            // All buffered data was checked so we read a next chunk.
+            //
+            // Reading a big chunk reduces the number of IO operations,
+            // but the data is still checked prefix by prefix (one byte more each iteration)
+            // to make things as lazy as possible.
+            //
+            // It's chose is important in using Regex as a Needle.
+            // Imagine we have a `\d+` regex.
+            // Checking the whole chunk would match more digits than a byte by byte reading would.
+            //
+            // An EOF indicator is kept by the stream itself,
+            // so it's not lost while the rest of the chunk is being checked.
+            nothing_to_read = stream.read_available_once(&mut buf)?.is_none();
+        }
+
+        let eof_reached = stream.is_eof();
+        let last_read = stream.last_read();
+        let available = stream.get_available();
+
+        // We intentinally not increase the counter
+        // and run check one more time even though the data isn't changed.
+        // Because it may be important for custom implementations of Needle.
+        if checking_data_length < available.len() {
+            checking_data_length += 1;
+        }
+
+        // EOF is reported only when all the data before it was checked.
+        let eof = eof_reached && checking_data_length == available.len();
+
+        // A stream is considered idle only when all the data was checked.
+        let idle = match checking_data_length == available.len() {
+            true => last_read.elapsed(),
+            false => Duration::ZERO,
+        };
+
+        let data = &available[..checking_data_length];
+
+        // A screen shows the same as a buffer only when all the data was checked.
+        let screen = match checking_data_length == available.len() {
+            true => stream.screen(),
+            false => None,
+        };
+
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(eof, idle, &recheck).with_screen(screen);
+        if let Some((end_index, found)) = find_in_window(&expect, data, &ctx, &limits)? {
+            stream.consume_from_buffer(end_index);
+            return Ok(found);
+        }
+
+        if let Some((end_index, found)) = find_in_window(&guard, data, &ctx, &limits)? {
+            stream.consume_from_buffer(end_index);
+            return Err(Error::GuardMatched(Box::new(found)));
+        }
+
+        if eof {
+            return Err(eof_error(&expect, available, start));
+        }
+
+        if let Some(timeout) = timeout {
+            if start.elapsed() > timeout {
+                let details = ExpectDetails::new(expect.description(), available, start.elapsed());
+                return Err(Error::ExpectTimeout(Box::new(details)));
+            }
+        }
+
+        let all_checked = checking_data_length == available.len();
+
+        // The checked bytes had no match so the oldest of them can be discarded.
+        let excess = limits.excess(checking_data_length);
+        if excess > 0 {
+            match limits.policy {
+                DiscardPolicy::DropOldest => {
+                    stream.consume_from_buffer(excess);
+                    checking_data_length -= excess;
+                }
+                DiscardPolicy::Fail => {
+                    let details =
+                        ExpectDetails::new(expect.description(), available, start.elapsed());
+                    return Err(Error::BufferOverflow(Box::new(details)));
+                }
+            }
+        }
+
+        // All the data was checked and the stream has nothing more to read,
+        // so we wait for an output instead of spinning in the loop.
+        //
+        // A needle may ask to be checked again after a while even without new data.
+        if nothing_to_read && all_checked {
+            let left_time = timeout.map(|timeout| timeout.saturating_sub(start.elapsed()));
+            let wait_time = match (left_time, recheck.get()) {
+                (Some(left_time), Some(recheck)) => Some(left_time.min(recheck)),
+                (left_time, recheck) => left_time.or(recheck),
+            };
+
+            stream.wait_readable(wait_time)?;
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+async fn expect_in_stream<S, E, G>(
+    stream: &mut Stream<S>,
+    expect: E,
+    guard: G,
+    timeout: Option<Duration>,
+    limits: BufferLimits,
+) -> Result<Found, Error>
+where
+    S: AsyncRead + Unpin,
+    E: Needle,
+    G: Needle,
+{
+    let mut checking_data_length = 0;
+    let mut nothing_to_read = false;
+    let mut buf = [0; EXPECT_READ_CHUNK_SIZE];
+    let start = time::Instant::now();
+    loop {
+        if checking_data_length == stream.get_available().len() {
+            // All buffered data was checked so we read a next chunk.
+            //
+            // Reading a big chunk reduces the number of IO operations,
+            // but the data is still checked prefix by prefix (one byte more each iteration)
+            // to make things as lazy as possible.
+            //
+            // It's chose is important in using Regex as a Needle.
+            // Imagine we have a `\d+` regex.
+            // Checking the whole chunk would match more digits than a byte by byte reading would.
+            //
+            // An EOF indicator is kept by the stream itself,
+            // so it's not lost while the rest of the chunk is being checked.
+            nothing_to_read = stream.read_available_once(&mut buf).await?.is_none();
+        }
+
+        let eof_reached = stream.is_eof();
+        let last_read = stream.last_read();
+        let available = stream.get_available();
+
+        // We intentinally not increase the counter
+        // and run check one more time even though the data isn't changed.
+        // Because it may be important for custom implementations of Needle.
+        if checking_data_length < available.len() {
+            checking_data_length += 1;
+        }
+
+        // EOF is reported only when all the data before it was checked.
+        let eof = eof_reached && checking_data_length == available.len();
+
+        // A stream is considered idle only when all the data was checked.
+        let idle = match checking_data_length == available.len() {
+            true => last_read.elapsed(),
+            false => Duration::ZERO,
+        };
+
+        let data = &available[..checking_data_length];
+
+        // A screen shows the same as a buffer only when all the data was checked.
+        let screen = match checking_data_length == available.len() {
+            true => stream.screen(),
+            false => None,
+        };
+
+        let recheck = Cell::new(None);
+        let ctx = NeedleContext::new(eof, idle, &recheck).with_screen(screen);
+        if let Some((end_index, found)) = find_in_window(&expect, data, &ctx, &limits)? {
+            stream.consume_from_buffer(end_index);
+            return Ok(found);
+        }
+
+        if let Some((end_index, found)) = find_in_window(&guard, data, &ctx, &limits)? {
+            stream.consume_from_buffer(end_index);
+            return Err(Error::GuardMatched(Box::new(found)));
+        }
+
+        if eof {
+            return Err(eof_error(&expect, available, start));
+        }
+
+        if let Some(timeout) = timeout {
+            if start.elapsed() > timeout {
+                let details = ExpectDetails::new(expect.description(), available, start.elapsed());
+                return Err(Error::ExpectTimeout(Box::new(details)));
+            }
+        }
+
+        let all_checked = checking_data_length == available.len();
+
+        // The checked bytes had no match so the oldest of them can be discarded.
+        let excess = limits.excess(checking_data_length);
+        if excess > 0 {
+            match limits.policy {
+                DiscardPolicy::DropOldest => {
+                    stream.consume_from_buffer(excess);
+                    checking_data_length -= excess;
+                }
+                DiscardPolicy::Fail => {
+                    let details =
+                        ExpectDetails::new(expect.description(), available, start.elapsed());
+                    return Err(Error::BufferOverflow(Box::new(details)));
+                }
+            }
+        }
+
+        // All the data was checked and the stream has nothing more to read,
+        // so we wait until it's readable or the timeout fires.
+        //
+        // The read data is put into the stream buffer right away,
+        // so if the future is dropped nothing is lost.
+        //
+        // A needle may ask to be checked again after a while even without new data.
+        //
+        // Replies to queries which are left are retried after a while,
+        // as a process may wait for them before it prints anything.
+        if nothing_to_read && all_checked {
+            let deadline = timeout.map(|timeout| start + timeout);
+            let recheck = match (recheck.get(), stream.flush_replies()) {
+                (recheck, true) => recheck,
+                (Some(recheck), false) => Some(recheck.min(REPLY_RETRY_INTERVAL)),
+                (None, false) => Some(REPLY_RETRY_INTERVAL),
+            };
+            let recheck_at = recheck.map(|recheck| time::Instant::now() + recheck);
+            let wake_up = match (deadline, recheck_at) {
+                (Some(deadline), Some(recheck_at)) => Some(deadline.min(recheck_at)),
+                (deadline, recheck_at) => deadline.or(recheck_at),
+            };
+
+            match wake_up {
+                Some(wake_up) => {
+                    let read = async { stream.read_available_wait(&mut buf).await.map(|_| ()) };
+                    let timer = async {
+                        async_io::Timer::at(wake_up).await;
+                        Ok(())
+                    };
+                    futures_lite::future::or(read, timer).await?;
+                }
+                None => {
+                    stream.read_available_wait(&mut buf).await?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "async"))]
+fn check_in_stream<S, E>(
+    stream: &mut Stream<S>,
+    needle: E,
+    limits: BufferLimits,
+) -> Result<Found, Error>
+where
+    S: Read + NonBlocking,
+    E: Needle,
+{
+    let start = time::Instant::now();
+    let eof = stream.read_available()?;
+    let idle = stream.last_read().elapsed();
+    let buf = stream.get_available();
+
+    let recheck = Cell::new(None);
+    let ctx = NeedleContext::new(eof, idle, &recheck).with_screen(stream.screen());
+    if let Some((end_index, found)) = find_in_window(&needle, buf, &ctx, &limits)? {
+        stream.consume_from_buffer(end_index);
+        return Ok(found);
+    }
+
+    if eof {
+        return Err(eof_error(&needle, buf, start));
+    }
+
+    discard_excess(stream, &needle, &limits, start)?;
+
+    Ok(Found::new(Vec::new(), Vec::new()))
+}
+
+#[cfg(feature = "async")]
+async fn check_in_stream<S, E>(
+    stream: &mut Stream<S>,
+    needle: E,
+    limits: BufferLimits,
+) -> Result<Found, Error>
+where
+    S: AsyncRead + Unpin,
+    E: Needle,
+{
+    let start = time::Instant::now();
+    let eof = stream.read_available().await?;
+    let idle = stream.last_read().elapsed();
+    let buf = stream.get_available();
+
+    let recheck = Cell::new(None);
+    let ctx = NeedleContext::new(eof, idle, &recheck).with_screen(stream.screen());
+    if let Some((end_index, found)) = find_in_window(&needle, buf, &ctx, &limits)? {
+        stream.consume_from_buffer(end_index);
+        return Ok(found);
+    }
+
+    if eof {
+        return Err(eof_error(&needle, buf, start));
+    }
+
+    discard_excess(stream, &needle, &limits, start)?;
+
+    Ok(Found::new(Vec::new(), Vec::new()))
+}
+
+/// A guard which is never matched.
+struct NoGuard;
+
+impl Needle for NoGuard {
+    fn check(&self, _: &[u8], _: bool) -> Result<Vec<Match>, Error> {
+        Ok(Vec::new())
+    }
+}
+
+/// Checks a needle against the bytes which are inside of a search window.
+///
+/// It returns a found match and a number of bytes it takes in data.
+fn find_in_window<E: Needle>(
+    needle: &E,
+    data: &[u8],
+    ctx: &NeedleContext<'_>,
+    limits: &BufferLimits,
+) -> Result<Option<(usize, Found)>, Error> {
+    let offset = limits.window_start(data.len());
+    let window = &data[offset..];
+
+    let groups = needle.check_groups_in_context(window, ctx)?;
+    let found = flatten_groups(&groups);
+    if found.is_empty() {
+        return Ok(None);
+    }
+
+    // The matches are made relative to the whole data.
+    let found: Vec<Match> = found.into_iter().map(|m| m.shift(offset)).collect();
+    let groups = groups.into_iter().map(|m| m.shift(offset)).collect();
+
+    let end_index = Found::right_most_index(&found);
+    let involved_bytes = data[..end_index].to_vec();
+    let found = Found::new(involved_bytes, found).with_groups(groups);
+
+    Ok(Some((end_index, found)))
+}
+
+/// Applies a discard policy to a buffer which was checked without a match.
+fn discard_excess<S, E: Needle>(
+    stream: &mut Stream<S>,
+    needle: &E,
+    limits: &BufferLimits,
+    start: time::Instant,
+) -> Result<(), Error> {
+    let buf = stream.get_available();
+    let excess = limits.excess(buf.len());
+    if excess == 0 {
+        return Ok(());
+    }
+
+    match limits.policy {
+        DiscardPolicy::DropOldest => {
+            stream.consume_from_buffer(excess);
+            Ok(())
+        }
+        DiscardPolicy::Fail => {
+            let details = ExpectDetails::new(needle.description(), buf, start.elapsed());
+            Err(Error::BufferOverflow(Box::new(details)))
+        }
+    }
+}
+
+fn eof_error<E: Needle>(needle: &E, buf: &[u8], start: time::Instant) -> Error {
+    let details = ExpectDetails::new(needle.description(), buf, start.elapsed());
+    Error::Eof(Box::new(details))
+}
+
+/// Returns a status of a process without collecting it.
+///
+/// It returns [WaitStatus::StillAlive] if a process hasn't exited yet.
+#[cfg(unix)]
+fn peek_process_status(pid: Pid) -> nix::Result<WaitStatus> {
+    use nix::libc;
+    use std::convert::TryFrom;
+
+    // SAFETY: siginfo_t is a plain C structure, so it's valid to be zeroed.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let options = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+    // SAFETY: the pointer is valid for the duration of the call.
+    let ret = unsafe { libc::waitid(libc::P_PID, pid.as_raw() as libc::id_t, &mut info, options) };
+    if ret != 0 {
+        return Err(nix::Error::Sys(Errno::last()));
+    }
+
+    // SAFETY: the fields are set by waitid for a child state change.
+    let (child, status) = unsafe { (info.si_pid(), info.si_status()) };
+    if child == 0 {
+        return Ok(WaitStatus::StillAlive);
+    }
+
+    match info.si_code {
+        libc::CLD_EXITED => Ok(WaitStatus::Exited(pid, status)),
+        libc::CLD_KILLED => Ok(WaitStatus::Signaled(pid, Signal::try_from(status)?, false)),
+        libc::CLD_DUMPED => Ok(WaitStatus::Signaled(pid, Signal::try_from(status)?, true)),
+        // Only exited processes are requested.
+        _ => Err(nix::Error::Sys(Errno::EINVAL)),
+    }
+}
+
+/// An interval of checking a process status while waiting for it.
+#[cfg(unix)]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Waits for a process to exit for at most a given timeout.
+///
+/// A status is not collected, so a process can still be waited.
+#[cfg(all(unix, not(feature = "async")))]
+pub(crate) fn wait_process_timeout(
+    pid: Pid,
+    timeout: Duration,
+) -> Result<Option<WaitStatus>, Error> {
+    let start = time::Instant::now();
+    loop {
+        match peek_process_status(pid)? {
+            WaitStatus::StillAlive => {}
+            status => return Ok(Some(status)),
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Ok(None);
+        }
+
+        std::thread::sleep(POLL_INTERVAL.min(timeout - elapsed));
+    }
+}
+
+/// Waits for a process to exit for at most a given timeout.
+///
+/// A status is not collected, so a process can still be waited.
+#[cfg(all(unix, feature = "async"))]
+pub(crate) async fn wait_process_timeout(
+    pid: Pid,
+    timeout: Duration,
+) -> Result<Option<WaitStatus>, Error> {
+    let start = time::Instant::now();
+    loop {
+        match peek_process_status(pid)? {
+            WaitStatus::StillAlive => {}
+            status => return Ok(Some(status)),
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Ok(None);
+        }
+
+        async_io::Timer::after(POLL_INTERVAL.min(timeout - elapsed)).await;
+    }
+}
+
+/// Terminates a process group which leader is a given process.
+///
+/// It blocks a calling thread until the group exits or `grace` is over.
+///
+/// It returns an exit status of the leader.
+#[cfg(unix)]
+pub(crate) fn terminate_process_group(group: Pid, grace: Duration) -> Result<WaitStatus, Error> {
+    let mut termination = GroupTermination::start(group, grace)?;
+    while let Some(delay) = termination.poll()? {
+        std::thread::sleep(delay);
+    }
+
+    termination.finish()
+}
+
+/// Terminates a process group which leader is a given process.
+///
+/// See sync version [terminate_process_group].
+#[cfg(all(unix, feature = "async"))]
+pub(crate) async fn terminate_process_group_async(
+    group: Pid,
+    grace: Duration,
+) -> Result<WaitStatus, Error> {
+    let mut termination = GroupTermination::start(group, grace)?;
+    while let Some(delay) = termination.poll()? {
+        async_io::Timer::after(delay).await;
+    }
+
+    termination.finish()
+}
+
+/// A process group which was asked to exit.
+///
+/// The leader is collected as soon as it exits.
+/// The group id can't be reused while there are processes in the group,
+/// so the rest of the group can be waited for and killed after that.
+#[cfg(unix)]
+struct GroupTermination {
+    group: Pid,
+    /// An exit status of the leader, it's [WaitStatus::StillAlive] until the leader is collected.
+    status: WaitStatus,
+    is_empty: bool,
+    start: time::Instant,
+    grace: Duration,
+}
+
+#[cfg(unix)]
+impl GroupTermination {
+    fn start(group: Pid, grace: Duration) -> Result<Self, Error> {
+        // It fails if the leader was already collected.
+        peek_process_status(group)?;
+
+        // SIGCONT is sent in case a process was stopped,
+        // otherwise it wouldn't handle the signals.
+        for &signal in &[Signal::SIGHUP, Signal::SIGTERM, Signal::SIGCONT] {
+            signal_process_group(group, signal)?;
+        }
+
+        Ok(Self {
+            group,
+            status: WaitStatus::StillAlive,
+            is_empty: false,
+            start: time::Instant::now(),
+            grace,
+        })
+    }
+
+    /// Checks the group and returns how long to wait before the next check.
+    ///
+    /// It returns [None] once the group has exited or `grace` is over.
+    fn poll(&mut self) -> Result<Option<Duration>, Error> {
+        if self.status == WaitStatus::StillAlive
+            && peek_process_status(self.group)? != WaitStatus::StillAlive
+        {
+            self.status = waitpid(self.group, None)?;
+        }
+
+        // An exited leader is a member of the group until it's collected.
+        if self.status != WaitStatus::StillAlive && is_process_group_empty(self.group)? {
+            self.is_empty = true;
+            return Ok(None);
+        }
+
+        let elapsed = self.start.elapsed();
+        if elapsed >= self.grace {
+            return Ok(None);
+        }
+
+        Ok(Some(POLL_INTERVAL.min(self.grace - elapsed)))
+    }
+
+    /// Kills processes which are still in the group and returns an exit status of the leader.
+    fn finish(self) -> Result<WaitStatus, Error> {
+        if self.is_empty {
+            return Ok(self.status);
+        }
+
+        signal_process_group(self.group, Signal::SIGKILL)?;
+
+        match self.status {
+            WaitStatus::StillAlive => Ok(waitpid(self.group, None)?),
+            status => Ok(status),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_process_group_empty(group: Pid) -> Result<bool, Error> {
+    match killpg(group, None) {
+        Ok(()) | Err(nix::Error::Sys(Errno::EPERM)) => Ok(false),
+        Err(nix::Error::Sys(Errno::ESRCH)) => Ok(true),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(unix)]
+fn signal_process_group(group: Pid, signal: Signal) -> Result<(), Error> {
+    match killpg(group, signal) {
+        Ok(()) | Err(nix::Error::Sys(Errno::ESRCH)) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns a key map for a `TERM` which a command will be run with.
+///
+/// A `TERM` of the current process is used if the command inherits an environment
+/// and doesn't set or remove the variable.
+#[cfg(unix)]
+pub(crate) fn command_key_map(command: &Command, inherits_env: bool) -> KeyMap {
+    let term = match command.get_envs().find(|(key, _)| *key == "TERM") {
+        Some((_, value)) => value.map(|value| value.to_os_string()),
+        None if inherits_env => env::var_os("TERM"),
+        None => None,
+    };
+
+    term.and_then(|term| KeyMap::from_terminfo(term.to_str()?).ok())
+        .unwrap_or_default()
+}
+
+fn parse_keys(keys: &str) -> io::Result<Vec<Key>> {
+    Key::parse_sequence(keys).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
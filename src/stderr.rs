@@ -0,0 +1,118 @@
+//! Module contains a redirection of a stderr of a process spawned in a pseudo terminal.
+//!
+//! A process spawned by [ptyprocess::PtyProcess] has all its descriptors closed before `exec`,
+//! except the std streams which are bound to a pty.
+//! So an inherited pipe can't be used.
+//! Instead we create a named pipe (FIFO) and a child opens it as its stderr right before `exec`.
+
+use crate::stream::{make_non_blocking, nix_error_to_io};
+use nix::{
+    fcntl::{open, OFlag},
+    libc::STDERR_FILENO,
+    sys::stat::Mode,
+    unistd::{close, dup2, mkfifo},
+};
+use std::{
+    ffi::{CString, OsString},
+    fs::{self, File},
+    io,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        prelude::{AsRawFd, FromRawFd},
+        process::CommandExt,
+    },
+    path::PathBuf,
+    process::Command,
+};
+
+/// StderrFifo is a named pipe which is used as a stderr of a process.
+///
+/// The pipe file is removed on drop.
+/// It's OK to do it as soon as a process is spawned because it has already opened it.
+#[derive(Debug)]
+pub(crate) struct StderrFifo {
+    dir: PathBuf,
+    path: PathBuf,
+    reader: Option<File>,
+}
+
+impl StderrFifo {
+    /// Creates a FIFO in a temporary directory and opens its reading end.
+    pub(crate) fn new() -> io::Result<Self> {
+        let dir = make_temp_dir()?;
+        let path = dir.join("stderr");
+
+        if let Err(err) = mkfifo(&path, Mode::S_IRUSR | Mode::S_IWUSR) {
+            let _ = fs::remove_dir(&dir);
+            return Err(nix_error_to_io(err));
+        }
+
+        // A reading end is opened in a non-blocking mode,
+        // otherwise it would block until a writer is connected.
+        let reader = open(
+            &path,
+            OFlag::O_RDONLY | OFlag::O_NONBLOCK | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        );
+        let reader = match reader {
+            // SAFETY: the descriptor was just opened and it's owned only by the File.
+            Ok(fd) => unsafe { File::from_raw_fd(fd) },
+            Err(err) => {
+                let _ = fs::remove_file(&path);
+                let _ = fs::remove_dir(&dir);
+                return Err(nix_error_to_io(err));
+            }
+        };
+        make_non_blocking(reader.as_raw_fd(), false)?;
+
+        Ok(Self {
+            dir,
+            path,
+            reader: Some(reader),
+        })
+    }
+
+    /// Makes a command to use the FIFO as its stderr.
+    pub(crate) fn redirect(&self, command: &mut Command) {
+        let path = self.path.clone();
+
+        // SAFETY: the closure is called after fork,
+        // it only calls open, dup2 and close which are async-signal-safe.
+        unsafe {
+            command.pre_exec(move || {
+                let fd = open(&path, OFlag::O_WRONLY, Mode::empty()).map_err(nix_error_to_io)?;
+                dup2(fd, STDERR_FILENO).map_err(nix_error_to_io)?;
+                close(fd).map_err(nix_error_to_io)?;
+                Ok(())
+            });
+        }
+    }
+
+    /// Returns a reading end of a FIFO.
+    pub(crate) fn into_reader(mut self) -> File {
+        self.reader.take().expect("a reader is taken only once")
+    }
+}
+
+impl Drop for StderrFifo {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_dir(&self.dir);
+    }
+}
+
+fn make_temp_dir() -> io::Result<PathBuf> {
+    let template = std::env::temp_dir().join("expectrl.XXXXXX");
+    let template = CString::new(template.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    let mut template = template.into_bytes_with_nul();
+    // SAFETY: a template is a valid nul terminated string which is modified in place.
+    let ptr = unsafe { nix::libc::mkdtemp(template.as_mut_ptr() as *mut nix::libc::c_char) };
+    if ptr.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    template.pop();
+    Ok(PathBuf::from(OsString::from_vec(template)))
+}
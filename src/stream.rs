@@ -1,124 +1,360 @@
 //! Module responsible for IO operations of [crate::Session].
 //!
-//! Module contains [Stream] structure async and sync flow and different one for windows.
+//! Module contains [Stream] structure async and sync flow.
 //! It also contains a [ReaderWithBuffer] for controlling buffering.
+//!
+//! The streams are generic over an underlying IO object,
+//! so a session can be run over a pty as well as over a socket or an in-memory buffer.
+
+use crate::{responder::Responder, screen::Screen};
+use std::{io, time::Duration};
+
+/// An interval of retrying replies to queries which a process couldn't take yet.
+///
+/// A process which asked a query usually waits for a reply before it prints anything,
+/// so an output can't be waited for while replies are left.
+pub(crate) const REPLY_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+#[cfg(windows)]
+use std::io::{Read, Write};
 
 /// Stream represent a IO stream.
 #[cfg(not(feature = "async"))]
-pub type Stream = sync_stream::Stream;
+pub type Stream<S> = sync_stream::Stream<S>;
 
 /// Stream represent a IO stream.
 #[cfg(feature = "async")]
+pub type Stream<S> = async_stream::AsyncStream<S>;
+
+/// NonBlocking is an interface of a stream which can be switched
+/// between blocking and non-blocking modes.
+///
+/// It's required by a sync version of [crate::session::Session]
+/// to look at the available output without blocking.
+pub trait NonBlocking {
+    /// Sets a stream into a non-blocking mode.
+    fn set_non_blocking(&mut self) -> io::Result<()>;
+
+    /// Sets a stream into a blocking mode.
+    fn set_blocking(&mut self) -> io::Result<()>;
+
+    /// Blocks until a stream has something to read or a timeout is reached.
+    ///
+    /// It's used by a sync version of [crate::session::Session] to wait for an output
+    /// instead of trying to read over and over again.
+    /// `None` means to wait without a timeout.
+    ///
+    /// A default implementation doesn't block at all,
+    /// so a stream will be polled in a loop.
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        let _ = timeout;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl NonBlocking for ptyprocess::stream::Stream {
+    fn set_non_blocking(&mut self) -> io::Result<()> {
+        use std::os::unix::prelude::AsRawFd;
+        make_non_blocking(self.as_raw_fd(), true)
+    }
+
+    fn set_blocking(&mut self) -> io::Result<()> {
+        use std::os::unix::prelude::AsRawFd;
+        make_non_blocking(self.as_raw_fd(), false)
+    }
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        use std::os::unix::prelude::AsRawFd;
+        wait_fd_readable(self.as_raw_fd(), timeout)
+    }
+}
+
+#[cfg(unix)]
+impl NonBlocking for std::fs::File {
+    fn set_non_blocking(&mut self) -> io::Result<()> {
+        use std::os::unix::prelude::AsRawFd;
+        make_non_blocking(self.as_raw_fd(), true)
+    }
+
+    fn set_blocking(&mut self) -> io::Result<()> {
+        use std::os::unix::prelude::AsRawFd;
+        make_non_blocking(self.as_raw_fd(), false)
+    }
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        use std::os::unix::prelude::AsRawFd;
+        wait_fd_readable(self.as_raw_fd(), timeout)
+    }
+}
+
+#[cfg(unix)]
+impl NonBlocking for std::os::unix::net::UnixStream {
+    fn set_non_blocking(&mut self) -> io::Result<()> {
+        self.set_nonblocking(true)
+    }
+
+    fn set_blocking(&mut self) -> io::Result<()> {
+        self.set_nonblocking(false)
+    }
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        use std::os::unix::prelude::AsRawFd;
+        wait_fd_readable(self.as_raw_fd(), timeout)
+    }
+}
+
+impl NonBlocking for std::net::TcpStream {
+    fn set_non_blocking(&mut self) -> io::Result<()> {
+        self.set_nonblocking(true)
+    }
+
+    fn set_blocking(&mut self) -> io::Result<()> {
+        self.set_nonblocking(false)
+    }
+    #[cfg(unix)]
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        use std::os::unix::prelude::AsRawFd;
+        wait_fd_readable(self.as_raw_fd(), timeout)
+    }
+}
+
+// An in memory buffer never blocks.
+impl<T> NonBlocking for io::Cursor<T> {
+    fn set_non_blocking(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_blocking(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn make_non_blocking(fd: std::os::unix::prelude::RawFd, on: bool) -> io::Result<()> {
+    use nix::fcntl::{fcntl, FcntlArg, OFlag};
+
+    let opt = fcntl(fd, FcntlArg::F_GETFL).map_err(nix_error_to_io)?;
+    let mut opt = OFlag::from_bits_truncate(opt);
+    opt.set(OFlag::O_NONBLOCK, on);
+    fcntl(fd, FcntlArg::F_SETFL(opt)).map_err(nix_error_to_io)?;
+    Ok(())
+}
+
+/// Blocks until a descriptor is ready to be read, it's closed or a timeout is reached.
+///
+/// An interruption by a signal is not considered an error,
+/// a caller is expected to check the descriptor again anyway.
 #[cfg(unix)]
-pub type Stream = async_stream::AsyncStream;
+pub(crate) fn wait_fd_readable(
+    fd: std::os::unix::prelude::RawFd,
+    timeout: Option<Duration>,
+) -> io::Result<()> {
+    use nix::{
+        errno::Errno,
+        libc::c_int,
+        poll::{poll, PollFd, PollFlags},
+    };
+
+    let timeout = match timeout {
+        // Round up so we don't wake up right before a deadline over and over again.
+        Some(timeout) => {
+            let millis = timeout.as_millis() + u128::from(timeout.subsec_nanos() % 1_000_000 != 0);
+            millis.min(c_int::MAX as u128) as c_int
+        }
+        None => -1,
+    };
+
+    let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+    match poll(&mut fds, timeout) {
+        Ok(_) | Err(nix::Error::Sys(Errno::EINTR)) => Ok(()),
+        Err(err) => Err(nix_error_to_io(err)),
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn nix_error_to_io(err: nix::Error) -> io::Error {
+    match err.as_errno() {
+        Some(code) => io::Error::from_raw_os_error(code as _),
+        None => io::Error::new(
+            io::ErrorKind::Other,
+            "Unexpected error type conversion from nix to io",
+        ),
+    }
+}
+
+/// ProcessStream is a duplex stream of a conpty process.
+#[cfg(windows)]
+#[derive(Debug)]
+pub struct ProcessStream {
+    input: conpty::io::PipeWriter,
+    output: conpty::io::PipeReader,
+}
+
+#[cfg(windows)]
+impl ProcessStream {
+    /// The function returns a new stream from process pipes.
+    pub fn new(input: conpty::io::PipeWriter, output: conpty::io::PipeReader) -> Self {
+        Self { input, output }
+    }
+}
+
+#[cfg(windows)]
+impl Write for ProcessStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.input.flush()
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.input.write_vectored(bufs)
+    }
+}
+
+#[cfg(windows)]
+impl Read for ProcessStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.output.read(buf)
+    }
+}
+
+#[cfg(windows)]
+impl NonBlocking for ProcessStream {
+    fn set_non_blocking(&mut self) -> io::Result<()> {
+        self.output.set_non_blocking_mode()
+    }
+
+    fn set_blocking(&mut self) -> io::Result<()> {
+        self.output.set_blocking_mode()
+    }
+}
 
 #[cfg(not(feature = "async"))]
 pub(super) mod sync_stream {
-    use super::ReaderWithBuffer;
+    use super::{NonBlocking, ReaderWithBuffer, REPLY_RETRY_INTERVAL};
+    use crate::{responder::Responder, screen::Screen};
     use std::{
-        fs::File,
         io::{self, BufRead, BufReader, Read, Write},
+        time::{Duration, Instant},
     };
 
-    #[cfg(unix)]
-    use std::os::unix::prelude::{AsRawFd, RawFd};
-
-    #[cfg(unix)]
-    type StreamReader = ptyprocess::stream::Stream;
-    #[cfg(unix)]
-    type StreamWriter = ptyprocess::stream::Stream;
-
-    #[cfg(windows)]
-    type StreamReader = conpty::io::PipeReader;
-    #[cfg(windows)]
-    type StreamWriter = conpty::io::PipeWriter;
-
     /// Stream represent a IO stream.
     #[derive(Debug)]
-    pub struct Stream {
-        input: StreamWriter,
-        output: BufReader<ReaderWithBuffer<StreamReader>>,
+    pub struct Stream<S> {
+        inner: BufReader<ReaderWithBuffer<S>>,
+        eof: bool,
+        last_read: Instant,
     }
 
-    #[cfg(windows)]
-    impl Stream {
-        /// The function returns a new Stream from a file.
-        pub fn new(input: conpty::io::PipeWriter, output: conpty::io::PipeReader) -> Self {
+    impl<S: Read> Stream<S> {
+        /// The function returns a new Stream from a reader and writer.
+        pub fn new(stream: S) -> Self {
             Self {
-                input,
-                output: BufReader::new(ReaderWithBuffer::new(output)),
+                inner: BufReader::new(ReaderWithBuffer::new(stream)),
+                eof: false,
+                last_read: Instant::now(),
             }
         }
+    }
 
-        fn set_non_blocking_output(&mut self) -> io::Result<()> {
-            self.output.get_mut().get_mut().set_non_blocking_mode()
+    impl<S> Stream<S> {
+        /// Returns a reference to the underlying stream.
+        pub fn get_ref(&self) -> &S {
+            &self.inner.get_ref().inner
         }
 
-        fn set_blocking_output(&mut self) -> io::Result<()> {
-            self.output.get_mut().get_mut().set_blocking_mode()
+        /// Returns a mutable reference to the underlying stream.
+        pub fn inner_mut(&mut self) -> &mut S {
+            &mut self.inner.get_mut().inner
         }
-    }
 
-    #[cfg(unix)]
-    impl Stream {
-        /// The function returns a new Stream from a file.
-        pub fn new(file: File) -> Self {
-            let copy_file = file
-                .try_clone()
-                .expect("It's ok to clone fd as it will be just DUPed");
-            let reader = BufReader::new(ReaderWithBuffer::new(ptyprocess::stream::Stream::new(
-                copy_file,
-            )));
-            let file = ptyprocess::stream::Stream::new(file);
+        /// Returns true if the last read from the underlying stream reached EOF.
+        ///
+        /// The flag is kept until a following read returns some data,
+        /// so it can't be lost while the buffered data is being checked.
+        pub fn is_eof(&self) -> bool {
+            self.eof
+        }
 
-            Self {
-                input: file,
-                output: reader,
-            }
+        /// Returns a time when some data was last read from the underlying stream.
+        ///
+        /// It's a time of a stream creation if nothing was read yet.
+        pub fn last_read(&self) -> Instant {
+            self.last_read
+        }
+
+        pub fn get_available(&self) -> &[u8] {
+            &self.inner.get_ref().buffer
         }
 
-        fn set_non_blocking_output(&mut self) -> io::Result<()> {
-            let fd = self.input.as_raw_fd();
-            _make_non_blocking(fd, true)
+        /// Returns a screen which is fed by the read data, if it's set.
+        pub fn screen(&self) -> Option<&Screen> {
+            self.inner.get_ref().screen.as_ref()
         }
 
-        fn set_blocking_output(&mut self) -> io::Result<()> {
-            let fd = self.input.as_raw_fd();
-            _make_non_blocking(fd, false)
+        pub fn screen_mut(&mut self) -> Option<&mut Screen> {
+            self.inner.get_mut().screen.as_mut()
+        }
+
+        pub fn set_screen(&mut self, screen: Option<Screen>) {
+            self.inner.get_mut().screen = screen;
+        }
+
+        pub(crate) fn set_responder(&mut self, responder: Option<Responder>) {
+            self.inner.get_mut().responder = responder;
+        }
+
+        pub fn consume_from_buffer(&mut self, n: usize) {
+            self.inner.get_mut().buffer.drain(..n);
+        }
+
+        pub fn keep_in_buffer(&mut self, v: &[u8]) {
+            self.inner.get_mut().keep_in_buffer(v);
+        }
+
+        /// Keeps the data which was just read from the underlying stream.
+        ///
+        /// The data is kept even if a reply to a query can't be written.
+        fn keep_read(&mut self, v: &[u8]) {
+            let reader = self.inner.get_mut();
+            reader.keep_in_buffer(v);
+            reader.feed(v);
+        }
+
+        /// Writes replies to queries which were not written while the data was read.
+        ///
+        /// It returns false if some of them are still left.
+        pub(crate) fn flush_replies(&mut self) -> bool {
+            self.inner.get_mut().flush_replies()
         }
     }
 
-    impl Stream {
+    impl<S: Read + NonBlocking> Stream<S> {
         /// Try to read in a non-blocking mode.
         ///
         /// It raises io::ErrorKind::WouldBlock if there's nothing to read.
         pub fn try_read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-            self.set_non_blocking_output()?;
+            self.flush_replies();
+            self.inner_mut().set_non_blocking()?;
 
-            let result = match self.read(buf) {
-                Ok(n) => Ok(n),
-                Err(err) => Err(err),
-            };
+            let result = self.read(buf);
 
             // As file is DUPed changes in one descriptor affects all ones
             // so we need to make blocking file after we finished.
-            self.set_blocking_output()?;
+            self.inner_mut().set_blocking()?;
 
             result
         }
 
         // non-buffered && non-blocking read
         fn try_read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-            self.set_non_blocking_output()?;
+            self.inner_mut().set_non_blocking()?;
 
-            let result = match self.output.get_mut().inner.read(buf) {
-                Ok(n) => Ok(n),
-                Err(err) => Err(err),
-            };
+            let result = self.inner_mut().read(buf);
 
             // As file is DUPed changes in one descriptor affects all ones
             // so we need to make blocking file after we finished.
-            self.set_blocking_output()?;
+            self.inner_mut().set_blocking()?;
 
             result
         }
@@ -134,13 +370,19 @@ pub(super) mod sync_stream {
 
         pub fn read_available(&mut self) -> std::io::Result<bool> {
             self.flush_in_buffer();
+            self.flush_replies();
 
             let mut buf = [0; 248];
             loop {
                 match self.try_read_inner(&mut buf) {
-                    Ok(0) => break Ok(true),
+                    Ok(0) => {
+                        self.eof = true;
+                        break Ok(true);
+                    }
                     Ok(n) => {
-                        self.keep_in_buffer(&buf[..n]);
+                        self.eof = false;
+                        self.last_read = Instant::now();
+                        self.keep_read(&buf[..n]);
                     }
                     Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(false),
                     Err(err) => break Err(err),
@@ -150,11 +392,17 @@ pub(super) mod sync_stream {
 
         pub fn read_available_once(&mut self, buf: &mut [u8]) -> std::io::Result<Option<usize>> {
             self.flush_in_buffer();
+            self.flush_replies();
 
             match self.try_read_inner(buf) {
-                Ok(0) => Ok(Some(0)),
+                Ok(0) => {
+                    self.eof = true;
+                    Ok(Some(0))
+                }
                 Ok(n) => {
-                    self.keep_in_buffer(&buf[..n]);
+                    self.eof = false;
+                    self.last_read = Instant::now();
+                    self.keep_read(&buf[..n]);
                     Ok(Some(n))
                 }
                 Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
@@ -162,16 +410,19 @@ pub(super) mod sync_stream {
             }
         }
 
-        pub fn get_available(&mut self) -> &[u8] {
-            &self.output.get_mut().buffer
-        }
-
-        pub fn consume_from_buffer(&mut self, n: usize) {
-            self.output.get_mut().buffer.drain(..n);
-        }
+        /// Blocks until the underlying stream has something to read or a timeout is reached.
+        ///
+        /// It returns earlier if there are replies to queries left, so they can be retried.
+        ///
+        /// See [NonBlocking::wait_readable].
+        pub fn wait_readable(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+            let timeout = if self.flush_replies() {
+                timeout
+            } else {
+                Some(timeout.map_or(REPLY_RETRY_INTERVAL, |t| t.min(REPLY_RETRY_INTERVAL)))
+            };
 
-        pub fn keep_in_buffer(&mut self, v: &[u8]) {
-            self.output.get_mut().keep_in_buffer(v);
+            self.inner_mut().wait_readable(timeout)
         }
 
         pub fn flush_in_buffer(&mut self) {
@@ -179,61 +430,39 @@ pub(super) mod sync_stream {
             // in read operations and the data which was via `keep_in_buffer` function.
             //
             // To eliminate it we move BufReader buffer to our buffer.
-            let b = self.output.buffer().to_vec();
-            self.output.consume(b.len());
+            let b = self.inner.buffer().to_vec();
+            self.inner.consume(b.len());
             self.keep_in_buffer(&b);
         }
     }
 
-    impl Write for Stream {
+    impl<S: Write> Write for Stream<S> {
         fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-            self.input.write(buf)
+            self.inner_mut().write(buf)
         }
 
         fn flush(&mut self) -> io::Result<()> {
-            self.input.flush()
+            self.inner_mut().flush()
         }
 
         fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
-            self.input.write_vectored(bufs)
+            self.inner_mut().write_vectored(bufs)
         }
     }
 
-    impl Read for Stream {
+    impl<S: Read> Read for Stream<S> {
         fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-            self.output.read(buf)
+            self.inner.read(buf)
         }
     }
 
-    impl BufRead for Stream {
+    impl<S: Read> BufRead for Stream<S> {
         fn fill_buf(&mut self) -> io::Result<&[u8]> {
-            self.output.fill_buf()
+            self.inner.fill_buf()
         }
 
         fn consume(&mut self, amt: usize) {
-            self.output.consume(amt)
-        }
-    }
-
-    #[cfg(unix)]
-    fn _make_non_blocking(fd: RawFd, blocking: bool) -> io::Result<()> {
-        use nix::fcntl::{fcntl, FcntlArg, OFlag};
-
-        let opt = fcntl(fd, FcntlArg::F_GETFL).map_err(nix_error_to_io)?;
-        let mut opt = OFlag::from_bits_truncate(opt);
-        opt.set(OFlag::O_NONBLOCK, blocking);
-        fcntl(fd, FcntlArg::F_SETFL(opt)).map_err(nix_error_to_io)?;
-        Ok(())
-    }
-
-    #[cfg(unix)]
-    fn nix_error_to_io(err: nix::Error) -> io::Error {
-        match err.as_errno() {
-            Some(code) => io::Error::from_raw_os_error(code as _),
-            None => io::Error::new(
-                io::ErrorKind::Other,
-                "Unexpected error type conversion from nix to io",
-            ),
+            self.inner.consume(amt)
         }
     }
 }
@@ -241,35 +470,30 @@ pub(super) mod sync_stream {
 #[cfg(feature = "async")]
 pub(super) mod async_stream {
     use super::ReaderWithBuffer;
-    use async_io::Async;
+    use crate::{responder::Responder, screen::Screen};
     use futures_lite::{io::BufReader, AsyncBufRead, AsyncRead, AsyncWrite};
-    use ptyprocess::stream::Stream;
     use std::{
-        fs::File,
         io,
         pin::Pin,
         task::{Context, Poll},
+        time::Instant,
     };
 
     /// Stream represent a IO stream.
     #[derive(Debug)]
-    pub struct AsyncStream {
-        inner: Async<Stream>,
-        reader: BufReader<ReaderWithBuffer<Async<Stream>>>,
+    pub struct AsyncStream<S> {
+        inner: BufReader<ReaderWithBuffer<S>>,
+        eof: bool,
+        last_read: Instant,
     }
 
-    impl AsyncStream {
-        /// The function returns a new Stream from a file.
-        pub fn new(file: File) -> Self {
-            let cloned = file.try_clone().unwrap();
-            let file = Async::new(Stream::new(file)).unwrap();
-            let reader = BufReader::new(ReaderWithBuffer::new(
-                Async::new(Stream::new(cloned)).unwrap(),
-            ));
-
+    impl<S: AsyncRead + Unpin> AsyncStream<S> {
+        /// The function returns a new Stream from a reader and writer.
+        pub fn new(stream: S) -> Self {
             Self {
-                inner: file,
-                reader,
+                inner: BufReader::new(ReaderWithBuffer::new(stream)),
+                eof: false,
+                last_read: Instant::now(),
             }
         }
 
@@ -278,7 +502,8 @@ pub(super) mod async_stream {
         /// It raises io::ErrorKind::WouldBlock if there's nothing to read.
         pub async fn try_read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
             use futures_lite::AsyncReadExt;
-            match futures_lite::future::poll_once(self.reader.read(buf)).await {
+            self.flush_replies();
+            match futures_lite::future::poll_once(self.inner.read(buf)).await {
                 Some(result) => result,
                 None => Err(io::Error::new(io::ErrorKind::WouldBlock, "")),
             }
@@ -296,7 +521,7 @@ pub(super) mod async_stream {
         // non-buffered && non-blocking read
         async fn try_read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
             use futures_lite::AsyncReadExt;
-            match futures_lite::future::poll_once(self.reader.get_mut().inner.read(buf)).await {
+            match futures_lite::future::poll_once(self.inner.get_mut().inner.read(buf)).await {
                 Some(result) => result,
                 None => Err(io::Error::new(io::ErrorKind::WouldBlock, "")),
             }
@@ -304,13 +529,19 @@ pub(super) mod async_stream {
 
         pub async fn read_available(&mut self) -> std::io::Result<bool> {
             self.flush_in_buffer();
+            self.flush_replies();
 
             let mut buf = [0; 248];
             loop {
                 match self.try_read_inner(&mut buf).await {
-                    Ok(0) => break Ok(true),
+                    Ok(0) => {
+                        self.eof = true;
+                        break Ok(true);
+                    }
                     Ok(n) => {
-                        self.keep_in_buffer(&buf[..n]);
+                        self.eof = false;
+                        self.last_read = Instant::now();
+                        self.keep_read(&buf[..n]);
                     }
                     Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(false),
                     Err(err) => break Err(err),
@@ -323,11 +554,17 @@ pub(super) mod async_stream {
             buf: &mut [u8],
         ) -> std::io::Result<Option<usize>> {
             self.flush_in_buffer();
+            self.flush_replies();
 
             match self.try_read_inner(buf).await {
-                Ok(0) => Ok(Some(0)),
+                Ok(0) => {
+                    self.eof = true;
+                    Ok(Some(0))
+                }
                 Ok(n) => {
-                    self.keep_in_buffer(&buf[..n]);
+                    self.eof = false;
+                    self.last_read = Instant::now();
+                    self.keep_read(&buf[..n]);
                     Ok(Some(n))
                 }
                 Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
@@ -335,16 +572,25 @@ pub(super) mod async_stream {
             }
         }
 
-        pub fn get_available(&mut self) -> &[u8] {
-            &self.reader.get_mut().buffer
-        }
+        /// Waits until there's something to read and keeps the read data in the buffer.
+        ///
+        /// It returns the number of read bytes, 0 means EOF.
+        ///
+        /// The data is put in the buffer in the same poll it's read,
+        /// so it's safe to drop the future at any point.
+        pub async fn read_available_wait(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            use futures_lite::AsyncReadExt;
 
-        pub fn consume_from_buffer(&mut self, n: usize) {
-            self.reader.get_mut().buffer.drain(..n);
-        }
+            self.flush_in_buffer();
 
-        pub fn keep_in_buffer(&mut self, v: &[u8]) {
-            self.reader.get_mut().keep_in_buffer(v);
+            let n = self.inner.get_mut().inner.read(buf).await?;
+            self.eof = n == 0;
+            if n > 0 {
+                self.last_read = Instant::now();
+            }
+            self.keep_read(&buf[..n]);
+
+            Ok(n)
         }
 
         pub fn flush_in_buffer(&mut self) {
@@ -353,27 +599,99 @@ pub(super) mod async_stream {
             //
             // To eliminate it we move BufReader buffer to our buffer.
             use futures_lite::AsyncBufReadExt;
-            let b = self.reader.buffer().to_vec();
-            self.reader.consume(b.len());
+            let b = self.inner.buffer().to_vec();
+            self.inner.consume(b.len());
             self.keep_in_buffer(&b);
         }
     }
 
-    impl AsyncWrite for AsyncStream {
+    impl<S> AsyncStream<S> {
+        /// Returns a reference to the underlying stream.
+        pub fn get_ref(&self) -> &S {
+            &self.inner.get_ref().inner
+        }
+
+        /// Returns a mutable reference to the underlying stream.
+        pub fn inner_mut(&mut self) -> &mut S {
+            &mut self.inner.get_mut().inner
+        }
+
+        /// Returns true if the last read from the underlying stream reached EOF.
+        ///
+        /// The flag is kept until a following read returns some data,
+        /// so it can't be lost while the buffered data is being checked.
+        pub fn is_eof(&self) -> bool {
+            self.eof
+        }
+
+        /// Returns a time when some data was last read from the underlying stream.
+        ///
+        /// It's a time of a stream creation if nothing was read yet.
+        pub fn last_read(&self) -> Instant {
+            self.last_read
+        }
+
+        pub fn get_available(&self) -> &[u8] {
+            &self.inner.get_ref().buffer
+        }
+
+        /// Returns a screen which is fed by the read data, if it's set.
+        pub fn screen(&self) -> Option<&Screen> {
+            self.inner.get_ref().screen.as_ref()
+        }
+
+        pub fn screen_mut(&mut self) -> Option<&mut Screen> {
+            self.inner.get_mut().screen.as_mut()
+        }
+
+        pub fn set_screen(&mut self, screen: Option<Screen>) {
+            self.inner.get_mut().screen = screen;
+        }
+
+        pub(crate) fn set_responder(&mut self, responder: Option<Responder>) {
+            self.inner.get_mut().responder = responder;
+        }
+
+        pub fn consume_from_buffer(&mut self, n: usize) {
+            self.inner.get_mut().buffer.drain(..n);
+        }
+
+        pub fn keep_in_buffer(&mut self, v: &[u8]) {
+            self.inner.get_mut().keep_in_buffer(v);
+        }
+
+        /// Keeps the data which was just read from the underlying stream.
+        ///
+        /// The data is kept even if a reply to a query can't be written.
+        fn keep_read(&mut self, v: &[u8]) {
+            let reader = self.inner.get_mut();
+            reader.keep_in_buffer(v);
+            reader.feed(v);
+        }
+
+        /// Writes replies to queries which were not written while the data was read.
+        ///
+        /// It returns false if some of them are still left.
+        pub(crate) fn flush_replies(&mut self) -> bool {
+            self.inner.get_mut().flush_replies()
+        }
+    }
+
+    impl<S: AsyncWrite + Unpin> AsyncWrite for AsyncStream<S> {
         fn poll_write(
             mut self: Pin<&mut Self>,
             cx: &mut Context<'_>,
             buf: &[u8],
         ) -> Poll<io::Result<usize>> {
-            <Async<Stream> as AsyncWrite>::poll_write(Pin::new(&mut self.inner), cx, buf)
+            Pin::new(self.inner_mut()).poll_write(cx, buf)
         }
 
         fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-            <Async<Stream> as AsyncWrite>::poll_flush(Pin::new(&mut self.inner), cx)
+            Pin::new(self.inner_mut()).poll_flush(cx)
         }
 
         fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-            <Async<Stream> as AsyncWrite>::poll_close(Pin::new(&mut self.inner), cx)
+            Pin::new(self.inner_mut()).poll_close(cx)
         }
 
         fn poll_write_vectored(
@@ -381,21 +699,21 @@ pub(super) mod async_stream {
             cx: &mut Context<'_>,
             bufs: &[io::IoSlice<'_>],
         ) -> Poll<io::Result<usize>> {
-            <Async<Stream> as AsyncWrite>::poll_write_vectored(Pin::new(&mut self.inner), cx, bufs)
+            Pin::new(self.inner_mut()).poll_write_vectored(cx, bufs)
         }
     }
 
-    impl AsyncRead for AsyncStream {
+    impl<S: AsyncRead + Unpin> AsyncRead for AsyncStream<S> {
         fn poll_read(
             mut self: Pin<&mut Self>,
             cx: &mut Context<'_>,
             buf: &mut [u8],
         ) -> Poll<io::Result<usize>> {
-            Pin::new(&mut self.reader).poll_read(cx, buf)
+            Pin::new(&mut self.inner).poll_read(cx, buf)
         }
     }
 
-    impl AsyncBufRead for AsyncStream {
+    impl<S: AsyncRead + Unpin> AsyncBufRead for AsyncStream<S> {
         fn poll_fill_buf<'a>(
             self: Pin<&'a mut Self>,
             cx: &mut Context<'_>,
@@ -403,11 +721,11 @@ pub(super) mod async_stream {
             // pin_project is used only for this function.
             // the solution was found in the original implementation of BufReader.
             let this = self.get_mut();
-            Pin::new(&mut this.reader).poll_fill_buf(cx)
+            Pin::new(&mut this.inner).poll_fill_buf(cx)
         }
 
         fn consume(mut self: Pin<&mut Self>, amt: usize) {
-            Pin::new(&mut self.reader).consume(amt)
+            Pin::new(&mut self.inner).consume(amt)
         }
     }
 }
@@ -416,25 +734,69 @@ pub(super) mod async_stream {
 struct ReaderWithBuffer<R> {
     inner: R,
     buffer: Vec<u8>,
+    screen: Option<Screen>,
+    responder: Option<Responder>,
 }
 
 impl<R> ReaderWithBuffer<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            inner: reader,
+            buffer: Vec::new(),
+            screen: None,
+            responder: None,
+        }
+    }
+
     fn keep_in_buffer(&mut self, v: &[u8]) {
         self.buffer.extend(v);
     }
 
-    #[allow(dead_code)]
-    fn get_mut(&mut self) -> &mut R {
-        &mut self.inner
+    /// Passes the data read from an inner reader to a screen and answers queries found in it.
+    ///
+    /// The buffered data must not be passed as it was already seen.
+    fn feed(&mut self, mut v: &[u8]) {
+        let responder = match &mut self.responder {
+            Some(responder) => responder,
+            None => {
+                self.feed_screen(v);
+                return;
+            }
+        };
+
+        // A screen is fed up to a query so a reply reflects its state at that moment.
+        while !v.is_empty() {
+            let (n, query) = responder.scan(v);
+            if let Some(screen) = &mut self.screen {
+                screen.process(&v[..n]);
+            }
+
+            if let Some(query) = query {
+                responder.reply(query, self.screen.as_ref());
+            }
+
+            v = &v[n..];
+        }
+
+        // An output is kept even if replies can't be written,
+        // e.g. a process has already exited.
+        let _ = responder.flush();
     }
-}
 
-#[cfg(not(feature = "async"))]
-impl<R: std::io::Read> ReaderWithBuffer<R> {
-    fn new(reader: R) -> Self {
-        Self {
-            inner: reader,
-            buffer: Vec::new(),
+    fn flush_replies(&mut self) -> bool {
+        match &mut self.responder {
+            Some(responder) => {
+                // Errors are ignored as in feed.
+                let _ = responder.flush();
+                !responder.has_pending()
+            }
+            None => true,
+        }
+    }
+
+    fn feed_screen(&mut self, v: &[u8]) {
+        if let Some(screen) = &mut self.screen {
+            screen.process(v);
         }
     }
 }
@@ -443,7 +805,9 @@ impl<R: std::io::Read> ReaderWithBuffer<R> {
 impl<R: std::io::Read> std::io::Read for ReaderWithBuffer<R> {
     fn read(&mut self, mut buf: &mut [u8]) -> std::io::Result<usize> {
         if self.buffer.is_empty() {
-            self.inner.read(buf)
+            let n = self.inner.read(buf)?;
+            self.feed(&buf[..n]);
+            Ok(n)
         } else {
             use std::io::Write;
             let n = buf.write(&self.buffer)?;
@@ -453,16 +817,6 @@ impl<R: std::io::Read> std::io::Read for ReaderWithBuffer<R> {
     }
 }
 
-#[cfg(feature = "async")]
-impl<R: futures_lite::AsyncRead> ReaderWithBuffer<R> {
-    fn new(reader: R) -> Self {
-        Self {
-            inner: reader,
-            buffer: Vec::new(),
-        }
-    }
-}
-
 #[cfg(feature = "async")]
 impl<R: futures_lite::AsyncRead + std::marker::Unpin> futures_lite::AsyncRead
     for ReaderWithBuffer<R>
@@ -474,18 +828,21 @@ impl<R: futures_lite::AsyncRead + std::marker::Unpin> futures_lite::AsyncRead
     ) -> std::task::Poll<std::io::Result<usize>> {
         // see sync version
         if self.buffer.is_empty() {
-            std::pin::Pin::new(&mut self.inner).poll_read(cx, buf)
+            let result = std::pin::Pin::new(&mut self.inner).poll_read(cx, buf);
+            if let std::task::Poll::Ready(Ok(n)) = result {
+                self.feed(&buf[..n]);
+            }
+
+            result
         } else {
             use std::io::Write;
             let n = buf.write(&self.buffer)?;
             self.buffer.drain(..n);
 
-            let poll = std::pin::Pin::new(&mut self.inner).poll_read(cx, &mut buf[n..]);
-            match poll {
-                std::task::Poll::Ready(Ok(n1)) => std::task::Poll::Ready(Ok(n + n1)),
-                std::task::Poll::Ready(Err(..)) => std::task::Poll::Ready(Ok(n)),
-                std::task::Poll::Pending => std::task::Poll::Pending,
-            }
+            // The buffered data is returned right away.
+            // Polling an inner reader here could return Pending
+            // and the bytes which were already drained would be lost.
+            std::task::Poll::Ready(Ok(n))
         }
     }
 }
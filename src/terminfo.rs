@@ -0,0 +1,255 @@
+//! Module contains a reader of compiled terminfo entries.
+//!
+//! Only string capabilities describing keys are needed, so the rest is skipped.
+
+use crate::error::Error;
+use std::{
+    convert::TryFrom,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// A magic number of the legacy format with 16-bit numbers.
+const MAGIC_LEGACY: u16 = 0o432;
+
+/// A magic number of the extended format with 32-bit numbers.
+const MAGIC_32BIT: u16 = 0o1036;
+
+/// Indexes of string capabilities in the order ncurses `Caps` file defines them.
+const STRINGS: &[(&str, usize)] = &[
+    ("kbs", 55),
+    ("kdch1", 59),
+    ("kcud1", 61),
+    ("kf0", 65),
+    ("kf1", 66),
+    ("kf10", 67),
+    ("kf2", 68),
+    ("kf3", 69),
+    ("kf4", 70),
+    ("kf5", 71),
+    ("kf6", 72),
+    ("kf7", 73),
+    ("kf8", 74),
+    ("kf9", 75),
+    ("khome", 76),
+    ("kich1", 77),
+    ("kcub1", 79),
+    ("knp", 81),
+    ("kpp", 82),
+    ("kcuf1", 83),
+    ("kcuu1", 87),
+    ("kcbt", 148),
+    ("kend", 164),
+];
+
+/// An index of `kf11`, function keys up to `kf63` follow it.
+const KF11: usize = 216;
+
+/// Directories which are searched after the ones set by environment variables.
+const SYSTEM_DIRS: &[&str] = &[
+    "/etc/terminfo",
+    "/lib/terminfo",
+    "/usr/share/terminfo",
+    "/usr/lib/terminfo",
+    "/usr/local/share/terminfo",
+];
+
+/// Terminfo is a parsed terminfo entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Terminfo {
+    strings: Vec<Option<Vec<u8>>>,
+}
+
+impl Terminfo {
+    /// Finds and parses an entry of a terminal.
+    pub(crate) fn load(term: &str) -> Result<Self, Error> {
+        let path = find(term)
+            .ok_or_else(|| Error::Other(format!("Terminfo entry {:?} is not found", term)))?;
+        let data = fs::read(path)?;
+        Self::parse(&data)
+    }
+
+    /// Parses a compiled entry.
+    pub(crate) fn parse(data: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::Other("Invalid terminfo entry".to_owned());
+
+        let header = |i: usize| read_i16(data, i * 2).ok_or_else(invalid);
+        let number_size = match header(0)? as u16 {
+            MAGIC_LEGACY => 2,
+            MAGIC_32BIT => 4,
+            _ => return Err(invalid()),
+        };
+        let [names_size, bools_count, numbers_count, strings_count, table_size] =
+            [header(1)?, header(2)?, header(3)?, header(4)?, header(5)?]
+                .map(|n| usize::try_from(n).unwrap_or(0));
+
+        let mut offset = 12 + names_size + bools_count;
+        // Numbers are aligned on an even byte.
+        offset += offset % 2;
+        offset += numbers_count * number_size;
+
+        let table_start = offset + strings_count * 2;
+        let table = data
+            .get(table_start..table_start + table_size)
+            .ok_or_else(invalid)?;
+
+        let strings = (0..strings_count)
+            .map(|i| {
+                // Negative offsets mark absent and cancelled capabilities.
+                let start = usize::try_from(read_i16(data, offset + i * 2)?).ok()?;
+                let len = table.get(start..)?.iter().position(|&b| b == 0)?;
+                Some(table[start..start + len].to_vec())
+            })
+            .collect();
+
+        Ok(Self { strings })
+    }
+
+    /// Returns a string capability by its name.
+    pub(crate) fn string(&self, name: &str) -> Option<&[u8]> {
+        let index = string_index(name)?;
+        self.strings.get(index)?.as_deref()
+    }
+}
+
+fn string_index(name: &str) -> Option<usize> {
+    if let Some(&(_, index)) = STRINGS.iter().find(|(n, _)| *n == name) {
+        return Some(index);
+    }
+
+    let n: usize = name.strip_prefix("kf")?.parse().ok()?;
+    match n {
+        11..=63 => Some(KF11 + n - 11),
+        _ => None,
+    }
+}
+
+fn read_i16(data: &[u8], i: usize) -> Option<i16> {
+    let bytes = data.get(i..i + 2)?;
+    Some(i16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Finds a file of an entry the way ncurses does it.
+fn find(term: &str) -> Option<PathBuf> {
+    let first = term.chars().next()?;
+    if term.contains('/') || term.starts_with('.') {
+        return None;
+    }
+
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(Path::new(&home).join(".terminfo"));
+    }
+
+    if let Some(list) = env::var_os("TERMINFO_DIRS") {
+        for dir in env::split_paths(&list) {
+            // An empty entry stands for system directories.
+            if dir.as_os_str().is_empty() {
+                dirs.extend(SYSTEM_DIRS.iter().map(PathBuf::from));
+            } else {
+                dirs.push(dir);
+            }
+        }
+    }
+
+    dirs.extend(SYSTEM_DIRS.iter().map(PathBuf::from));
+
+    // Some systems use a hex code of a first letter as a directory name.
+    let subdirs = [first.to_string(), format!("{:02x}", first as u32)];
+    dirs.iter()
+        .flat_map(|dir| {
+            subdirs
+                .iter()
+                .map(move |subdir| dir.join(subdir).join(term))
+        })
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compiles an entry with given string capabilities.
+    fn compile(magic: u16, strings: &[(usize, &[u8])]) -> Vec<u8> {
+        let names = b"test|a test terminal\0";
+        let bools = [1u8; 3];
+        let numbers_count = 2;
+        let number_size = if magic == MAGIC_LEGACY { 2 } else { 4 };
+        let strings_count = strings.iter().map(|(i, _)| i + 1).max().unwrap_or(0);
+
+        let mut offsets = vec![-1i16; strings_count];
+        let mut table = Vec::new();
+        for (i, s) in strings {
+            offsets[*i] = table.len() as i16;
+            table.extend_from_slice(s);
+            table.push(0);
+        }
+
+        let mut data = Vec::new();
+        for n in [
+            magic as i16,
+            names.len() as i16,
+            bools.len() as i16,
+            numbers_count as i16,
+            strings_count as i16,
+            table.len() as i16,
+        ] {
+            data.extend(n.to_le_bytes());
+        }
+        data.extend(names);
+        data.extend(bools);
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        data.extend(vec![0xff; numbers_count * number_size]);
+        for offset in offsets {
+            data.extend(offset.to_le_bytes());
+        }
+        data.extend(table);
+        data
+    }
+
+    #[test]
+    fn test_parse() {
+        for magic in [MAGIC_LEGACY, MAGIC_32BIT] {
+            let data = compile(
+                magic,
+                &[
+                    (55, b"\x08"),
+                    (87, b"\x1bOA"),
+                    (KF11 + 1, b"\x1b[24~"),
+                    (164, b"\x1bOF"),
+                ],
+            );
+            let terminfo = Terminfo::parse(&data).unwrap();
+            assert_eq!(terminfo.string("kbs"), Some(&b"\x08"[..]));
+            assert_eq!(terminfo.string("kcuu1"), Some(&b"\x1bOA"[..]));
+            assert_eq!(terminfo.string("kf12"), Some(&b"\x1b[24~"[..]));
+            assert_eq!(terminfo.string("kend"), Some(&b"\x1bOF"[..]));
+            assert_eq!(terminfo.string("kcud1"), None);
+            assert_eq!(terminfo.string("kf64"), None);
+            assert_eq!(terminfo.string("unknown"), None);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Terminfo::parse(b"").is_err());
+        assert!(Terminfo::parse(&[0; 12]).is_err());
+
+        let mut data = compile(MAGIC_LEGACY, &[(55, b"\x7f")]);
+        data.truncate(data.len() - 1);
+        assert!(Terminfo::parse(&data).is_err());
+    }
+
+    #[test]
+    fn test_find_rejects_paths() {
+        assert_eq!(find(""), None);
+        assert_eq!(find("../xterm"), None);
+        assert_eq!(find("x/../../xterm"), None);
+    }
+}
@@ -0,0 +1,194 @@
+#![cfg(unix)]
+
+use expectrl::{Eof, SessionBuilder};
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn builder_window_size() {
+    let mut session = SessionBuilder::new("stty")
+        .arg("size")
+        .window_size(120, 40)
+        .spawn()
+        .unwrap();
+
+    assert_eq!(session.get_window_size().unwrap(), (120, 40));
+
+    let m = session.expect(Eof).unwrap();
+    assert_eq!(m.first(), b"40 120\r\n");
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn builder_window_size() {
+    futures_lite::future::block_on(async {
+        let mut session = SessionBuilder::new("stty")
+            .arg("size")
+            .window_size(120, 40)
+            .spawn()
+            .unwrap();
+
+        assert_eq!(session.get_window_size().unwrap(), (120, 40));
+
+        let m = session.expect(Eof).await.unwrap();
+        assert_eq!(m.first(), b"40 120\r\n");
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn builder_window_size_is_set_before_start() {
+    // A parent resets a size after a process is spawned,
+    // so it's repeated to make sure a program never sees a default one.
+    for i in 0..50 {
+        let mut session = SessionBuilder::new("stty")
+            .arg("size")
+            .window_size(120, 40)
+            .separate_stderr(i % 2 == 0)
+            .spawn()
+            .unwrap();
+
+        let m = session.expect(Eof).unwrap();
+        assert_eq!(m.first(), b"40 120\r\n");
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn builder_window_size_is_set_before_start() {
+    futures_lite::future::block_on(async {
+        for i in 0..50 {
+            let mut session = SessionBuilder::new("stty")
+                .arg("size")
+                .window_size(120, 40)
+                .separate_stderr(i % 2 == 0)
+                .spawn()
+                .unwrap();
+
+            let m = session.expect(Eof).await.unwrap();
+            assert_eq!(m.first(), b"40 120\r\n");
+        }
+    })
+}
+
+#[test]
+fn builder_window_size_with_missing_program() {
+    let result = SessionBuilder::new("expectrl-missing-program")
+        .window_size(120, 40)
+        .spawn();
+
+    assert!(result.is_err());
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn builder_env_and_current_dir() {
+    let mut session = SessionBuilder::new("/bin/sh")
+        .args(["-c", "echo \"$TERM $HOME $EXPECTRL_VAR\"; pwd"])
+        .env_clear()
+        .term("dumb")
+        .env("EXPECTRL_VAR", "value")
+        .current_dir("/")
+        .spawn()
+        .unwrap();
+
+    let m = session.expect(Eof).unwrap();
+    assert_eq!(m.first(), b"dumb  value\r\n/\r\n");
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn builder_env_and_current_dir() {
+    futures_lite::future::block_on(async {
+        let mut session = SessionBuilder::new("/bin/sh")
+            .args(["-c", "echo \"$TERM $HOME $EXPECTRL_VAR\"; pwd"])
+            .env_clear()
+            .term("dumb")
+            .env("EXPECTRL_VAR", "value")
+            .current_dir("/")
+            .spawn()
+            .unwrap();
+
+        let m = session.expect(Eof).await.unwrap();
+        assert_eq!(m.first(), b"dumb  value\r\n/\r\n");
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn builder_termios() {
+    let mut session = SessionBuilder::new("stty")
+        .arg("-a")
+        .echo(true)
+        .canonical(false)
+        .onlcr(false)
+        .spawn()
+        .unwrap();
+
+    let m = session.expect(Eof).unwrap();
+    let settings = String::from_utf8_lossy(m.first()).to_string();
+    let flags = settings.split_whitespace().collect::<Vec<_>>();
+
+    assert!(flags.contains(&"echo"), "{:?}", settings);
+    assert!(flags.contains(&"-icanon"), "{:?}", settings);
+    assert!(flags.contains(&"-onlcr"), "{:?}", settings);
+    // -onlcr makes an output use only \n
+    assert!(!settings.contains('\r'), "{:?}", settings);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn builder_termios() {
+    futures_lite::future::block_on(async {
+        let mut session = SessionBuilder::new("stty")
+            .arg("-a")
+            .echo(true)
+            .canonical(false)
+            .onlcr(false)
+            .spawn()
+            .unwrap();
+
+        let m = session.expect(Eof).await.unwrap();
+        let settings = String::from_utf8_lossy(m.first()).to_string();
+        let flags = settings.split_whitespace().collect::<Vec<_>>();
+
+        assert!(flags.contains(&"echo"), "{:?}", settings);
+        assert!(flags.contains(&"-icanon"), "{:?}", settings);
+        assert!(flags.contains(&"-onlcr"), "{:?}", settings);
+        // -onlcr makes an output use only \n
+        assert!(!settings.contains('\r'), "{:?}", settings);
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn builder_repl() {
+    let mut sh = SessionBuilder::new("sh")
+        .env("PS1", "EXPECT_PROMPT ")
+        .echo(true)
+        .spawn_repl("EXPECT_PROMPT ", Some("exit"))
+        .unwrap();
+
+    assert!(sh.get_echo().unwrap());
+
+    sh.expect_prompt().unwrap();
+    let out = sh.execute("echo Hello World").unwrap();
+    assert_eq!(out, b"\r\nHello World\r\n");
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn builder_repl() {
+    futures_lite::future::block_on(async {
+        let mut sh = SessionBuilder::new("sh")
+            .env("PS1", "EXPECT_PROMPT ")
+            .echo(true)
+            .spawn_repl("EXPECT_PROMPT ", Some("exit"))
+            .unwrap();
+
+        assert!(sh.get_echo().unwrap());
+
+        sh.expect_prompt().await.unwrap();
+        let out = sh.execute("echo Hello World").await.unwrap();
+        assert_eq!(out, b"\r\nHello World\r\n");
+    })
+}
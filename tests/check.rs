@@ -52,6 +52,51 @@ fn check_regex() {
     })
 }
 
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn check_regex_captures() {
+    let mut session = spawn("cat").unwrap();
+    session.send_line("a=1 b=2 c=").unwrap();
+
+    thread::sleep(Duration::from_millis(600));
+
+    let m = session.check(Regex(r"(?P<key>\w)=(?P<value>\d)?")).unwrap();
+    let captures = m.captures().collect::<Vec<_>>();
+    assert_eq!(captures.len(), 3);
+    assert_eq!(captures[0].name("key"), Some(&b"a"[..]));
+    assert_eq!(captures[0].name("value"), Some(&b"1"[..]));
+    assert_eq!(captures[1].get(0), Some(&b"b=2"[..]));
+    assert_eq!(captures[1].get(2), Some(&b"2"[..]));
+    assert_eq!(captures[2].name("key"), Some(&b"c"[..]));
+    assert_eq!(captures[2].name("value"), None);
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn check_regex_captures() {
+    futures_lite::future::block_on(async {
+        let mut session = spawn("cat").unwrap();
+        session.send_line("a=1 b=2 c=").await.unwrap();
+
+        thread::sleep(Duration::from_millis(600));
+
+        let m = session
+            .check(Regex(r"(?P<key>\w)=(?P<value>\d)?"))
+            .await
+            .unwrap();
+        let captures = m.captures().collect::<Vec<_>>();
+        assert_eq!(captures.len(), 3);
+        assert_eq!(captures[0].name("key"), Some(&b"a"[..]));
+        assert_eq!(captures[0].name("value"), Some(&b"1"[..]));
+        assert_eq!(captures[1].get(0), Some(&b"b=2"[..]));
+        assert_eq!(captures[1].get(2), Some(&b"2"[..]));
+        assert_eq!(captures[2].name("key"), Some(&b"c"[..]));
+        assert_eq!(captures[2].name("value"), None);
+    })
+}
+
 #[cfg(unix)]
 #[cfg(not(feature = "async"))]
 #[test]
@@ -94,7 +139,7 @@ fn check_eof() {
 
     assert_eq!(m.before(), b"");
     #[cfg(target_os = "linux")]
-    assert_eq!(m.first(), b"'Hello World'\r\n");
+    assert_eq!(m.first(), b"Hello World\r\n");
     #[cfg(not(target_os = "linux"))]
     assert!(m.matches().is_empty());
 }
@@ -112,7 +157,7 @@ fn check_eof() {
 
         assert_eq!(m.before(), b"");
         #[cfg(target_os = "linux")]
-        assert_eq!(m.first(), b"'Hello World'\r\n");
+        assert_eq!(m.first(), b"Hello World\r\n");
         #[cfg(not(target_os = "linux"))]
         assert!(m.matches().is_empty());
     })
@@ -258,7 +303,7 @@ fn check_macro_eof() {
         session,
         output = Eof => {
             #[cfg(target_os = "linux")]
-            assert_eq!(output.first(), b"'Hello World'\r\n");
+            assert_eq!(output.first(), b"Hello World\r\n");
             #[cfg(not(target_os = "linux"))]
             assert_eq!(output.first(), b"");
             assert_eq!(output.before(), b"");
@@ -286,7 +331,7 @@ fn check_macro_eof() {
             session,
             output = Eof => {
                 #[cfg(target_os = "linux")]
-                assert_eq!(output.first(), b"'Hello World'\r\n");
+                assert_eq!(output.first(), b"Hello World\r\n");
                 #[cfg(not(target_os = "linux"))]
                 assert_eq!(output.first(), b"");
                 assert_eq!(output.before(), b"");
@@ -436,3 +481,189 @@ fn check_macro_with_different_needles() {
         .unwrap();
     });
 }
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn check_macro_needle_index() {
+    let mut session = spawn("cat").unwrap();
+    session.send_line("Hello World").unwrap();
+
+    thread::sleep(Duration::from_millis(600));
+
+    expectrl::check!(
+        session,
+        _ = "Bye" => {
+            panic!("Unexpected result");
+        },
+        world = "World" => {
+            assert_eq!(world.needle_index(), Some(1));
+        },
+        default => {
+            panic!("Unexpected result");
+        },
+    )
+    .unwrap();
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn check_macro_needle_index() {
+    futures_lite::future::block_on(async {
+        let mut session = spawn("cat").unwrap();
+        session.send_line("Hello World").await.unwrap();
+
+        thread::sleep(Duration::from_millis(600));
+
+        expectrl::check!(
+            session,
+            _ = "Bye" => {
+                panic!("Unexpected result");
+            },
+            world = "World" => {
+                assert_eq!(world.needle_index(), Some(1));
+            },
+            default => {
+                panic!("Unexpected result");
+            },
+        )
+        .await
+        .unwrap();
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn check_any_order() {
+    let mut session = spawn("cat").unwrap();
+    session.send_line("Hello World").unwrap();
+    thread::sleep(Duration::from_millis(600));
+
+    // By default a needle which goes first in the list wins.
+    let m = session.check(Any(["World", "Hello"])).unwrap();
+    assert_eq!(m.needle_index(), Some(0));
+    assert_eq!(m.before(), b"Hello ");
+
+    session.send_line("Hello World").unwrap();
+    thread::sleep(Duration::from_millis(600));
+
+    let m = session
+        .check(Any(["World", "Hello"]).earliest_start())
+        .unwrap();
+    assert_eq!(m.needle_index(), Some(1));
+    assert_eq!(m.before(), b"\r\n");
+
+    let m = session
+        .check(Any(["World\r", "Wor"]).earliest_end())
+        .unwrap();
+    assert_eq!(m.needle_index(), Some(1));
+    assert_eq!(m.before(), b" ");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn check_any_order() {
+    futures_lite::future::block_on(async {
+        let mut session = spawn("cat").unwrap();
+        session.send_line("Hello World").await.unwrap();
+        thread::sleep(Duration::from_millis(600));
+
+        // By default a needle which goes first in the list wins.
+        let m = session.check(Any(["World", "Hello"])).await.unwrap();
+        assert_eq!(m.needle_index(), Some(0));
+        assert_eq!(m.before(), b"Hello ");
+
+        session.send_line("Hello World").await.unwrap();
+        thread::sleep(Duration::from_millis(600));
+
+        let m = session
+            .check(Any(["World", "Hello"]).earliest_start())
+            .await
+            .unwrap();
+        assert_eq!(m.needle_index(), Some(1));
+        assert_eq!(m.before(), b"\r\n");
+
+        let m = session
+            .check(Any(["World\r", "Wor"]).earliest_end())
+            .await
+            .unwrap();
+        assert_eq!(m.needle_index(), Some(1));
+        assert_eq!(m.before(), b" ");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn check_macro_idle() {
+    use expectrl::{Idle, Session};
+    use std::{process::Command, time::Instant};
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo Hello; echo World; sleep 10"]);
+    let mut session = Session::spawn(cmd).unwrap();
+
+    let start = Instant::now();
+    let mut quiet_output = None;
+    while quiet_output.is_none() {
+        assert!(start.elapsed() < Duration::from_secs(3));
+
+        expectrl::check!(
+            session,
+            _ = "Bye" => {
+                panic!("Unexpected result");
+            },
+            quiet = Idle(Duration::from_millis(300)) => {
+                assert_eq!(quiet.needle_index(), Some(1));
+                quiet_output = Some(quiet.as_bytes().to_vec());
+            },
+        )
+        .unwrap();
+
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert_eq!(quiet_output.unwrap(), b"Hello\r\nWorld\r\n".to_vec());
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn check_macro_idle() {
+    use expectrl::{Idle, Session};
+    use std::{process::Command, time::Instant};
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo Hello; echo World; sleep 10"]);
+        let mut session = Session::spawn(cmd).unwrap();
+
+        let start = Instant::now();
+        let mut quiet_output = None;
+        while quiet_output.is_none() {
+            assert!(start.elapsed() < Duration::from_secs(3));
+
+            expectrl::check!(
+                session,
+                _ = "Bye" => {
+                    panic!("Unexpected result");
+                },
+                quiet = Idle(Duration::from_millis(300)) => {
+                    assert_eq!(quiet.needle_index(), Some(1));
+                    quiet_output = Some(quiet.as_bytes().to_vec());
+                },
+            )
+            .await
+            .unwrap();
+
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert_eq!(quiet_output.unwrap(), b"Hello\r\nWorld\r\n".to_vec());
+    })
+}
@@ -126,7 +126,7 @@ fn expect_eof() {
     let mut session = spawn("echo 'Hello World'").unwrap();
     session.set_expect_timeout(None);
     let m = session.expect(Eof).unwrap();
-    assert_eq!(m.first(), b"'Hello World'\r\n");
+    assert_eq!(m.first(), b"Hello World\r\n");
     assert_eq!(m.before(), b"");
 }
 
@@ -138,7 +138,7 @@ fn expect_eof() {
         let mut session = spawn("echo 'Hello World'").unwrap();
         session.set_expect_timeout(None);
         let m = session.expect(Eof).await.unwrap();
-        assert_eq!(m.first(), b"'Hello World'\r\n");
+        assert_eq!(m.first(), b"Hello World\r\n");
         assert_eq!(m.before(), b"");
     })
 }
@@ -153,7 +153,7 @@ fn expect_eof() {
     std::thread::sleep(Duration::from_millis(300));
 
     let m = session.expect(Eof).unwrap();
-    assert_eq!(m.first(), b"'Hello World'\r\n");
+    assert_eq!(m.first(), b"Hello World\r\n");
     assert_eq!(m.before(), b"");
 }
 
@@ -207,7 +207,7 @@ fn expect_eof_timeout() {
     let mut p = spawn("sleep 3").expect("cannot run sleep 3");
     p.set_expect_timeout(Some(Duration::from_millis(100)));
     match p.expect(Eof) {
-        Err(expectrl::Error::ExpectTimeout) => {}
+        Err(expectrl::Error::ExpectTimeout(_)) => {}
         r => panic!("should raise TimeOut {:?}", r),
     }
 }
@@ -220,19 +220,792 @@ fn expect_eof_timeout() {
         let mut p = spawn("sleep 3").expect("cannot run sleep 3");
         p.set_expect_timeout(Some(Duration::from_millis(100)));
         match p.expect(Eof).await {
-            Err(expectrl::Error::ExpectTimeout) => {}
+            Err(expectrl::Error::ExpectTimeout(_)) => {}
             r => panic!("should raise TimeOut {:?}", r),
         }
     })
 }
 
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_timeout_details() {
+    use expectrl::{Error, Session, WaitStatus};
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo Hello; sleep 10"]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.set_expect_timeout(Some(Duration::from_millis(300)));
+
+    let details = match session.expect(Regex("Bye|Exit")) {
+        Err(Error::ExpectTimeout(details)) => details,
+        r => panic!("should raise TimeOut {:?}", r),
+    };
+    assert_eq!(details.needle(), r#"Regex("Bye|Exit")"#);
+    assert_eq!(details.buffer(), b"Hello\r\n");
+    assert_eq!(details.buffer_len(), 7);
+    assert!(!details.is_truncated());
+    assert!(details.elapsed() >= Duration::from_millis(300));
+    assert_eq!(details.status(), Some(WaitStatus::StillAlive));
+
+    let message = Error::ExpectTimeout(details).to_string();
+    assert!(message.contains(r#"Regex("Bye|Exit")"#), "{}", message);
+    assert!(message.contains(r#""Hello\r\n""#), "{}", message);
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_timeout_details() {
+    use expectrl::{Error, Session, WaitStatus};
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo Hello; sleep 10"]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        session.set_expect_timeout(Some(Duration::from_millis(300)));
+
+        let details = match session.expect(Regex("Bye|Exit")).await {
+            Err(Error::ExpectTimeout(details)) => details,
+            r => panic!("should raise TimeOut {:?}", r),
+        };
+        assert_eq!(details.needle(), r#"Regex("Bye|Exit")"#);
+        assert_eq!(details.buffer(), b"Hello\r\n");
+        assert_eq!(details.buffer_len(), 7);
+        assert!(!details.is_truncated());
+        assert!(details.elapsed() >= Duration::from_millis(300));
+        assert_eq!(details.status(), Some(WaitStatus::StillAlive));
+
+        let message = Error::ExpectTimeout(details).to_string();
+        assert!(message.contains(r#"Regex("Bye|Exit")"#), "{}", message);
+        assert!(message.contains(r#""Hello\r\n""#), "{}", message);
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_eof_details() {
+    use expectrl::{Error, Session, WaitStatus};
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "seq 1 1000; exit 3"]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    // make sure the process has exited.
+    std::thread::sleep(Duration::from_millis(500));
+
+    let details = match session.expect("Bye") {
+        Err(Error::Eof(details)) => details,
+        r => panic!("should raise Eof {:?}", r),
+    };
+    assert_eq!(details.needle(), r#""Bye""#);
+    assert!(details.is_truncated());
+    assert_eq!(
+        details.buffer().len(),
+        expectrl::ExpectDetails::BUFFER_LIMIT
+    );
+    assert!(details.buffer().ends_with(b"999\r\n1000\r\n"));
+    assert_eq!(details.status(), Some(WaitStatus::Exited(session.pid(), 3)));
+
+    // The status is still available.
+    assert_eq!(
+        session.wait().unwrap(),
+        WaitStatus::Exited(session.pid(), 3)
+    );
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_eof_details() {
+    use expectrl::{Error, Session, WaitStatus};
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "seq 1 1000; exit 3"]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        // make sure the process has exited.
+        std::thread::sleep(Duration::from_millis(500));
+
+        let details = match session.expect("Bye").await {
+            Err(Error::Eof(details)) => details,
+            r => panic!("should raise Eof {:?}", r),
+        };
+        assert_eq!(details.needle(), r#""Bye""#);
+        assert!(details.is_truncated());
+        assert_eq!(
+            details.buffer().len(),
+            expectrl::ExpectDetails::BUFFER_LIMIT
+        );
+        assert!(details.buffer().ends_with(b"999\r\n1000\r\n"));
+        assert_eq!(details.status(), Some(WaitStatus::Exited(session.pid(), 3)));
+
+        // The status is still available.
+        assert_eq!(
+            session.wait().unwrap(),
+            WaitStatus::Exited(session.pid(), 3)
+        );
+    })
+}
+
 #[cfg(windows)]
 #[test]
 fn expect_eof_timeout() {
     let mut p = spawn("sleep 3").expect("cannot run sleep 3");
     p.set_expect_timeout(Some(Duration::from_millis(100)));
     match p.expect(Eof) {
-        Err(expectrl::Error::ExpectTimeout) => {}
+        Err(expectrl::Error::ExpectTimeout(_)) => {}
+        r => panic!("should raise TimeOut {:?}", r),
+    }
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_eof_large_output() {
+    let mut session = spawn("seq 1 100000").unwrap();
+    session.set_expect_timeout(Some(Duration::from_secs(30)));
+    let m = session.expect(Eof).unwrap();
+    assert!(m.first().starts_with(b"1\r\n2\r\n3\r\n"));
+    assert!(m.first().ends_with(b"99999\r\n100000\r\n"));
+    assert_eq!(m.before(), b"");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_eof_large_output() {
+    futures_lite::future::block_on(async {
+        let mut session = spawn("seq 1 100000").unwrap();
+        session.set_expect_timeout(Some(Duration::from_secs(30)));
+        let m = session.expect(Eof).await.unwrap();
+        assert!(m.first().starts_with(b"1\r\n2\r\n3\r\n"));
+        assert!(m.first().ends_with(b"99999\r\n100000\r\n"));
+        assert_eq!(m.before(), b"");
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_regex_is_lazy_within_a_chunk() {
+    use expectrl::Session;
+    use std::io::Cursor;
+
+    // The whole buffer is read at once,
+    // but it's still checked by prefixes.
+    let mut session = Session::new((), Cursor::new(b"12345 end".to_vec()));
+
+    let m = session.expect(Regex("\\d+")).unwrap();
+    assert_eq!(m.first(), b"1");
+
+    let m = session.expect(Eof).unwrap();
+    assert_eq!(m.first(), b"2345 end");
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn expect_regex_is_lazy_within_a_chunk() {
+    use expectrl::Session;
+    use futures_lite::io::Cursor;
+
+    futures_lite::future::block_on(async {
+        // The whole buffer is read at once,
+        // but it's still checked by prefixes.
+        let mut session = Session::new((), Cursor::new(b"12345 end".to_vec()));
+
+        let m = session.expect(Regex("\\d+")).await.unwrap();
+        assert_eq!(m.first(), b"1");
+
+        let m = session.expect(Eof).await.unwrap();
+        assert_eq!(m.first(), b"2345 end");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_compiled_regex() {
+    use expectrl::CompiledRegex;
+
+    let re = CompiledRegex::new(r"\d+ World").unwrap();
+
+    let mut session = spawn("cat").unwrap();
+    session.send_line("Hello 2021 World").unwrap();
+    let m = session.expect(&re).unwrap();
+    assert_eq!(m.before(), b"Hello ");
+    assert_eq!(m.first(), b"2021 World");
+
+    session.send_line("Hello 2022 World").unwrap();
+    let m = session.expect(&re).unwrap();
+    assert_eq!(m.first(), b"2022 World");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_compiled_regex() {
+    use expectrl::CompiledRegex;
+
+    futures_lite::future::block_on(async {
+        let re = CompiledRegex::new(r"\d+ World").unwrap();
+
+        let mut session = spawn("cat").unwrap();
+        session.send_line("Hello 2021 World").await.unwrap();
+        let m = session.expect(&re).await.unwrap();
+        assert_eq!(m.before(), b"Hello ");
+        assert_eq!(m.first(), b"2021 World");
+
+        session.send_line("Hello 2022 World").await.unwrap();
+        let m = session.expect(&re).await.unwrap();
+        assert_eq!(m.first(), b"2022 World");
+    })
+}
+
+#[cfg(target_os = "linux")]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_waits_without_spinning() {
+    let mut p = spawn("sleep 3").unwrap();
+    p.set_expect_timeout(Some(Duration::from_millis(500)));
+
+    let cpu_time_before = thread_cpu_time();
+    let now = std::time::Instant::now();
+    match p.expect(Eof) {
+        Err(expectrl::Error::ExpectTimeout(_)) => {}
         r => panic!("should raise TimeOut {:?}", r),
     }
+    let elapsed = now.elapsed();
+    let cpu_time = thread_cpu_time() - cpu_time_before;
+
+    assert!(elapsed >= Duration::from_millis(500));
+    assert!(elapsed < Duration::from_secs(2));
+    assert!(
+        cpu_time < Duration::from_millis(100),
+        "expect used {:?} of cpu time",
+        cpu_time
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[cfg(not(feature = "async"))]
+fn thread_cpu_time() -> Duration {
+    use nix::libc::{getrusage, rusage, RUSAGE_THREAD};
+
+    let mut usage: rusage = unsafe { std::mem::zeroed() };
+    assert_eq!(unsafe { getrusage(RUSAGE_THREAD, &mut usage) }, 0);
+
+    let time = |t: nix::libc::timeval| {
+        Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+    };
+    time(usage.ru_utime) + time(usage.ru_stime)
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_regex_groups() {
+    let mut session = spawn("cat").unwrap();
+    session.send_line("started pid=123 port=8080").unwrap();
+
+    let m = session
+        .expect(Regex(r"pid=(?P<pid>\d+) port=(?P<port>\d+)\r"))
+        .unwrap();
+    assert_eq!(m.before(), b"started ");
+    assert_eq!(m.get(0), Some(&b"pid=123 port=8080\r"[..]));
+    assert_eq!(m.get(1), Some(&b"123"[..]));
+    assert_eq!(m.get(2), Some(&b"8080"[..]));
+    assert_eq!(m.get(3), None);
+    assert_eq!(m.name("pid"), Some(&b"123"[..]));
+    assert_eq!(m.name("port"), Some(&b"8080"[..]));
+    assert_eq!(m.name("user"), None);
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_regex_groups() {
+    futures_lite::future::block_on(async {
+        let mut session = spawn("cat").unwrap();
+        session
+            .send_line("started pid=123 port=8080")
+            .await
+            .unwrap();
+
+        let m = session
+            .expect(Regex(r"pid=(?P<pid>\d+) port=(?P<port>\d+)\r"))
+            .await
+            .unwrap();
+        assert_eq!(m.before(), b"started ");
+        assert_eq!(m.get(0), Some(&b"pid=123 port=8080\r"[..]));
+        assert_eq!(m.get(1), Some(&b"123"[..]));
+        assert_eq!(m.get(2), Some(&b"8080"[..]));
+        assert_eq!(m.get(3), None);
+        assert_eq!(m.name("pid"), Some(&b"123"[..]));
+        assert_eq!(m.name("port"), Some(&b"8080"[..]));
+        assert_eq!(m.name("user"), None);
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_combinators() {
+    use expectrl::{All, Needle, Not, Repeat, Sequence, Within};
+
+    let mut session = spawn("cat").unwrap();
+    session.send_line("one two three").unwrap();
+    session.send_line("four five").unwrap();
+
+    let m = session
+        .expect(Sequence::boxed(vec![
+            Box::new("two"),
+            Box::new(Regex(r"t\w+\r")),
+        ]))
+        .unwrap();
+    assert_eq!(m.before(), b"one ");
+    assert_eq!(m.matches(), vec![b"two".as_ref(), b"three\r".as_ref()]);
+
+    let m = session.expect(Repeat("\n", 2)).unwrap();
+    assert_eq!(m.before(), b"");
+    assert_eq!(m.as_bytes(), b"\nfour five\r\n");
+
+    session.send_line("six seven").unwrap();
+    let m = session
+        .expect(All([
+            Box::new(Within("six", 3)) as Box<dyn Needle>,
+            Box::new(Not("seven", "eight")),
+        ]))
+        .unwrap();
+    assert_eq!(m.matches(), vec![b"six".as_ref(), b"seven".as_ref()]);
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_combinators() {
+    use expectrl::{All, Needle, Not, Repeat, Sequence, Within};
+
+    futures_lite::future::block_on(async {
+        let mut session = spawn("cat").unwrap();
+        session.send_line("one two three").await.unwrap();
+        session.send_line("four five").await.unwrap();
+
+        let m = session
+            .expect(Sequence::boxed(vec![
+                Box::new("two"),
+                Box::new(Regex(r"t\w+\r")),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(m.before(), b"one ");
+        assert_eq!(m.matches(), vec![b"two".as_ref(), b"three\r".as_ref()]);
+
+        let m = session.expect(Repeat("\n", 2)).await.unwrap();
+        assert_eq!(m.before(), b"");
+        assert_eq!(m.as_bytes(), b"\nfour five\r\n");
+
+        session.send_line("six seven").await.unwrap();
+        let m = session
+            .expect(All([
+                Box::new(Within("six", 3)) as Box<dyn Needle>,
+                Box::new(Not("seven", "eight")),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(m.matches(), vec![b"six".as_ref(), b"seven".as_ref()]);
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_any_needle_index() {
+    use expectrl::Any;
+
+    let mut session = spawn("cat").unwrap();
+    session.send_line("Hello World").unwrap();
+
+    let m = session.expect(Any(["Bye", "World", "Hi"])).unwrap();
+    assert_eq!(m.needle_index(), Some(1));
+    assert_eq!(m.before(), b"Hello ");
+
+    session.send_line("123").unwrap();
+    let m = session
+        .expect(Any::boxed(vec![
+            Box::new(NBytes(10)),
+            Box::new(Regex(r"\d+")),
+        ]))
+        .unwrap();
+    assert_eq!(m.needle_index(), Some(1));
+
+    session.send_line("Hello").unwrap();
+    let m = session.expect("Hello").unwrap();
+    assert_eq!(m.needle_index(), None);
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_any_needle_index() {
+    use expectrl::Any;
+
+    futures_lite::future::block_on(async {
+        let mut session = spawn("cat").unwrap();
+        session.send_line("Hello World").await.unwrap();
+
+        let m = session.expect(Any(["Bye", "World", "Hi"])).await.unwrap();
+        assert_eq!(m.needle_index(), Some(1));
+        assert_eq!(m.before(), b"Hello ");
+
+        session.send_line("123").await.unwrap();
+        let m = session
+            .expect(Any::boxed(vec![
+                Box::new(NBytes(10)),
+                Box::new(Regex(r"\d+")),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(m.needle_index(), Some(1));
+
+        session.send_line("Hello").await.unwrap();
+        let m = session.expect("Hello").await.unwrap();
+        assert_eq!(m.needle_index(), None);
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_any_order() {
+    use expectrl::{Any, Needle};
+
+    let mut session = spawn("cat").unwrap();
+
+    // Expect checks data byte by byte so a needle whose match ends first wins
+    // regardless of the order and the option.
+    let needles: Vec<Box<dyn Needle>> = vec![
+        Box::new(Any(["World", "Hello"])),
+        Box::new(Any(["World", "Hello"]).earliest_start()),
+        Box::new(Any(["World", "Hello"]).earliest_end()),
+    ];
+    for needle in needles {
+        session.send_line("Hello World").unwrap();
+        let m = session.expect(needle).unwrap();
+        assert_eq!(m.needle_index(), Some(1));
+        session.expect("\n").unwrap();
+    }
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_any_order() {
+    use expectrl::{Any, Needle};
+
+    futures_lite::future::block_on(async {
+        let mut session = spawn("cat").unwrap();
+
+        // Expect checks data byte by byte so a needle whose match ends first wins
+        // regardless of the order and the option.
+        let needles: Vec<Box<dyn Needle>> = vec![
+            Box::new(Any(["World", "Hello"])),
+            Box::new(Any(["World", "Hello"]).earliest_start()),
+            Box::new(Any(["World", "Hello"]).earliest_end()),
+        ];
+        for needle in needles {
+            session.send_line("Hello World").await.unwrap();
+            let m = session.expect(needle).await.unwrap();
+            assert_eq!(m.needle_index(), Some(1));
+            session.expect("\n").await.unwrap();
+        }
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_idle() {
+    use expectrl::{Idle, Session};
+    use std::{process::Command, time::Instant};
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo first; sleep 0.6; echo second; sleep 10"]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.set_expect_timeout(Some(Duration::from_secs(5)));
+
+    let start = Instant::now();
+
+    let m = session.expect(Idle(Duration::from_millis(200))).unwrap();
+    assert_eq!(m.before(), b"");
+    assert_eq!(m.as_bytes(), b"first\r\n");
+
+    // A time is counted from the last output, so it covers the second line.
+    let m = session.expect(Idle(Duration::from_secs(1))).unwrap();
+    assert_eq!(m.as_bytes(), b"second\r\n");
+
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(1600), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_idle() {
+    use expectrl::{Idle, Session};
+    use std::{process::Command, time::Instant};
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo first; sleep 0.6; echo second; sleep 10"]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        session.set_expect_timeout(Some(Duration::from_secs(5)));
+
+        let start = Instant::now();
+
+        let m = session
+            .expect(Idle(Duration::from_millis(200)))
+            .await
+            .unwrap();
+        assert_eq!(m.before(), b"");
+        assert_eq!(m.as_bytes(), b"first\r\n");
+
+        // A time is counted from the last output, so it covers the second line.
+        let m = session.expect(Idle(Duration::from_secs(1))).await.unwrap();
+        assert_eq!(m.as_bytes(), b"second\r\n");
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(1600), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_timeout_per_call() {
+    use expectrl::{Error, Session};
+    use std::{process::Command, time::Instant};
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo first; sleep 0.5; echo second; sleep 10"]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    // A session timeout is not used by the calls.
+    session.set_expect_timeout(None);
+
+    let start = Instant::now();
+    let err = session
+        .expect_timeout("second", Duration::from_millis(100))
+        .unwrap_err();
+    assert!(matches!(err, Error::ExpectTimeout(_)));
+    assert!(start.elapsed() < Duration::from_millis(400));
+
+    let m = session
+        .expect_timeout("second", Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(m.before(), b"first\r\n");
+
+    let start = Instant::now();
+    let deadline = start + Duration::from_millis(200);
+    let err = session.expect_until("third", deadline).unwrap_err();
+    assert!(matches!(err, Error::ExpectTimeout(_)));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_timeout_per_call() {
+    use expectrl::{Error, Session};
+    use std::{process::Command, time::Instant};
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo first; sleep 0.5; echo second; sleep 10"]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        // A session timeout is not used by the calls.
+        session.set_expect_timeout(None);
+
+        let start = Instant::now();
+        let err = session
+            .expect_timeout("second", Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ExpectTimeout(_)));
+        assert!(start.elapsed() < Duration::from_millis(400));
+
+        let m = session
+            .expect_timeout("second", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(m.before(), b"first\r\n");
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(200);
+        let err = session.expect_until("third", deadline).await.unwrap_err();
+        assert!(matches!(err, Error::ExpectTimeout(_)));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_guarded() {
+    use expectrl::{Any, Error, Session};
+    use std::{process::Command, time::Instant};
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo Working; echo 'Error: oops'; sleep 10"]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.set_expect_timeout(Some(Duration::from_secs(10)));
+
+    let guard = Any(["Error:", "Traceback"]);
+
+    let m = session.expect_guarded("Working", &guard).unwrap();
+    assert_eq!(m.first(), b"Working");
+
+    let start = Instant::now();
+    let err = session.expect_guarded("Success", &guard).unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(2));
+    match err {
+        Error::GuardMatched(found) => {
+            assert_eq!(found.before(), b"\r\n");
+            assert_eq!(found.first(), b"Error:");
+            assert_eq!(found.needle_index(), Some(0));
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+
+    // A guard match is consumed.
+    let m = session.expect("\r\n").unwrap();
+    assert_eq!(m.before(), b" oops");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_guarded() {
+    use expectrl::{Any, Error, Session};
+    use std::{process::Command, time::Instant};
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo Working; echo 'Error: oops'; sleep 10"]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        session.set_expect_timeout(Some(Duration::from_secs(10)));
+
+        let guard = Any(["Error:", "Traceback"]);
+
+        let m = session.expect_guarded("Working", &guard).await.unwrap();
+        assert_eq!(m.first(), b"Working");
+
+        let start = Instant::now();
+        let err = session.expect_guarded("Success", &guard).await.unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        match err {
+            Error::GuardMatched(found) => {
+                assert_eq!(found.before(), b"\r\n");
+                assert_eq!(found.first(), b"Error:");
+                assert_eq!(found.needle_index(), Some(0));
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        // A guard match is consumed.
+        let m = session.expect("\r\n").await.unwrap();
+        assert_eq!(m.before(), b" oops");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_plain() {
+    use expectrl::{Plain, Session};
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", r"printf 'ok: h\033[1mello\033[0m world\n'"]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    let m = session.expect(Plain("hello world")).unwrap();
+    assert_eq!(m.before(), b"ok: ");
+    assert_eq!(m.first(), b"h\x1b[1mello\x1b[0m world");
+    session.expect(Eof).unwrap();
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_plain() {
+    use expectrl::{Plain, Session};
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", r"printf 'ok: h\033[1mello\033[0m world\n'"]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        let m = session.expect(Plain("hello world")).await.unwrap();
+        assert_eq!(m.before(), b"ok: ");
+        assert_eq!(m.first(), b"h\x1b[1mello\x1b[0m world");
+        session.expect(Eof).await.unwrap();
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_with_strip_ansi() {
+    use expectrl::Session;
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", r"printf '\033[32mok\033[0m: h\033[1mello\033[0m\n'"]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.set_strip_ansi(true);
+
+    let m = session.expect(Regex(r"\w+: ")).unwrap();
+    assert_eq!(m.first(), b"\x1b[32mok\x1b[0m: ");
+    let m = session.expect("hello\r\n").unwrap();
+    assert_eq!(m.first(), b"h\x1b[1mello\x1b[0m\r\n");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_with_strip_ansi() {
+    use expectrl::Session;
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", r"printf '\033[32mok\033[0m: h\033[1mello\033[0m\n'"]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        session.set_strip_ansi(true);
+
+        let m = session.expect(Regex(r"\w+: ")).await.unwrap();
+        assert_eq!(m.first(), b"\x1b[32mok\x1b[0m: ");
+        let m = session.expect("hello\r\n").await.unwrap();
+        assert_eq!(m.first(), b"h\x1b[1mello\x1b[0m\r\n");
+    })
 }
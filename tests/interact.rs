@@ -228,6 +228,45 @@ fn interact_on_output_not_matched() {
     assert_eq!(m.before(), b"");
 }
 
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn interact_on_output_idle() {
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo first; sleep 1; echo second; sleep 10"]);
+
+    let reader = ListReaderWithDelayedEof::new(Vec::new(), Duration::from_secs(3));
+    let mut writer = io::Cursor::new(vec![0; 1024]);
+
+    let mut session = expectrl::Session::spawn(cmd).unwrap();
+    let mut opts = expectrl::interact::InteractOptions::streamed(reader, &mut writer)
+        .unwrap()
+        .state(Vec::new())
+        .on_output(
+            expectrl::Idle(Duration::from_millis(300)),
+            |mut ctx, found| {
+                ctx.state().push(found.as_bytes().to_vec());
+                Ok(())
+            },
+        );
+
+    opts.interact(&mut session).unwrap();
+
+    // A handler is called once for each quiet period.
+    assert_eq!(
+        opts.get_state(),
+        &vec![b"first\r\n".to_vec(), b"second\r\n".to_vec()]
+    );
+
+    drop(opts);
+
+    let buffer = String::from_utf8_lossy(writer.get_ref());
+    let buffer = buffer.trim_end_matches(char::from(0));
+    assert_eq!(buffer, "first\r\nsecond\r\n");
+}
+
 #[cfg(unix)]
 #[cfg(not(feature = "async"))]
 #[test]
@@ -353,3 +392,26 @@ where
         std::task::Poll::Ready(result)
     }
 }
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn interact_strip_filter() {
+    let reader = ReaderWithDelayEof::new("", Duration::from_secs(2));
+    let mut writer = io::Cursor::new(vec![0; 2048]);
+
+    let mut session =
+        expectrl::spawn(r"printf '\033[31mred\033[0m \033]0;title\007plain\n'").unwrap();
+    let mut opts = expectrl::interact::InteractOptions::streamed(reader, &mut writer)
+        .unwrap()
+        .output_filter(expectrl::ansi::strip_filter());
+
+    opts.interact(&mut session).unwrap();
+
+    drop(opts);
+
+    let buffer = String::from_utf8_lossy(writer.get_ref());
+    let buffer = buffer.trim_end_matches(char::from(0));
+
+    assert_eq!(buffer, "red plain\r\n");
+}
@@ -208,7 +208,7 @@ fn expect_after_is_matched_eof() {
     #[cfg(not(target_os = "linux"))]
     assert_eq!(m.first(), b"");
 
-    assert!(matches!(p.expect("").unwrap_err(), expectrl::Error::Eof));
+    assert!(matches!(p.expect("").unwrap_err(), expectrl::Error::Eof(_)));
 }
 
 #[cfg(unix)]
@@ -230,7 +230,7 @@ fn expect_after_is_matched_eof() {
 
         assert!(matches!(
             p.expect("").await.unwrap_err(),
-            expectrl::Error::Eof
+            expectrl::Error::Eof(_)
         ));
     })
 }
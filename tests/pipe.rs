@@ -0,0 +1,153 @@
+#![cfg(unix)]
+
+use expectrl::{pipe::PipeSession, Eof, WaitStatus};
+use std::process::Command;
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn pipe_is_not_a_tty() {
+    let mut session = PipeSession::spawn_pipe(Command::new("tty")).unwrap();
+    session.expect("not a tty\n").unwrap();
+    assert_eq!(
+        session.wait().unwrap(),
+        WaitStatus::Exited(session.pid(), 1)
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn pipe_is_not_a_tty() {
+    futures_lite::future::block_on(async {
+        let mut session = PipeSession::spawn_pipe(Command::new("tty")).unwrap();
+        session.expect("not a tty\n").await.unwrap();
+        assert_eq!(
+            session.wait().unwrap(),
+            WaitStatus::Exited(session.pid(), 1)
+        );
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn pipe_send_and_expect() {
+    let mut session = PipeSession::spawn_pipe(Command::new("cat")).unwrap();
+    session.send_line("Hello World").unwrap();
+
+    // There's no echo and no \r\n translation as in a pty.
+    let m = session.expect("World").unwrap();
+    assert_eq!(m.before(), b"Hello ");
+
+    session.send("Bye").unwrap();
+    session.send_eof().unwrap();
+
+    let m = session.expect(Eof).unwrap();
+    assert_eq!(m.first(), b"\nBye");
+    assert_eq!(
+        session.wait().unwrap(),
+        WaitStatus::Exited(session.pid(), 0)
+    );
+
+    assert!(session.send_line("Hello").is_err());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn pipe_send_and_expect() {
+    futures_lite::future::block_on(async {
+        let mut session = PipeSession::spawn_pipe(Command::new("cat")).unwrap();
+        session.send_line("Hello World").await.unwrap();
+
+        // There's no echo and no \r\n translation as in a pty.
+        let m = session.expect("World").await.unwrap();
+        assert_eq!(m.before(), b"Hello ");
+
+        session.send("Bye").await.unwrap();
+        session.send_eof().await.unwrap();
+
+        let m = session.expect(Eof).await.unwrap();
+        assert_eq!(m.first(), b"\nBye");
+        assert_eq!(
+            session.wait().unwrap(),
+            WaitStatus::Exited(session.pid(), 0)
+        );
+
+        assert!(session.send_line("Hello").await.is_err());
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn pipe_check_and_is_matched() {
+    let mut session = PipeSession::spawn_pipe(Command::new("echo")).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    assert!(session.is_matched("\n").unwrap());
+    let m = session.check("\n").unwrap();
+    assert_eq!(m.first(), b"\n");
+    assert!(session.is_matched(Eof).unwrap());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn pipe_check_and_is_matched() {
+    futures_lite::future::block_on(async {
+        let mut session = PipeSession::spawn_pipe(Command::new("echo")).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(300));
+
+        assert!(session.is_matched("\n").await.unwrap());
+        let m = session.check("\n").await.unwrap();
+        assert_eq!(m.first(), b"\n");
+        assert!(session.is_matched(Eof).await.unwrap());
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn pipe_killed_process_status() {
+    let mut session = PipeSession::spawn_pipe(Command::new("cat")).unwrap();
+    assert!(session.is_alive().unwrap());
+
+    session.kill(expectrl::Signal::SIGKILL).unwrap();
+    assert_eq!(
+        session.wait().unwrap(),
+        WaitStatus::Signaled(session.pid(), expectrl::Signal::SIGKILL, false)
+    );
+    assert!(!session.is_alive().unwrap());
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn pipe_terminate() {
+    use expectrl::Signal;
+    use std::time::Duration;
+
+    let mut session = PipeSession::spawn_pipe(Command::new("cat")).unwrap();
+    assert_eq!(
+        session.terminate(Duration::from_secs(1)).unwrap(),
+        WaitStatus::Signaled(session.pid(), Signal::SIGHUP, false)
+    );
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn pipe_wait_timeout() {
+    use std::time::Duration;
+
+    let mut session = PipeSession::spawn_pipe(Command::new("cat")).unwrap();
+    assert_eq!(
+        session.wait_timeout(Duration::from_millis(100)).unwrap(),
+        None
+    );
+
+    session.send_eof().unwrap();
+    assert_eq!(
+        session.wait_timeout(Duration::from_secs(5)).unwrap(),
+        Some(WaitStatus::Exited(session.pid(), 0))
+    );
+
+    // A status is left to be collected.
+    assert_eq!(
+        session.wait().unwrap(),
+        WaitStatus::Exited(session.pid(), 0)
+    );
+}
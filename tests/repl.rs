@@ -155,3 +155,45 @@ fn bash_control_chars() {
     p.send_control(ControlCode::Substitute).unwrap(); // suspend:SIGTSTPcon
     p.expect_prompt().unwrap();
 }
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn bash_execute_timeout() {
+    use expectrl::Error;
+
+    let mut p = spawn_bash().unwrap();
+
+    let err = p
+        .execute_timeout("sleep 1", Duration::from_millis(200))
+        .unwrap_err();
+    assert!(matches!(err, Error::ExpectTimeout(_)));
+    p.expect_prompt().unwrap();
+
+    let output = p
+        .execute_timeout("echo Hello World", Duration::from_secs(5))
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output).contains("Hello World"));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn bash_execute_timeout() {
+    use expectrl::Error;
+
+    futures_lite::future::block_on(async {
+        let mut p = spawn_bash().await.unwrap();
+
+        let err = p
+            .execute_timeout("sleep 1", Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ExpectTimeout(_)));
+        p.expect_prompt().await.unwrap();
+
+        let output = p
+            .execute_timeout("echo Hello World", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output).contains("Hello World"));
+    });
+}
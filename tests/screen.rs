@@ -0,0 +1,221 @@
+#![cfg(unix)]
+
+use expectrl::{screen::Color, Eof, ScreenText, Session, StyledText};
+use std::{process::Command, time::Duration};
+
+const ANSI_SCREEN: &str = "status:  good
+[5/5]: file4
+[5/5]: file4
+Here is a test password prompt
+Do not enter a real password
+Password:";
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn screen_of_ansi_script() {
+    let mut cmd = Command::new("python");
+    cmd.arg("./tests/source/ansi.py");
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.enable_screen(80, 24);
+    session.set_expect_timeout(Some(Duration::from_secs(20)));
+
+    // A line which is redrawn via `\r` is seen only in its last state.
+    let m = session.expect(ScreenText("[5/5]: file4")).unwrap();
+    assert!(String::from_utf8_lossy(m.as_bytes()).contains("[1/5]: file0\r"));
+    let screen = session.screen().unwrap();
+    assert_eq!(screen.row(0).unwrap(), "status:  good");
+    assert_eq!(screen.row(1).unwrap(), "[5/5]: file4");
+
+    session.expect(ScreenText("Password:")).unwrap();
+    let screen = session.screen().unwrap();
+    assert_eq!(screen.contents(), ANSI_SCREEN);
+    assert_eq!(screen.cursor_position(), (10, 5));
+
+    // An echo is turned off in a spawned terminal, so the input is not seen.
+    session.send_line("secret").unwrap();
+    session.expect(ScreenText("Continue [y/n]:")).unwrap();
+    let screen = session.screen().unwrap();
+    assert_eq!(screen.row(5).unwrap(), "Password:");
+    assert_eq!(screen.row(6).unwrap(), "Continue [y/n]:");
+
+    session.send_line("n").unwrap();
+    session.expect(Eof).unwrap();
+    assert_eq!(
+        session.screen().unwrap().row(6).unwrap(),
+        "Continue [y/n]:You said: n"
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn screen_of_ansi_script() {
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("python");
+        cmd.arg("./tests/source/ansi.py");
+
+        let mut session = Session::spawn(cmd).unwrap();
+        session.enable_screen(80, 24);
+        session.set_expect_timeout(Some(Duration::from_secs(20)));
+
+        // A line which is redrawn via `\r` is seen only in its last state.
+        let m = session.expect(ScreenText("[5/5]: file4")).await.unwrap();
+        assert!(String::from_utf8_lossy(m.as_bytes()).contains("[1/5]: file0\r"));
+        let screen = session.screen().unwrap();
+        assert_eq!(screen.row(0).unwrap(), "status:  good");
+        assert_eq!(screen.row(1).unwrap(), "[5/5]: file4");
+
+        session.expect(ScreenText("Password:")).await.unwrap();
+        let screen = session.screen().unwrap();
+        assert_eq!(screen.contents(), ANSI_SCREEN);
+        assert_eq!(screen.cursor_position(), (10, 5));
+
+        // An echo is turned off in a spawned terminal, so the input is not seen.
+        session.send_line("secret").await.unwrap();
+        session.expect(ScreenText("Continue [y/n]:")).await.unwrap();
+        let screen = session.screen().unwrap();
+        assert_eq!(screen.row(5).unwrap(), "Password:");
+        assert_eq!(screen.row(6).unwrap(), "Continue [y/n]:");
+
+        session.send_line("n").await.unwrap();
+        session.expect(Eof).await.unwrap();
+        assert_eq!(
+            session.screen().unwrap().row(6).unwrap(),
+            "Continue [y/n]:You said: n"
+        );
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn screen_text_check() {
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "printf 'loading...'; sleep 0.3; printf '\\r\\033[Kdone'; sleep 10",
+    ]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.enable_screen(20, 5);
+
+    session.expect("loading...").unwrap();
+    assert!(!session.is_matched(ScreenText("done")).unwrap());
+    assert!(session.check(ScreenText("done")).unwrap().is_empty());
+
+    std::thread::sleep(Duration::from_millis(600));
+
+    assert!(session.is_matched(ScreenText("done")).unwrap());
+    let m = session.check(ScreenText("done")).unwrap();
+    assert_eq!(m.as_bytes(), b"\r\x1b[Kdone");
+    assert_eq!(session.screen().unwrap().contents(), "done");
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn screen_text_check() {
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "printf 'loading...'; sleep 0.3; printf '\\r\\033[Kdone'; sleep 10",
+        ]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        session.enable_screen(20, 5);
+
+        session.expect("loading...").await.unwrap();
+        assert!(!session.is_matched(ScreenText("done")).await.unwrap());
+        assert!(session.check(ScreenText("done")).await.unwrap().is_empty());
+
+        std::thread::sleep(Duration::from_millis(600));
+
+        assert!(session.is_matched(ScreenText("done")).await.unwrap());
+        let m = session.check(ScreenText("done")).await.unwrap();
+        assert_eq!(m.as_bytes(), b"\r\x1b[Kdone");
+        assert_eq!(session.screen().unwrap().contents(), "done");
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn screen_is_resized_with_window() {
+    let mut session = Session::spawn(Command::new("cat")).unwrap();
+    session.enable_screen(80, 24);
+
+    session.set_window_size(40, 10).unwrap();
+    assert_eq!(session.screen().unwrap().size(), (40, 10));
+
+    session.disable_screen();
+    assert!(session.screen().is_none());
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn screen_styled_text() {
+    let mut cmd = Command::new("python");
+    cmd.current_dir("./tests/source").args([
+        "-c",
+        "from colors import colorize, Color; print(colorize('ok', Color.GREEN), colorize('failed', Color.RED), 'done')",
+    ]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.enable_screen(80, 24);
+    session
+        .expect(StyledText::new("failed").foreground(Color::RED))
+        .unwrap();
+    session.expect(Eof).unwrap();
+
+    let screen = session.screen().unwrap();
+    assert_eq!(screen.row(0).unwrap(), "ok failed done");
+    assert_eq!(screen.cell(0, 0).unwrap().style().foreground, Color::GREEN);
+    assert_eq!(screen.cell(3, 0).unwrap().style().foreground, Color::RED);
+    assert_eq!(
+        screen.cell(10, 0).unwrap().style().foreground,
+        Color::Default
+    );
+    assert_eq!(
+        screen.find_styled("ok", |style| style.foreground == Color::GREEN),
+        Some((0, 0))
+    );
+    assert_eq!(
+        screen.find_styled("done", |style| style.foreground == Color::RED),
+        None
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn screen_styled_text() {
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("python");
+        cmd.current_dir("./tests/source").args([
+            "-c",
+            "from colors import colorize, Color; print(colorize('ok', Color.GREEN), colorize('failed', Color.RED), 'done')",
+        ]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        session.enable_screen(80, 24);
+        session
+            .expect(StyledText::new("failed").foreground(Color::RED))
+            .await
+            .unwrap();
+        session.expect(Eof).await.unwrap();
+
+        let screen = session.screen().unwrap();
+        assert_eq!(screen.row(0).unwrap(), "ok failed done");
+        assert_eq!(screen.cell(0, 0).unwrap().style().foreground, Color::GREEN);
+        assert_eq!(screen.cell(3, 0).unwrap().style().foreground, Color::RED);
+        assert_eq!(
+            screen.cell(10, 0).unwrap().style().foreground,
+            Color::Default
+        );
+        assert_eq!(
+            screen.find_styled("ok", |style| style.foreground == Color::GREEN),
+            Some((0, 0))
+        );
+        assert_eq!(
+            screen.find_styled("done", |style| style.foreground == Color::RED),
+            None
+        );
+    })
+}
@@ -189,3 +189,1046 @@ fn send_line() {
         );
     }
 }
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn session_over_unix_socket() {
+    use expectrl::Session;
+    use std::os::unix::net::UnixStream;
+
+    let (local, mut remote) = UnixStream::pair().unwrap();
+    let mut session = Session::new((), local);
+
+    session.send_line("Hello World").unwrap();
+
+    let mut buf = [0; 12];
+    remote.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"Hello World\n");
+
+    remote.write_all(b"Hi there").unwrap();
+
+    let m = session.expect("there").unwrap();
+    assert_eq!(m.before(), b"Hi ");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn session_over_unix_socket() {
+    use expectrl::Session;
+    use std::os::unix::net::UnixStream;
+
+    futures_lite::future::block_on(async {
+        let (local, mut remote) = UnixStream::pair().unwrap();
+        let mut session = Session::new((), async_io::Async::new(local).unwrap());
+
+        session.send_line("Hello World").await.unwrap();
+
+        let mut buf = [0; 12];
+        std::io::Read::read_exact(&mut remote, &mut buf).unwrap();
+        assert_eq!(&buf, b"Hello World\n");
+
+        std::io::Write::write_all(&mut remote, b"Hi there").unwrap();
+
+        let m = session.expect("there").await.unwrap();
+        assert_eq!(m.before(), b"Hi ");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_timeout_on_silent_stream() {
+    use expectrl::{Error, Session};
+    use std::{
+        os::unix::net::UnixStream,
+        time::{Duration, Instant},
+    };
+
+    let (local, _remote) = UnixStream::pair().unwrap();
+    let mut session = Session::new((), local);
+    session.set_expect_timeout(Some(Duration::from_millis(200)));
+
+    let now = Instant::now();
+    assert!(matches!(
+        session.expect("Hello"),
+        Err(Error::ExpectTimeout(_))
+    ));
+    assert!(now.elapsed() >= Duration::from_millis(200));
+    assert!(now.elapsed() < Duration::from_secs(2));
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn expect_timeout_on_silent_stream() {
+    use expectrl::{Error, Session};
+    use std::{
+        os::unix::net::UnixStream,
+        time::{Duration, Instant},
+    };
+
+    futures_lite::future::block_on(async {
+        let (local, _remote) = UnixStream::pair().unwrap();
+        let mut session = Session::new((), async_io::Async::new(local).unwrap());
+        session.set_expect_timeout(Some(Duration::from_millis(200)));
+
+        let now = Instant::now();
+        assert!(matches!(
+            session.expect("Hello").await,
+            Err(Error::ExpectTimeout(_))
+        ));
+        assert!(now.elapsed() >= Duration::from_millis(200));
+        assert!(now.elapsed() < Duration::from_secs(2));
+    })
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn cancelled_expect_keeps_data() {
+    use expectrl::Session;
+    use std::{io::Write, os::unix::net::UnixStream, time::Duration};
+
+    futures_lite::future::block_on(async {
+        let (local, mut remote) = UnixStream::pair().unwrap();
+        let mut session = Session::new((), async_io::Async::new(local).unwrap());
+        session.set_expect_timeout(None);
+
+        remote.write_all(b"Hello ").unwrap();
+
+        // The expect future is dropped while it waits for the rest of the data.
+        let cancelled = futures_lite::future::or(
+            async {
+                session.expect("World").await.unwrap();
+                false
+            },
+            async {
+                async_io::Timer::after(Duration::from_millis(200)).await;
+                true
+            },
+        )
+        .await;
+        assert!(cancelled);
+
+        remote.write_all(b"World").unwrap();
+
+        let m = session.expect("Hello World").await.unwrap();
+        assert_eq!(m.before(), b"");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn search_window() {
+    use expectrl::{Error, Session};
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    let (local, mut remote) = UnixStream::pair().unwrap();
+    let mut session = Session::new((), local);
+    session.set_expect_timeout(Some(Duration::from_millis(300)));
+    session.set_search_window(Some(5));
+
+    remote.write_all(b"Hello World").unwrap();
+
+    assert!(matches!(
+        session.expect("Hello World"),
+        Err(Error::ExpectTimeout(_))
+    ));
+
+    let m = session.expect("World").unwrap();
+    assert_eq!(m.before(), b"Hello ");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn search_window() {
+    use expectrl::{Error, Session};
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    futures_lite::future::block_on(async {
+        let (local, mut remote) = UnixStream::pair().unwrap();
+        let mut session = Session::new((), async_io::Async::new(local).unwrap());
+        session.set_expect_timeout(Some(Duration::from_millis(300)));
+        session.set_search_window(Some(5));
+
+        remote.write_all(b"Hello World").unwrap();
+
+        assert!(matches!(
+            session.expect("Hello World").await,
+            Err(Error::ExpectTimeout(_))
+        ));
+
+        let m = session.expect("World").await.unwrap();
+        assert_eq!(m.before(), b"Hello ");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn max_buffer_size_drops_oldest_bytes() {
+    use expectrl::{Error, Session};
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    let (local, mut remote) = UnixStream::pair().unwrap();
+    let mut session = Session::new((), local);
+    session.set_expect_timeout(Some(Duration::from_millis(300)));
+    session.set_max_buffer_size(Some(4));
+
+    remote.write_all(b"Hello World").unwrap();
+
+    assert!(matches!(
+        session.expect("Bye"),
+        Err(Error::ExpectTimeout(_))
+    ));
+    assert!(!session.is_matched("Hello").unwrap());
+
+    remote.write_all(b"Bye").unwrap();
+
+    let m = session.expect("Bye").unwrap();
+    assert_eq!(m.before(), b"ld");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn max_buffer_size_drops_oldest_bytes() {
+    use expectrl::{Error, Session};
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    futures_lite::future::block_on(async {
+        let (local, mut remote) = UnixStream::pair().unwrap();
+        let mut session = Session::new((), async_io::Async::new(local).unwrap());
+        session.set_expect_timeout(Some(Duration::from_millis(300)));
+        session.set_max_buffer_size(Some(4));
+
+        remote.write_all(b"Hello World").unwrap();
+
+        assert!(matches!(
+            session.expect("Bye").await,
+            Err(Error::ExpectTimeout(_))
+        ));
+        assert!(!session.is_matched("Hello").await.unwrap());
+
+        remote.write_all(b"Bye").unwrap();
+
+        let m = session.expect("Bye").await.unwrap();
+        assert_eq!(m.before(), b"ld");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn max_buffer_size_fails() {
+    use expectrl::{session::DiscardPolicy, Error, Session};
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    let (local, mut remote) = UnixStream::pair().unwrap();
+    let mut session = Session::new((), local);
+    session.set_max_buffer_size(Some(4));
+    session.set_discard_policy(DiscardPolicy::Fail);
+
+    remote.write_all(b"Hello World").unwrap();
+
+    let err = session.expect("Bye").unwrap_err();
+    assert!(matches!(err, Error::BufferOverflow(_)));
+
+    // Nothing is discarded.
+    session.set_max_buffer_size(None);
+    let m = session.expect("World").unwrap();
+    assert_eq!(m.before(), b"Hello ");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn max_buffer_size_fails() {
+    use expectrl::{session::DiscardPolicy, Error, Session};
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    futures_lite::future::block_on(async {
+        let (local, mut remote) = UnixStream::pair().unwrap();
+        let mut session = Session::new((), async_io::Async::new(local).unwrap());
+        session.set_max_buffer_size(Some(4));
+        session.set_discard_policy(DiscardPolicy::Fail);
+
+        remote.write_all(b"Hello World").unwrap();
+
+        let err = session.expect("Bye").await.unwrap_err();
+        assert!(matches!(err, Error::BufferOverflow(_)));
+
+        // Nothing is discarded.
+        session.set_max_buffer_size(None);
+        let m = session.expect("World").await.unwrap();
+        assert_eq!(m.before(), b"Hello ");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn wait_timeout() {
+    use expectrl::{Session, WaitStatus};
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "sleep 0.5; exit 3"]);
+    let session = Session::spawn(cmd).unwrap();
+
+    let status = session.wait_timeout(Duration::from_millis(100)).unwrap();
+    assert_eq!(status, None);
+
+    let status = session.wait_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(status, Some(WaitStatus::Exited(session.pid(), 3)));
+
+    // A status is left to be collected.
+    assert_eq!(
+        session.wait().unwrap(),
+        WaitStatus::Exited(session.pid(), 3)
+    );
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn wait_timeout() {
+    use expectrl::{Session, WaitStatus};
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 0.5; exit 3"]);
+        let session = Session::spawn(cmd).unwrap();
+
+        let status = session
+            .wait_timeout(Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert_eq!(status, None);
+
+        let status = session.wait_timeout(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(status, Some(WaitStatus::Exited(session.pid(), 3)));
+
+        // A status is left to be collected.
+        assert_eq!(
+            session.wait().unwrap(),
+            WaitStatus::Exited(session.pid(), 3)
+        );
+    })
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn session_over_buffer() {
+    use expectrl::{Eof, Session};
+    use std::io::Cursor;
+
+    let mut session = Session::new((), Cursor::new(b"123 456".to_vec()));
+
+    let m = session.expect(" ").unwrap();
+    assert_eq!(m.before(), b"123");
+
+    let m = session.expect(Eof).unwrap();
+    assert_eq!(m.first(), b"456");
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn query_reply_failure_keeps_output() {
+    use expectrl::{Eof, Session};
+    use std::io::{self, Cursor};
+
+    struct ClosedWriter;
+
+    impl Write for ClosedWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut session = Session::new((), Cursor::new(b"123\x1b[6n456".to_vec()));
+    session.enable_query_replies_with(ClosedWriter);
+
+    let m = session.expect(Eof).unwrap();
+    assert_eq!(m.first(), b"123\x1b[6n456");
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn spawn_with_stderr() {
+    use expectrl::{Eof, Session};
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo Hello; echo World >&2; echo Bye >&2"]);
+
+    let mut session = Session::spawn_with_stderr(cmd).unwrap();
+
+    let m = session.expect_stderr("World").unwrap();
+    assert_eq!(m.before(), b"");
+
+    let m = session.expect(Eof).unwrap();
+    assert_eq!(m.first(), b"Hello\r\n");
+
+    // stderr isn't attached to a pty so there's no \r\n translation.
+    let m = session.expect_stderr(Eof).unwrap();
+    assert_eq!(m.first(), b"\nBye\n");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn spawn_with_stderr() {
+    use expectrl::{Eof, Session};
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo Hello; echo World >&2; echo Bye >&2"]);
+
+        let mut session = Session::spawn_with_stderr(cmd).unwrap();
+
+        let m = session.expect_stderr("World").await.unwrap();
+        assert_eq!(m.before(), b"");
+
+        let m = session.expect(Eof).await.unwrap();
+        assert_eq!(m.first(), b"Hello\r\n");
+
+        // stderr isn't attached to a pty so there's no \r\n translation.
+        let m = session.expect_stderr(Eof).await.unwrap();
+        assert_eq!(m.first(), b"\nBye\n");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn check_stderr() {
+    use expectrl::Session;
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "echo Hello; echo World >&2; sleep 1"]);
+
+    let mut session = Session::spawn_with_stderr(cmd).unwrap();
+    thread::sleep(Duration::from_millis(500));
+
+    assert!(session.check_stderr("Hello").unwrap().is_empty());
+    assert_eq!(session.check_stderr("World").unwrap().first(), b"World");
+    assert_eq!(session.check("Hello").unwrap().first(), b"Hello");
+    assert!(session.check("World").unwrap().is_empty());
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn check_stderr() {
+    use expectrl::Session;
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo Hello; echo World >&2; sleep 1"]);
+
+        let mut session = Session::spawn_with_stderr(cmd).unwrap();
+        thread::sleep(Duration::from_millis(500));
+
+        assert!(session.check_stderr("Hello").await.unwrap().is_empty());
+        assert_eq!(
+            session.check_stderr("World").await.unwrap().first(),
+            b"World"
+        );
+        assert_eq!(session.check("Hello").await.unwrap().first(), b"Hello");
+        assert!(session.check("World").await.unwrap().is_empty());
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn expect_stderr_without_pipe() {
+    let mut session = spawn("echo Hello").unwrap();
+    assert!(matches!(
+        session.expect_stderr("Hello"),
+        Err(expectrl::Error::StderrNotCaptured)
+    ));
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn window_size() {
+    use expectrl::Session;
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "trap 'stty size' WINCH; echo ready; while true; do sleep 0.1; done",
+    ]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    assert_eq!(session.get_window_size().unwrap(), (80, 24));
+
+    session.expect("ready").unwrap();
+
+    session.set_window_size(100, 30).unwrap();
+    assert_eq!(session.get_window_size().unwrap(), (100, 30));
+
+    session.expect("30 100").unwrap();
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn window_size() {
+    use expectrl::Session;
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "trap 'stty size' WINCH; echo ready; while true; do sleep 0.1; done",
+        ]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        assert_eq!(session.get_window_size().unwrap(), (80, 24));
+
+        session.expect("ready").await.unwrap();
+
+        session.set_window_size(100, 30).unwrap();
+        assert_eq!(session.get_window_size().unwrap(), (100, 30));
+
+        session.expect("30 100").await.unwrap();
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn window_size_unchanged_still_notifies() {
+    use expectrl::Session;
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "trap 'echo resized' WINCH; echo ready; while true; do sleep 0.1; done",
+    ]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.expect("ready").unwrap();
+
+    session.set_window_size(80, 24).unwrap();
+    session.expect("resized").unwrap();
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn terminate_process_group() {
+    use expectrl::{Regex, Session, Signal, WaitStatus};
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "sleep 100 & echo \"child $!\"; wait"]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    let m = session.expect(Regex("child (\\d+)\r\n")).unwrap();
+    let child = parse_pid(m.matches()[1]);
+    assert!(is_alive(child));
+
+    let status = session.terminate(Duration::from_secs(1)).unwrap();
+    assert_eq!(
+        status,
+        WaitStatus::Signaled(session.pid(), Signal::SIGHUP, false)
+    );
+    assert!(exits_soon(child));
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn terminate_process_group() {
+    use expectrl::{Regex, Session, Signal, WaitStatus};
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 100 & echo \"child $!\"; wait"]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        let m = session.expect(Regex("child (\\d+)\r\n")).await.unwrap();
+        let child = parse_pid(m.matches()[1]);
+        assert!(is_alive(child));
+
+        let status = session.terminate(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(
+            status,
+            WaitStatus::Signaled(session.pid(), Signal::SIGHUP, false)
+        );
+        assert!(exits_soon(child));
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn terminate_escalates_to_kill() {
+    use expectrl::{Session, Signal, WaitStatus};
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "trap '' HUP TERM; echo ready; while true; do sleep 0.1; done",
+    ]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.expect("ready").unwrap();
+
+    let status = session.terminate(Duration::from_millis(300)).unwrap();
+    assert_eq!(
+        status,
+        WaitStatus::Signaled(session.pid(), Signal::SIGKILL, false)
+    );
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn terminate_waits_for_process_group() {
+    use expectrl::{Session, Signal, WaitStatus};
+    use std::process::Command;
+
+    let file = std::env::temp_dir().join(format!("expectrl-terminate-{}", std::process::id()));
+    let _ = std::fs::remove_file(&file);
+
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        &format!(
+            "(trap '' HUP TERM; echo ready; sleep 0.5; touch {}) & wait",
+            file.display()
+        ),
+    ]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.expect("ready").unwrap();
+
+    let status = session.terminate(Duration::from_secs(5)).unwrap();
+    assert_eq!(
+        status,
+        WaitStatus::Signaled(session.pid(), Signal::SIGHUP, false)
+    );
+    assert!(file.exists());
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn terminate_exited_process() {
+    use expectrl::{Eof, Session, WaitStatus};
+    use std::process::Command;
+
+    let mut session = Session::spawn(Command::new("true")).unwrap();
+    session.expect(Eof).unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    let status = session.terminate(Duration::from_secs(1)).unwrap();
+    assert_eq!(status, WaitStatus::Exited(session.pid(), 0));
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn terminate_after_wait_timeout() {
+    use expectrl::{Session, WaitStatus};
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "exit 3"]);
+    let mut session = Session::spawn(cmd).unwrap();
+
+    let status = session.wait_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(status, Some(WaitStatus::Exited(session.pid(), 3)));
+
+    let status = session.terminate(Duration::from_secs(1)).unwrap();
+    assert_eq!(status, WaitStatus::Exited(session.pid(), 3));
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn drop_policy() {
+    use expectrl::{session::DropPolicy, Regex, Session};
+    use std::process::Command;
+
+    for policy in [DropPolicy::default(), DropPolicy::Kill] {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "trap '' HUP TERM; sleep 100 & echo \"child $!\"; wait",
+        ]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        session.set_drop_policy(policy);
+
+        let m = session.expect(Regex("child (\\d+)\r\n")).unwrap();
+        let child = parse_pid(m.matches()[1]);
+        assert!(is_alive(child));
+
+        drop(session);
+
+        assert!(exits_soon(child));
+    }
+}
+
+#[cfg(unix)]
+fn parse_pid(bytes: &[u8]) -> i32 {
+    String::from_utf8_lossy(bytes).parse().unwrap()
+}
+
+/// Checks that a process exists and it's not a zombie,
+/// which may happen if it was reparented to an init which doesn't reap them.
+// A signal is delivered asynchronously so a process may still be running for a moment.
+#[cfg(unix)]
+fn exits_soon(pid: i32) -> bool {
+    let start = std::time::Instant::now();
+    while start.elapsed() < std::time::Duration::from_secs(1) {
+        if !is_alive(pid) {
+            return true;
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    false
+}
+
+#[cfg(unix)]
+fn is_alive(pid: i32) -> bool {
+    let exists = nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), None).is_ok();
+    if !exists {
+        return false;
+    }
+
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => !stat.contains(") Z "),
+        Err(_) => true,
+    }
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn spawn_with_quotes_and_envs() {
+    use expectrl::Eof;
+
+    let mut session = spawn(r#"NAME='John Doe' sh -c "echo \"Hello \$NAME\"""#).unwrap();
+    let m = session.expect(Eof).unwrap();
+    assert_eq!(m.first(), b"Hello John Doe\r\n");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn spawn_with_quotes_and_envs() {
+    use expectrl::Eof;
+
+    futures_lite::future::block_on(async {
+        let mut session = spawn(r#"NAME='John Doe' sh -c "echo \"Hello \$NAME\"""#).unwrap();
+        let m = session.expect(Eof).await.unwrap();
+        assert_eq!(m.first(), b"Hello John Doe\r\n");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn spawn_shell() {
+    use expectrl::Eof;
+
+    let mut session = expectrl::spawn_shell("echo 'Hello World' | tr a-z A-Z").unwrap();
+    let m = session.expect(Eof).unwrap();
+    assert_eq!(m.first(), b"HELLO WORLD\r\n");
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn spawn_shell() {
+    use expectrl::Eof;
+
+    futures_lite::future::block_on(async {
+        let mut session = expectrl::spawn_shell("echo 'Hello World' | tr a-z A-Z").unwrap();
+        let m = session.expect(Eof).await.unwrap();
+        assert_eq!(m.first(), b"HELLO WORLD\r\n");
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn query_replies() {
+    use expectrl::{Eof, Session};
+    use std::process::Command;
+
+    let mut cmd = Command::new("python");
+    cmd.arg("./tests/source/queries.py");
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.enable_screen(80, 24);
+    session.enable_query_replies().unwrap();
+
+    let m = session.expect(Eof).unwrap();
+    let output = String::from_utf8_lossy(m.first());
+    assert!(output.contains(r"position: b'\x1b[1;6R'"), "{:?}", output);
+    assert!(
+        output.contains(r"attributes: b'\x1b[?62;22c'"),
+        "{:?}",
+        output
+    );
+    assert!(
+        output.contains(r"background: b'\x1b]11;rgb:0000/0000/0000\x1b\\'"),
+        "{:?}",
+        output
+    );
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn query_replies() {
+    use expectrl::{Eof, Session};
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("python");
+        cmd.arg("./tests/source/queries.py");
+
+        let mut session = Session::spawn(cmd).unwrap();
+        session.enable_screen(80, 24);
+        session.enable_query_replies().unwrap();
+
+        let m = session.expect(Eof).await.unwrap();
+        let output = String::from_utf8_lossy(m.first());
+        assert!(output.contains(r"position: b'\x1b[1;6R'"), "{:?}", output);
+        assert!(
+            output.contains(r"attributes: b'\x1b[?62;22c'"),
+            "{:?}",
+            output
+        );
+        assert!(
+            output.contains(r"background: b'\x1b]11;rgb:0000/0000/0000\x1b\\'"),
+            "{:?}",
+            output
+        );
+    })
+}
+
+/// A writer which isn't ready on a first write.
+#[cfg(unix)]
+struct BusyOnceWriter<W> {
+    writer: W,
+    busy: bool,
+}
+
+#[cfg(unix)]
+impl<W: std::io::Write> std::io::Write for BusyOnceWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.busy {
+            self.busy = false;
+            return Err(std::io::ErrorKind::WouldBlock.into());
+        }
+
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn query_reply_is_retried_while_waiting() {
+    use expectrl::Session;
+    use std::process::Command;
+
+    let mut cmd = Command::new("sh");
+    cmd.args([
+        "-c",
+        "stty -icanon; printf '\\033[6n'; head -c 6 > /dev/null; echo done",
+    ]);
+
+    let mut session = Session::spawn(cmd).unwrap();
+    let writer = session.get_pty_stream().unwrap();
+    session.enable_query_replies_with(BusyOnceWriter { writer, busy: true });
+    session.set_expect_timeout(Some(Duration::from_secs(5)));
+
+    session.expect("done").unwrap();
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn query_reply_is_retried_while_waiting() {
+    use expectrl::Session;
+    use std::process::Command;
+
+    futures_lite::future::block_on(async {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "stty -icanon; printf '\\033[6n'; head -c 6 > /dev/null; echo done",
+        ]);
+
+        let mut session = Session::spawn(cmd).unwrap();
+        let writer = session.get_pty_stream().unwrap();
+        session.enable_query_replies_with(BusyOnceWriter { writer, busy: true });
+        session.set_expect_timeout(Some(Duration::from_secs(5)));
+
+        session.expect("done").await.unwrap();
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn query_is_not_answered_by_default() {
+    use expectrl::{Error, Session};
+    use std::process::Command;
+
+    let mut cmd = Command::new("python");
+    cmd.arg("./tests/source/queries.py");
+
+    let mut session = Session::spawn(cmd).unwrap();
+    session.set_expect_timeout(Some(Duration::from_millis(500)));
+    assert!(matches!(
+        session.expect("position:"),
+        Err(Error::ExpectTimeout(_))
+    ));
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn send_keys() {
+    use expectrl::{Key, KeyCode, KeyMap};
+
+    let mut session = spawn("cat -v").unwrap();
+    session.set_key_map(KeyMap::xterm());
+    session.send_keys("<Up><C-a>x<S-F5>").unwrap();
+    session.send_key(Key::new(KeyCode::Enter)).unwrap();
+
+    session.expect("^[[A^Ax^[[15;2~\r\n").unwrap();
+}
+
+#[cfg(unix)]
+#[cfg(feature = "async")]
+#[test]
+fn send_keys() {
+    use expectrl::{Key, KeyCode, KeyMap};
+
+    futures_lite::future::block_on(async {
+        let mut session = spawn("cat -v").unwrap();
+        session.set_key_map(KeyMap::xterm());
+        session.send_keys("<Up><C-a>x<S-F5>").await.unwrap();
+        session.send_key(Key::new(KeyCode::Enter)).await.unwrap();
+
+        session.expect("^[[A^Ax^[[15;2~\r\n").await.unwrap();
+    })
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn send_keys_with_wrong_notation() {
+    use expectrl::{Key, KeyCode, KeyMap};
+
+    let mut session = spawn("cat").unwrap();
+    session.set_key_map(KeyMap::xterm());
+    let err = session.send_keys("abc<Unknown>").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    // A key without a sequence.
+    let err = session.send_keys("abc<F20>").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = session.send_key(Key::new(KeyCode::F(0))).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    // Nothing was sent.
+    session.send_line("def").unwrap();
+    let m = session.expect("def").unwrap();
+    assert_eq!(m.before(), b"");
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn send_keys_use_xterm_sequences_if_term_is_not_set() {
+    use expectrl::{KeyMap, Session, SessionBuilder};
+    use std::process::Command;
+
+    let mut cmd = Command::new("cat");
+    cmd.env_remove("TERM");
+    let session = Session::spawn(cmd).unwrap();
+    assert_eq!(session.get_key_map(), &KeyMap::xterm());
+
+    let session = SessionBuilder::new("cat").env_clear().spawn().unwrap();
+    assert_eq!(session.get_key_map(), &KeyMap::xterm());
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn send_keys_use_inherited_term() {
+    use expectrl::{KeyMap, Session};
+    use std::process::Command;
+
+    let key_map = std::env::var("TERM")
+        .ok()
+        .and_then(|term| KeyMap::from_terminfo(&term).ok())
+        .unwrap_or_default();
+
+    let session = Session::spawn(Command::new("cat")).unwrap();
+    assert_eq!(session.get_key_map(), &key_map);
+}
+
+#[cfg(unix)]
+#[cfg(not(feature = "async"))]
+#[test]
+fn send_keys_encoded_for_term() {
+    use expectrl::{KeyMap, Session};
+    use std::process::Command;
+
+    // Not every system has a terminfo database.
+    let key_map = match KeyMap::from_terminfo("xterm") {
+        Ok(key_map) => key_map,
+        Err(_) => return,
+    };
+
+    let mut cmd = Command::new("cat");
+    cmd.arg("-v").env("TERM", "xterm");
+
+    let mut session = Session::spawn(cmd).unwrap();
+    assert_eq!(session.get_key_map(), &key_map);
+
+    session.send_keys("<Up><CR>").unwrap();
+    session.expect("^[OA\r\n").unwrap();
+}
@@ -0,0 +1,37 @@
+#!/usr/bin/env python3
+
+# Asks a terminal a few questions and prints its answers.
+
+import os
+import sys
+import tty
+
+
+def ask(query: str, end: bytes) -> bytes:
+    """Write a query and wait for a reply which ends with `end`."""
+    sys.stdout.write(query)
+    sys.stdout.flush()
+
+    reply = b""
+    while not reply.endswith(end):
+        reply += os.read(sys.stdin.fileno(), 1)
+
+    return reply
+
+
+def main():
+    # Replies don't end with a newline, so a line discipline must not wait for it.
+    tty.setcbreak(sys.stdin.fileno())
+
+    position = ask("Hello\033[6n", b"R")
+    attributes = ask("\033[c", b"c")
+    background = ask("\033]11;?\033\\", b"\033\\")
+
+    print()
+    print("position:", position)
+    print("attributes:", attributes)
+    print("background:", background)
+
+
+if __name__ == "__main__":
+    main()